@@ -33,6 +33,7 @@ pub fn derive(input: TokenStream) -> TokenStream {
 
 fn named_fields(ident: Ident, input: FieldsNamed) -> TokenStream {
     let mut conversions = quote! {};
+    let mut identifiers = vec![];
 
     input.named.into_iter().for_each(
         |Field {
@@ -47,6 +48,10 @@ fn named_fields(ident: Ident, input: FieldsNamed) -> TokenStream {
                 field_str = rename;
             }
 
+            if !many {
+                identifiers.push(field_str.clone());
+            }
+
             let conversion = if many {
                 quote! {
                     let fs = <#ty as ToNamespaceMap>::to_ns_map(self.#field);
@@ -88,6 +93,11 @@ fn named_fields(ident: Ident, input: FieldsNamed) -> TokenStream {
                     map
                 }
             }
+            impl #ident {
+                /// The identifiers of the data elements that this struct maps to, excluding those
+                /// produced by `many` fields.
+                pub const ELEMENT_IDENTIFIERS: &'static [&'static str] = &[#(#identifiers),*];
+            }
             impl ToCbor for #ident {
                 fn to_cbor(self) -> Value {
                     let map = self.to_ns_map()
@@ -26,6 +26,18 @@ pub struct KeyAuthorizations {
     pub data_elements: Option<NonEmptyMap<String, NonEmptyVec<String>>>,
 }
 
+impl DeviceKeyInfo {
+    /// Determine whether the device key is permitted to sign over the designated element.
+    ///
+    /// If no key authorizations are present then the device key may not sign over any elements.
+    pub fn permitted(&self, namespace: &String, element_identifier: &String) -> bool {
+        self.key_authorizations
+            .as_ref()
+            .map(|authorizations| authorizations.permitted(namespace, element_identifier))
+            .unwrap_or(false)
+    }
+}
+
 impl KeyAuthorizations {
     /// If a namespace is present in authorized namespaces then it cannot be present in
     /// authorized data elements.
@@ -52,7 +64,9 @@ impl KeyAuthorizations {
     /// Determine whether the key is permitted to sign over the designated element.
     pub fn permitted(&self, namespace: &String, element_identifier: &String) -> bool {
         if let Some(namespaces) = self.namespaces.as_ref() {
-            return namespaces.contains(namespace);
+            if namespaces.contains(namespace) {
+                return true;
+            }
         }
         if let Some(namespaces) = self.data_elements.as_ref() {
             if let Some(data_elements) = namespaces.get(namespace).as_ref() {
@@ -68,3 +82,39 @@ pub enum Error {
     #[error("namespace '{0}' cannot be present in both authorized_namespaces and authorized_data_elements")]
     DoubleAuthorized(String),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn permitted() {
+        let authorizations = KeyAuthorizations {
+            namespaces: Some(NonEmptyVec::new("namespace_1".to_string())),
+            data_elements: Some(NonEmptyMap::new(
+                "namespace_2".to_string(),
+                NonEmptyVec::new("element_1".to_string()),
+            )),
+        };
+
+        assert!(authorizations.permitted(&"namespace_1".into(), &"element_1".into()));
+        assert!(authorizations.permitted(&"namespace_1".into(), &"element_2".into()));
+        assert!(authorizations.permitted(&"namespace_2".into(), &"element_1".into()));
+        assert!(!authorizations.permitted(&"namespace_2".into(), &"element_2".into()));
+        assert!(!authorizations.permitted(&"namespace_3".into(), &"element_1".into()));
+    }
+
+    #[test]
+    fn nothing_permitted_without_authorizations() {
+        let device_key_info = DeviceKeyInfo {
+            device_key: CoseKey::OKP {
+                crv: cose_key::OKPCurve::Ed25519,
+                x: vec![],
+            },
+            key_authorizations: None,
+            key_info: None,
+        };
+
+        assert!(!device_key_info.permitted(&"namespace_1".into(), &"element_1".into()));
+    }
+}
@@ -0,0 +1,199 @@
+//! Normalisation of remotely produced signatures to the encoding required by COSE.
+//!
+//! COSE requires ECDSA signatures to be the fixed-length concatenation of `r` and `s`
+//! ([RFC 8152 § 8.1](https://www.rfc-editor.org/rfc/rfc8152#section-8.1)), whereas most KMS and
+//! HSM APIs return the ASN.1 DER encoding `SEQUENCE { r INTEGER, s INTEGER }`.
+use cose_rs::algorithm::Algorithm;
+use serde::{Deserialize, Serialize};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum Error {
+    #[error(
+        "expected a {expected} byte r || s or DER encoded ECDSA signature, received {actual} bytes"
+    )]
+    InvalidLength { expected: usize, actual: usize },
+    #[error("DER encoded ECDSA signature has a component longer than {0} bytes")]
+    ComponentTooLong(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+/// The encoding that a signature must have in a COSE structure.
+pub enum SignatureFormat {
+    /// An ECDSA signature, encoded as `r || s` with each component of the given length in bytes.
+    Ecdsa { component_len: usize },
+    /// Any other signature, which is used unchanged.
+    #[default]
+    Other,
+}
+
+impl From<Algorithm> for SignatureFormat {
+    fn from(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::ES256 => Self::Ecdsa { component_len: 32 },
+            Algorithm::ES384 => Self::Ecdsa { component_len: 48 },
+            Algorithm::ES512 => Self::Ecdsa { component_len: 66 },
+            _ => Self::Other,
+        }
+    }
+}
+
+impl SignatureFormat {
+    /// Convert a signature to the encoding required by COSE.
+    ///
+    /// ECDSA signatures may be given either as `r || s` or DER encoded, and any other length is
+    /// rejected, as it would produce a COSE structure that cannot be verified.
+    pub fn normalize(&self, signature: Vec<u8>) -> Result<Vec<u8>> {
+        let component_len = match self {
+            Self::Ecdsa { component_len } => *component_len,
+            Self::Other => return Ok(signature),
+        };
+
+        // A raw signature could only parse as DER by an astronomically unlikely coincidence, as
+        // every length field would have to agree, so DER is tried first.
+        if let Some((r, s)) = parse_der(&signature) {
+            let mut raw = vec![0; 2 * component_len];
+            for (component, out) in [r, s].into_iter().zip(raw.chunks_mut(component_len)) {
+                if component.len() > component_len {
+                    return Err(Error::ComponentTooLong(component_len));
+                }
+                out[component_len - component.len()..].copy_from_slice(component);
+            }
+            return Ok(raw);
+        }
+
+        if signature.len() != 2 * component_len {
+            return Err(Error::InvalidLength {
+                expected: 2 * component_len,
+                actual: signature.len(),
+            });
+        }
+        Ok(signature)
+    }
+}
+
+/// Parse a DER encoded ECDSA signature, returning `r` and `s` without leading zeroes.
+fn parse_der(der: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (tag, contents, rest) = parse_tlv(der)?;
+    if tag != 0x30 || !rest.is_empty() {
+        return None;
+    }
+    let (tag, r, contents) = parse_tlv(contents)?;
+    if tag != 0x02 {
+        return None;
+    }
+    let (tag, s, contents) = parse_tlv(contents)?;
+    if tag != 0x02 || !contents.is_empty() {
+        return None;
+    }
+    Some((parse_unsigned(r)?, parse_unsigned(s)?))
+}
+
+/// Parse a tag, length and value, returning the tag, the value and the remaining bytes.
+fn parse_tlv(bytes: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, bytes) = bytes.split_first()?;
+    let (&len, bytes) = bytes.split_first()?;
+    let (len, bytes) = match len {
+        0..=0x7f => (len as usize, bytes),
+        // The long form is only needed for P-521, whose signatures exceed 127 bytes.
+        0x81 => {
+            let (&len, bytes) = bytes.split_first()?;
+            if len < 0x80 {
+                return None;
+            }
+            (len as usize, bytes)
+        }
+        _ => return None,
+    };
+    if bytes.len() < len {
+        return None;
+    }
+    let (value, rest) = bytes.split_at(len);
+    Some((tag, value, rest))
+}
+
+/// Strip the sign byte from a positive DER integer.
+fn parse_unsigned(integer: &[u8]) -> Option<&[u8]> {
+    match integer {
+        [] => None,
+        // Negative integers are not valid signature components.
+        [first, ..] if first & 0x80 != 0 => None,
+        [0, rest @ ..] if rest.first().is_some_and(|b| b & 0x80 != 0) => Some(rest),
+        // A leading zero is only permitted in DER when it is needed for the sign.
+        [0, _, ..] => None,
+        _ => Some(integer),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use p256::ecdsa::{Signature, SigningKey};
+    use signature::Signer;
+
+    fn sign(message: &[u8]) -> Signature {
+        let key = SigningKey::from_slice(&[1; 32]).unwrap();
+        key.sign(message)
+    }
+
+    #[test]
+    fn raw() {
+        let signature = sign(b"raw").to_vec();
+        let format = SignatureFormat::from(Algorithm::ES256);
+        assert_eq!(format.normalize(signature.clone()).unwrap(), signature);
+    }
+
+    #[test]
+    fn der() {
+        let format = SignatureFormat::from(Algorithm::ES256);
+        // Vary the message so that short and sign-padded components are covered.
+        for i in 0..64u8 {
+            let signature = sign(&[i]);
+            let der = signature.to_der().as_bytes().to_vec();
+            assert_eq!(format.normalize(der).unwrap(), signature.to_vec());
+        }
+    }
+
+    #[test]
+    fn p521_der() {
+        let format = SignatureFormat::from(Algorithm::ES512);
+        let r = [0x01; 66];
+        let s = [0x7f; 65];
+        let mut der = vec![0x30, 0x81, 135, 0x02, 66];
+        der.extend_from_slice(&r);
+        der.extend_from_slice(&[0x02, 65]);
+        der.extend_from_slice(&s);
+        let raw = format.normalize(der).unwrap();
+        assert_eq!(&raw[..66], &r);
+        assert_eq!(raw[66], 0);
+        assert_eq!(&raw[67..], &s);
+    }
+
+    #[test]
+    fn malformed() {
+        let format = SignatureFormat::from(Algorithm::ES256);
+        assert_eq!(
+            format.normalize(vec![0; 63]),
+            Err(Error::InvalidLength {
+                expected: 64,
+                actual: 63
+            })
+        );
+        let mut der = sign(b"truncated").to_der().as_bytes().to_vec();
+        der.pop();
+        assert!(format.normalize(der).is_err());
+
+        let mut der = vec![0x30, 70, 0x02, 33, 0x00];
+        der.extend_from_slice(&[0x80; 32]);
+        der.extend_from_slice(&[0x02, 33, 0x01]);
+        der.extend_from_slice(&[0; 32]);
+        assert_eq!(format.normalize(der), Err(Error::ComponentTooLong(32)));
+    }
+
+    #[test]
+    fn other() {
+        let format = SignatureFormat::from(Algorithm::EdDSA);
+        assert_eq!(format.normalize(vec![1, 2, 3]).unwrap(), vec![1, 2, 3]);
+    }
+}
@@ -8,6 +8,7 @@ pub mod issuer_signed;
 pub mod mso;
 pub mod namespaces;
 pub mod session;
+pub mod status_list;
 pub mod traits;
 pub mod validity_info;
 
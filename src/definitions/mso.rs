@@ -1,4 +1,4 @@
-use crate::definitions::{helpers::ByteStr, DeviceKeyInfo, ValidityInfo};
+use crate::definitions::{helpers::ByteStr, status_list::Status, DeviceKeyInfo, ValidityInfo};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
@@ -18,6 +18,8 @@ pub struct Mso {
     pub device_key_info: DeviceKeyInfo,
     pub doc_type: String,
     pub validity_info: ValidityInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
 }
 
 #[derive(Clone, Debug, Copy, Deserialize, Serialize)]
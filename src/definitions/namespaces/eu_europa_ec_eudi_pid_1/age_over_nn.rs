@@ -0,0 +1,42 @@
+use crate::definitions::{
+    namespaces::org_iso_18013_5_1::AgeOver,
+    traits::{FromJsonError, FromJsonMap, ToNamespaceMap},
+};
+use serde_cbor::Value as Cbor;
+use serde_json::{Map, Value as Json};
+use std::{collections::BTreeMap, ops::Deref};
+
+/// `age_over_NN` in the eu.europa.ec.eudi.pid.1 namespace.
+///
+/// `age_over_18` is mandatory in this namespace and has a field of its own, so it is left out.
+#[derive(Debug, Clone)]
+pub struct AgeOverNN(AgeOver);
+
+const AGE_OVER_18: &str = "age_over_18";
+
+impl Deref for AgeOverNN {
+    type Target = AgeOver;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromJsonMap for AgeOverNN {
+    fn from_map(m: &Map<String, Json>) -> Result<Self, FromJsonError> {
+        let m = m
+            .iter()
+            .filter(|(k, _)| *k != AGE_OVER_18)
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        AgeOver::from_map(&m).map(Self)
+    }
+}
+
+impl ToNamespaceMap for AgeOverNN {
+    fn to_ns_map(self) -> BTreeMap<String, Cbor> {
+        let mut map = self.0.to_ns_map();
+        map.remove(AGE_OVER_18);
+        map
+    }
+}
@@ -0,0 +1,242 @@
+mod age_over_nn;
+mod nationality;
+
+pub use super::fulldate::FullDate;
+pub use super::org_iso_18013_5_1::{
+    AgeOver, Alpha2, IssuingJurisdiction, Sex, TDate, TDateOrFullDate,
+};
+pub use age_over_nn::AgeOverNN;
+pub use nationality::Nationality;
+
+use crate::{
+    definitions::{
+        namespaces::registry::{ElementType, NamespaceSchema},
+        traits::Namespace,
+    },
+    macros::{FromJson, ToCbor},
+};
+
+/// The `eu.europa.ec.eudi.pid.1` namespace, as per the Person Identification Data rulebook of
+/// the EUDI Wallet Architecture and Reference Framework.
+#[derive(Debug, Clone, FromJson, ToCbor)]
+pub struct EuEuropaEcEudiPid1 {
+    pub family_name: String,
+    pub given_name: String,
+    pub birth_date: FullDate,
+    pub age_over_18: bool,
+    pub nationality: Nationality,
+    pub issuance_date: TDateOrFullDate,
+    pub expiry_date: TDateOrFullDate,
+    pub issuing_authority: String,
+    pub issuing_country: Alpha2,
+    /// Every `age_over_NN` element other than `age_over_18`.
+    #[isomdl(many)]
+    pub age_over_nn: AgeOverNN,
+    pub age_in_years: Option<u32>,
+    pub age_birth_year: Option<u32>,
+    pub family_name_birth: Option<String>,
+    pub given_name_birth: Option<String>,
+    pub birth_place: Option<String>,
+    pub birth_country: Option<Alpha2>,
+    pub birth_state: Option<String>,
+    pub birth_city: Option<String>,
+    pub resident_address: Option<String>,
+    pub resident_country: Option<Alpha2>,
+    pub resident_state: Option<String>,
+    pub resident_city: Option<String>,
+    pub resident_postal_code: Option<String>,
+    pub resident_street: Option<String>,
+    pub resident_house_number: Option<String>,
+    pub gender: Option<Sex>,
+    pub document_number: Option<String>,
+    pub administrative_number: Option<String>,
+    #[isomdl(dynamic_parse)]
+    pub issuing_jurisdiction: Option<IssuingJurisdiction>,
+}
+
+impl Namespace for EuEuropaEcEudiPid1 {
+    const NAMESPACE: &'static str = "eu.europa.ec.eudi.pid.1";
+}
+
+impl EuEuropaEcEudiPid1 {
+    /// The schema of the data elements in this namespace, as per the Person
+    /// Identification Data rulebook.
+    pub fn schema() -> NamespaceSchema {
+        NamespaceSchema::new()
+            .mandatory("family_name", ElementType::Text)
+            .mandatory("given_name", ElementType::Text)
+            .mandatory("birth_date", ElementType::FullDate)
+            .mandatory("age_over_18", ElementType::Bool)
+            .mandatory("nationality", ElementType::Any)
+            .mandatory("issuance_date", ElementType::TDateOrFullDate)
+            .mandatory("expiry_date", ElementType::TDateOrFullDate)
+            .mandatory("issuing_authority", ElementType::Text)
+            .mandatory("issuing_country", ElementType::Text)
+            .optional("age_over_*", ElementType::Bool)
+            .optional("age_in_years", ElementType::Uint)
+            .optional("age_birth_year", ElementType::Uint)
+            .optional("family_name_birth", ElementType::Text)
+            .optional("given_name_birth", ElementType::Text)
+            .optional("birth_place", ElementType::Text)
+            .optional("birth_country", ElementType::Text)
+            .optional("birth_state", ElementType::Text)
+            .optional("birth_city", ElementType::Text)
+            .optional("resident_address", ElementType::Text)
+            .optional("resident_country", ElementType::Text)
+            .optional("resident_state", ElementType::Text)
+            .optional("resident_city", ElementType::Text)
+            .optional("resident_postal_code", ElementType::Text)
+            .optional("resident_street", ElementType::Text)
+            .optional("resident_house_number", ElementType::Text)
+            .optional("gender", ElementType::Uint)
+            .optional("document_number", ElementType::Text)
+            .optional("administrative_number", ElementType::Text)
+            .optional("issuing_jurisdiction", ElementType::Text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::traits::{FromJson, FromJsonError, ToCbor, ToNamespaceMap};
+    use serde_cbor::Value as Cbor;
+
+    #[test]
+    fn all() {
+        let json = serde_json::json!({
+          "family_name":"Mustermann",
+          "given_name":"Erika",
+          "birth_date":"1964-08-12",
+          "age_over_18":true,
+          "nationality":"DE",
+          "issuance_date":"2024-01-01",
+          "expiry_date":"2029-01-01T00:00:00Z",
+          "issuing_authority":"Bundesdruckerei",
+          "issuing_country":"DE",
+          "age_over_21":true,
+          "age_over_65":false,
+          "age_in_years":59,
+          "age_birth_year":1964,
+          "family_name_birth":"Gabler",
+          "given_name_birth":"Erika",
+          "birth_place":"Berlin",
+          "birth_country":"DE",
+          "birth_state":"Berlin",
+          "birth_city":"Berlin",
+          "resident_address":"Heidestraße 17, 51147 Köln",
+          "resident_country":"DE",
+          "resident_state":"Nordrhein-Westfalen",
+          "resident_city":"Köln",
+          "resident_postal_code":"51147",
+          "resident_street":"Heidestraße",
+          "resident_house_number":"17",
+          "gender":2,
+          "document_number":"T22000129",
+          "administrative_number":"123456789",
+          "issuing_jurisdiction":"DE-BE"
+        });
+
+        let ns = EuEuropaEcEudiPid1::from_json(&json).unwrap();
+        EuEuropaEcEudiPid1::schema()
+            .validate(
+                EuEuropaEcEudiPid1::NAMESPACE,
+                &ns.clone().to_ns_map(),
+                time::macros::date!(2024 - 01 - 01),
+            )
+            .unwrap();
+
+        assert!(ns.age_over_18);
+        assert!(ns.age_over_nn.get(&18.try_into().unwrap()).is_none());
+        assert!(ns.age_over_nn.get(&21.try_into().unwrap()).unwrap());
+        assert!(!ns.age_over_nn.get(&65.try_into().unwrap()).unwrap());
+
+        assert!(ns.age_in_years.is_some());
+        assert!(ns.age_birth_year.is_some());
+        assert!(ns.family_name_birth.is_some());
+        assert!(ns.given_name_birth.is_some());
+        assert!(ns.birth_place.is_some());
+        assert!(ns.birth_country.is_some());
+        assert!(ns.birth_state.is_some());
+        assert!(ns.birth_city.is_some());
+        assert!(ns.resident_address.is_some());
+        assert!(ns.resident_country.is_some());
+        assert!(ns.resident_state.is_some());
+        assert!(ns.resident_city.is_some());
+        assert!(ns.resident_postal_code.is_some());
+        assert!(ns.resident_street.is_some());
+        assert!(ns.resident_house_number.is_some());
+        assert!(ns.gender.is_some());
+        assert!(ns.document_number.is_some());
+        assert!(ns.administrative_number.is_some());
+        assert!(ns.issuing_jurisdiction.is_some());
+
+        let cbor = match ns.to_cbor() {
+            Cbor::Map(map) => map,
+            _ => panic!("expected a map"),
+        };
+        assert_eq!(
+            cbor[&Cbor::Text("nationality".into())],
+            Cbor::Text("DE".into())
+        );
+        assert_eq!(cbor[&Cbor::Text("age_over_18".into())], Cbor::Bool(true));
+    }
+
+    #[test]
+    fn age_over_18_field() {
+        let mut json = serde_json::json!({
+          "family_name":"Mustermann",
+          "given_name":"Erika",
+          "birth_date":"1964-08-12",
+          "age_over_18":true,
+          "nationality":"DE",
+          "issuance_date":"2024-01-01",
+          "expiry_date":"2029-01-01",
+          "issuing_authority":"Bundesdruckerei",
+          "issuing_country":"DE"
+        });
+
+        let mut ns = EuEuropaEcEudiPid1::from_json(&json).unwrap();
+        ns.age_over_18 = false;
+        let map = ns.to_ns_map();
+        assert_eq!(map["age_over_18"], Cbor::Bool(false));
+
+        json["age_over_18"] = false.into();
+        let ns = EuEuropaEcEudiPid1::from_json(&json).unwrap();
+        assert!(ns.age_over_nn.is_empty());
+    }
+
+    #[test]
+    fn mandatory() {
+        let json = serde_json::json!({
+          "family_name":"Mustermann",
+          "given_name":"Erika",
+          "birth_date":"1964-08-12",
+          "nationality":["DE", "XX"],
+          "issuance_date":"2024-01-01",
+          "expiry_date":"2029-01-01",
+          "issuing_authority":"Bundesdruckerei",
+          "issuing_country":"DE"
+        });
+
+        let errors = match EuEuropaEcEudiPid1::from_json(&json) {
+            Err(FromJsonError::Multiple(errors)) => errors,
+            other => panic!("expected multiple errors, got {other:?}"),
+        };
+        let fields: Vec<_> = errors
+            .iter()
+            .map(|error| match error {
+                FromJsonError::WithContext(field, _) => *field,
+                error => panic!("unexpected error: {error}"),
+            })
+            .collect();
+        assert_eq!(fields, ["age_over_18", "nationality"]);
+    }
+
+    #[test]
+    fn schema_matches_fields() {
+        crate::definitions::namespaces::registry::assert_schema_covers(
+            &EuEuropaEcEudiPid1::schema(),
+            EuEuropaEcEudiPid1::ELEMENT_IDENTIFIERS,
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use crate::definitions::{
+    helpers::NonEmptyVec,
+    namespaces::org_iso_18013_5_1::Alpha2,
+    traits::{FromJson, FromJsonError},
+};
+use serde_cbor::Value as Cbor;
+use serde_json::Value as Json;
+
+/// `nationality` in the eu.europa.ec.eudi.pid.1 namespace.
+///
+/// Earlier versions of the PID rulebook encode a single nationality, later versions an array of
+/// every nationality of the holder. Either form is accepted and preserved.
+#[derive(Debug, Clone)]
+pub enum Nationality {
+    Single(Alpha2),
+    Multiple(NonEmptyVec<Alpha2>),
+}
+
+impl From<Nationality> for Cbor {
+    fn from(n: Nationality) -> Cbor {
+        match n {
+            Nationality::Single(a) => a.into(),
+            Nationality::Multiple(v) => {
+                Cbor::Array(v.into_inner().into_iter().map(Into::into).collect())
+            }
+        }
+    }
+}
+
+impl FromJson for Nationality {
+    fn from_json(v: &Json) -> Result<Self, FromJsonError> {
+        match v {
+            Json::Array(_) => NonEmptyVec::from_json(v).map(Self::Multiple),
+            _ => Alpha2::from_json(v).map(Self::Single),
+        }
+    }
+}
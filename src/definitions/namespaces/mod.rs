@@ -1,5 +1,12 @@
+pub mod eu_europa_ec_eudi_pid_1;
 pub mod org_iso_18013_5_1;
 pub mod org_iso_18013_5_1_aamva;
+pub mod org_iso_23220_1;
+pub mod org_iso_23220_photoid_1;
+pub mod org_iso_7367_1;
+pub mod org_micov_attestation_1;
+pub mod org_micov_vtr_1;
+pub mod registry;
 
 mod fulldate;
 mod latin1;
@@ -23,9 +23,16 @@ pub use tdate::{TDate, TDateOrFullDate};
 pub use un_distinguishing_sign::UNDistinguishingSign;
 
 use crate::{
-    definitions::helpers::ByteStr,
+    definitions::{
+        helpers::ByteStr,
+        namespaces::registry::{ElementSchema, ElementType, NamespaceSchema},
+        traits::Namespace,
+    },
     macros::{FromJson, ToCbor},
 };
+use serde_cbor::Value as CborValue;
+use std::collections::BTreeMap;
+use time::{macros::format_description, Date};
 
 /// The `org.iso.18013.5.1` namespace.
 #[derive(Debug, Clone, FromJson, ToCbor)]
@@ -68,10 +75,104 @@ pub struct OrgIso1801351 {
     pub signature_usual_mark: Option<ByteStr>,
 }
 
+impl Namespace for OrgIso1801351 {
+    const NAMESPACE: &'static str = "org.iso.18013.5.1";
+}
+
+impl OrgIso1801351 {
+    /// The schema of the data elements in this namespace, as per ISO/IEC 18013-5 Table 5.
+    pub fn schema() -> NamespaceSchema {
+        NamespaceSchema::new()
+            .mandatory("family_name", ElementType::Text)
+            .mandatory("given_name", ElementType::Text)
+            .mandatory("birth_date", ElementType::FullDate)
+            .mandatory("issue_date", ElementType::TDateOrFullDate)
+            .mandatory("expiry_date", ElementType::TDateOrFullDate)
+            .mandatory("issuing_country", ElementType::Text)
+            .mandatory("issuing_authority", ElementType::Text)
+            .mandatory("document_number", ElementType::Text)
+            .mandatory("portrait", ElementType::Bytes)
+            .mandatory("driving_privileges", ElementType::Array)
+            .mandatory("un_distinguishing_sign", ElementType::Text)
+            .optional("administrative_number", ElementType::Text)
+            .optional("sex", ElementType::Uint)
+            .optional("height", ElementType::Uint)
+            .optional("weight", ElementType::Uint)
+            .optional("eye_colour", ElementType::Text)
+            .optional("hair_colour", ElementType::Text)
+            .optional("birth_place", ElementType::Text)
+            .optional("resident_address", ElementType::Text)
+            .optional("portrait_capture_date", ElementType::TDate)
+            .optional("age_in_years", ElementType::Uint)
+            .optional("age_birth_year", ElementType::Uint)
+            .optional("age_over_*", ElementType::Bool)
+            .optional("issuing_jurisdiction", ElementType::Text)
+            .optional("nationality", ElementType::Text)
+            .optional("resident_city", ElementType::Text)
+            .optional("resident_state", ElementType::Text)
+            .optional("resident_postal_code", ElementType::Text)
+            .optional("resident_country", ElementType::Text)
+            // Biometric templates are for matching the holder during the transaction only.
+            .element(
+                "biometric_template_*",
+                ElementSchema::optional(ElementType::Bytes).retention_prohibited(),
+            )
+            .optional("family_name_national_character", ElementType::Text)
+            .optional("given_name_national_character", ElementType::Text)
+            .optional("signature_usual_mark", ElementType::Bytes)
+            .check(check_age_over)
+    }
+}
+
+/// Check that each `age_over_NN` element agrees with `birth_date` on the given date.
+fn check_age_over(elements: &BTreeMap<String, CborValue>, on: Date) -> Result<(), String> {
+    let birth_date = match elements.get("birth_date") {
+        Some(CborValue::Tag(1004, v)) => match v.as_ref() {
+            CborValue::Text(s) => s,
+            _ => return Err("'birth_date' must be a full-date".to_string()),
+        },
+        _ => return Err("'birth_date' must be a full-date".to_string()),
+    };
+    let birth_date = Date::parse(birth_date, format_description!("[year]-[month]-[day]"))
+        .map_err(|e| format!("unable to parse 'birth_date': {e}"))?;
+    let age = age_on(birth_date, on);
+
+    for (element_identifier, value) in elements {
+        let threshold = match element_identifier
+            .strip_prefix("age_over_")
+            .filter(|nn| nn.len() == 2)
+            .and_then(|nn| nn.parse::<i32>().ok())
+        {
+            Some(threshold) => threshold,
+            None => continue,
+        };
+        let age_over = match value {
+            CborValue::Bool(b) => *b,
+            _ => return Err(format!("'{element_identifier}' must be a boolean")),
+        };
+        if age_over != (age >= threshold) {
+            return Err(format!(
+                "'{element_identifier}' is {age_over}, but the holder is {age} according to birth_date"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn age_on(birth_date: Date, on: Date) -> i32 {
+    let age = on.year() - birth_date.year();
+    if (on.month() as u8, on.day()) < (birth_date.month() as u8, birth_date.day()) {
+        age - 1
+    } else {
+        age
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::definitions::traits::FromJson;
+    use crate::definitions::traits::{FromJson, ToNamespaceMap};
 
     #[test]
     fn all() {
@@ -122,6 +223,13 @@ mod test {
         });
 
         let ns = OrgIso1801351::from_json(&json).unwrap();
+        OrgIso1801351::schema()
+            .validate(
+                OrgIso1801351::NAMESPACE,
+                &ns.clone().to_ns_map(),
+                time::macros::date!(2024 - 01 - 01),
+            )
+            .unwrap();
 
         assert!(ns.age_over_xx.get(&18.try_into().unwrap()).unwrap());
         assert!(ns.age_over_xx.get(&21.try_into().unwrap()).unwrap());
@@ -144,4 +252,12 @@ mod test {
         assert!(ns.resident_postal_code.is_some());
         assert!(ns.resident_country.is_some());
     }
+
+    #[test]
+    fn schema_matches_fields() {
+        crate::definitions::namespaces::registry::assert_schema_covers(
+            &OrgIso1801351::schema(),
+            OrgIso1801351::ELEMENT_IDENTIFIERS,
+        );
+    }
 }
@@ -21,7 +21,13 @@ pub use race_and_ethnicity::RaceAndEthnicity;
 pub use sex::Sex;
 pub use weight_range::WeightRange;
 
-use crate::macros::{FromJson, ToCbor};
+use crate::{
+    definitions::{
+        namespaces::registry::{ElementType, NamespaceSchema},
+        traits::Namespace,
+    },
+    macros::{FromJson, ToCbor},
+};
 
 /// `org.iso.18013.5.1.aamva` namespace, as per the AAMVA mDL Implementation
 /// Guidelines (Version 1.2).
@@ -55,10 +61,41 @@ pub struct OrgIso1801351Aamva {
     pub dhs_temporary_lawful_status: Option<Present>,
 }
 
+impl Namespace for OrgIso1801351Aamva {
+    const NAMESPACE: &'static str = "org.iso.18013.5.1.aamva";
+}
+
+impl OrgIso1801351Aamva {
+    /// The schema of the data elements in this namespace, as per the AAMVA mDL
+    /// Implementation Guidelines.
+    pub fn schema() -> NamespaceSchema {
+        NamespaceSchema::new()
+            .mandatory("domestic_driving_privileges", ElementType::Array)
+            .optional("name_suffix", ElementType::Text)
+            .optional("organ_donor", ElementType::Uint)
+            .optional("veteran", ElementType::Uint)
+            .mandatory("family_name_truncation", ElementType::Text)
+            .mandatory("given_name_truncation", ElementType::Text)
+            .optional("aka_family_name.v2", ElementType::Text)
+            .optional("aka_given_name.v2", ElementType::Text)
+            .optional("aka_suffix", ElementType::Text)
+            .optional("weight_range", ElementType::Uint)
+            .optional("race_ethnicity", ElementType::Text)
+            .optional("EDL_credential", ElementType::Uint)
+            .mandatory("sex", ElementType::Uint)
+            .mandatory("DHS_compliance", ElementType::Text)
+            .optional("resident_county", ElementType::Text)
+            .optional("hazmat_endorsement_expiration_date", ElementType::FullDate)
+            .optional("CDL_indicator", ElementType::Uint)
+            .optional("DHS_compliance_text", ElementType::Text)
+            .optional("DHS_temporary_lawful_status", ElementType::Uint)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::definitions::traits::FromJson;
+    use crate::definitions::traits::{FromJson, ToNamespaceMap};
 
     #[test]
     fn all() {
@@ -102,6 +139,13 @@ mod test {
         });
 
         let ns = OrgIso1801351Aamva::from_json(&json).unwrap();
+        OrgIso1801351Aamva::schema()
+            .validate(
+                OrgIso1801351Aamva::NAMESPACE,
+                &ns.clone().to_ns_map(),
+                time::macros::date!(2024 - 01 - 01),
+            )
+            .unwrap();
 
         assert!(ns.name_suffix.is_some());
         assert!(ns.organ_donor.is_some());
@@ -116,4 +160,12 @@ mod test {
         assert!(ns.dhs_compliance_text.is_some());
         assert!(ns.dhs_temporary_lawful_status.is_some());
     }
+
+    #[test]
+    fn schema_matches_fields() {
+        crate::definitions::namespaces::registry::assert_schema_covers(
+            &OrgIso1801351Aamva::schema(),
+            OrgIso1801351Aamva::ELEMENT_IDENTIFIERS,
+        );
+    }
 }
@@ -0,0 +1,151 @@
+pub use super::org_iso_18013_5_1::{AgeOver, Alpha2, Sex, TDate, TDateOrFullDate};
+pub use super::{fulldate::FullDate, latin1::Latin1};
+
+use crate::{
+    definitions::{
+        helpers::ByteStr,
+        namespaces::registry::{ElementType, NamespaceSchema},
+        traits::Namespace,
+    },
+    macros::{FromJson, ToCbor},
+};
+
+/// The `org.iso.23220.1` namespace of ISO/IEC 23220-2, common to the mdoc document types built
+/// on ISO/IEC 23220, such as the `org.iso.23220.photoid.1` photo ID.
+#[derive(Debug, Clone, FromJson, ToCbor)]
+pub struct OrgIso232201 {
+    pub family_name_unicode: String,
+    pub given_name_unicode: String,
+    pub birth_date: FullDate,
+    pub portrait: ByteStr,
+    pub issue_date: TDateOrFullDate,
+    pub expiry_date: TDateOrFullDate,
+    pub issuing_authority_unicode: String,
+    pub issuing_country: Alpha2,
+    pub age_in_years: Option<u32>,
+    #[isomdl(many)]
+    pub age_over_xx: AgeOver,
+    pub age_birth_year: Option<u32>,
+    pub portrait_capture_date: Option<TDate>,
+    pub birthplace: Option<String>,
+    pub name_at_birth: Option<String>,
+    pub resident_address_unicode: Option<String>,
+    pub resident_city_unicode: Option<String>,
+    pub resident_postal_code: Option<String>,
+    pub resident_country: Option<Alpha2>,
+    pub sex: Option<Sex>,
+    pub nationality: Option<Alpha2>,
+    pub document_number: Option<String>,
+    /// ISO 3166-2 code of the subdivision of the issuing country that issued the document.
+    pub issuing_subdivision: Option<String>,
+    pub family_name_latin1: Option<Latin1>,
+    pub given_name_latin1: Option<Latin1>,
+}
+
+impl Namespace for OrgIso232201 {
+    const NAMESPACE: &'static str = "org.iso.23220.1";
+}
+
+impl OrgIso232201 {
+    /// The schema of the data elements in this namespace, as per ISO/IEC 23220-2.
+    pub fn schema() -> NamespaceSchema {
+        NamespaceSchema::new()
+            .mandatory("family_name_unicode", ElementType::Text)
+            .mandatory("given_name_unicode", ElementType::Text)
+            .mandatory("birth_date", ElementType::FullDate)
+            .mandatory("portrait", ElementType::Bytes)
+            .mandatory("issue_date", ElementType::TDateOrFullDate)
+            .mandatory("expiry_date", ElementType::TDateOrFullDate)
+            .mandatory("issuing_authority_unicode", ElementType::Text)
+            .mandatory("issuing_country", ElementType::Text)
+            .optional("age_in_years", ElementType::Uint)
+            .optional("age_over_*", ElementType::Bool)
+            .optional("age_birth_year", ElementType::Uint)
+            .optional("portrait_capture_date", ElementType::TDate)
+            .optional("birthplace", ElementType::Text)
+            .optional("name_at_birth", ElementType::Text)
+            .optional("resident_address_unicode", ElementType::Text)
+            .optional("resident_city_unicode", ElementType::Text)
+            .optional("resident_postal_code", ElementType::Text)
+            .optional("resident_country", ElementType::Text)
+            .optional("sex", ElementType::Uint)
+            .optional("nationality", ElementType::Text)
+            .optional("document_number", ElementType::Text)
+            .optional("issuing_subdivision", ElementType::Text)
+            .optional("family_name_latin1", ElementType::Text)
+            .optional("given_name_latin1", ElementType::Text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::traits::{FromJson, ToNamespaceMap};
+
+    #[test]
+    fn all() {
+        let json = serde_json::json!({
+          "family_name_unicode":"Müller",
+          "given_name_unicode":"Jürgen",
+          "birth_date":"1985-03-30",
+          "portrait":include_str!("../../../../test/issuance/portrait.b64"),
+          "issue_date":"2024-01-01",
+          "expiry_date":"2034-01-01",
+          "issuing_authority_unicode":"Stadt Köln",
+          "issuing_country":"DE",
+          "age_in_years":39,
+          "age_over_18":true,
+          "age_over_65":false,
+          "age_birth_year":1985,
+          "portrait_capture_date":"2023-12-01T10:00:00Z",
+          "birthplace":"Köln",
+          "name_at_birth":"Jürgen Schmidt",
+          "resident_address_unicode":"Domkloster 4",
+          "resident_city_unicode":"Köln",
+          "resident_postal_code":"50667",
+          "resident_country":"DE",
+          "sex":1,
+          "nationality":"DE",
+          "document_number":"L01X00T47",
+          "issuing_subdivision":"DE-NW",
+          "family_name_latin1":"Müller",
+          "given_name_latin1":"Jürgen"
+        });
+
+        let ns = OrgIso232201::from_json(&json).unwrap();
+        OrgIso232201::schema()
+            .validate(
+                OrgIso232201::NAMESPACE,
+                &ns.clone().to_ns_map(),
+                time::macros::date!(2024 - 01 - 01),
+            )
+            .unwrap();
+
+        assert!(ns.age_over_xx.get(&18.try_into().unwrap()).unwrap());
+        assert!(!ns.age_over_xx.get(&65.try_into().unwrap()).unwrap());
+
+        assert!(ns.age_in_years.is_some());
+        assert!(ns.age_birth_year.is_some());
+        assert!(ns.portrait_capture_date.is_some());
+        assert!(ns.birthplace.is_some());
+        assert!(ns.name_at_birth.is_some());
+        assert!(ns.resident_address_unicode.is_some());
+        assert!(ns.resident_city_unicode.is_some());
+        assert!(ns.resident_postal_code.is_some());
+        assert!(ns.resident_country.is_some());
+        assert!(ns.sex.is_some());
+        assert!(ns.nationality.is_some());
+        assert!(ns.document_number.is_some());
+        assert!(ns.issuing_subdivision.is_some());
+        assert!(ns.family_name_latin1.is_some());
+        assert!(ns.given_name_latin1.is_some());
+    }
+
+    #[test]
+    fn schema_matches_fields() {
+        crate::definitions::namespaces::registry::assert_schema_covers(
+            &OrgIso232201::schema(),
+            OrgIso232201::ELEMENT_IDENTIFIERS,
+        );
+    }
+}
@@ -0,0 +1,105 @@
+pub use super::org_iso_18013_5_1::Alpha2;
+
+use crate::{
+    definitions::{
+        namespaces::registry::{ElementType, NamespaceSchema},
+        traits::Namespace,
+    },
+    macros::{FromJson, ToCbor},
+};
+
+/// The `org.iso.23220.photoid.1` namespace of the ISO/IEC 23220 photo ID, extending the
+/// `org.iso.23220.1` namespace.
+#[derive(Debug, Clone, FromJson, ToCbor)]
+pub struct OrgIso23220Photoid1 {
+    pub person_id: Option<String>,
+    pub birth_country: Option<Alpha2>,
+    pub birth_state: Option<String>,
+    pub birth_city: Option<String>,
+    pub administrative_number: Option<String>,
+    pub resident_street: Option<String>,
+    pub resident_house_number: Option<String>,
+    pub resident_state: Option<String>,
+    pub travel_document_type: Option<String>,
+    pub travel_document_number: Option<String>,
+    pub travel_document_mrz: Option<String>,
+}
+
+impl Namespace for OrgIso23220Photoid1 {
+    const NAMESPACE: &'static str = "org.iso.23220.photoid.1";
+}
+
+impl OrgIso23220Photoid1 {
+    /// The schema of the data elements in this namespace, as per ISO/IEC 23220.
+    pub fn schema() -> NamespaceSchema {
+        NamespaceSchema::new()
+            .optional("person_id", ElementType::Text)
+            .optional("birth_country", ElementType::Text)
+            .optional("birth_state", ElementType::Text)
+            .optional("birth_city", ElementType::Text)
+            .optional("administrative_number", ElementType::Text)
+            .optional("resident_street", ElementType::Text)
+            .optional("resident_house_number", ElementType::Text)
+            .optional("resident_state", ElementType::Text)
+            .optional("travel_document_type", ElementType::Text)
+            .optional("travel_document_number", ElementType::Text)
+            .optional("travel_document_mrz", ElementType::Text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::traits::{FromJson, ToNamespaceMap};
+
+    #[test]
+    fn all() {
+        let json = serde_json::json!({
+          "person_id":"DE-1234567890",
+          "birth_country":"DE",
+          "birth_state":"Nordrhein-Westfalen",
+          "birth_city":"Köln",
+          "administrative_number":"987654321",
+          "resident_street":"Domkloster",
+          "resident_house_number":"4",
+          "resident_state":"Nordrhein-Westfalen",
+          "travel_document_type":"P",
+          "travel_document_number":"C01X00T47",
+          "travel_document_mrz":"P<D<<MUELLER<<JUERGEN<<<<<<<<<<<<<<<<<<<<<<<C01X00T478D<<8503309M3401013<<<<<<<<<<<<<<<4"
+        });
+
+        let ns = OrgIso23220Photoid1::from_json(&json).unwrap();
+        OrgIso23220Photoid1::schema()
+            .validate(
+                OrgIso23220Photoid1::NAMESPACE,
+                &ns.clone().to_ns_map(),
+                time::macros::date!(2024 - 01 - 01),
+            )
+            .unwrap();
+
+        assert!(ns.person_id.is_some());
+        assert!(ns.birth_country.is_some());
+        assert!(ns.birth_state.is_some());
+        assert!(ns.birth_city.is_some());
+        assert!(ns.administrative_number.is_some());
+        assert!(ns.resident_street.is_some());
+        assert!(ns.resident_house_number.is_some());
+        assert!(ns.resident_state.is_some());
+        assert!(ns.travel_document_type.is_some());
+        assert!(ns.travel_document_number.is_some());
+        assert!(ns.travel_document_mrz.is_some());
+
+        assert!(OrgIso23220Photoid1::from_json(&serde_json::json!({})).is_ok());
+        assert!(
+            OrgIso23220Photoid1::from_json(&serde_json::json!({ "birth_country": "XX" })).is_err()
+        );
+    }
+
+    #[test]
+    fn schema_matches_fields() {
+        crate::definitions::namespaces::registry::assert_schema_covers(
+            &OrgIso23220Photoid1::schema(),
+            OrgIso23220Photoid1::ELEMENT_IDENTIFIERS,
+        );
+    }
+}
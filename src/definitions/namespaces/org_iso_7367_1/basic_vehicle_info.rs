@@ -0,0 +1,13 @@
+use super::Latin1;
+use crate::macros::{FromJson, ToCbor};
+
+/// `basic_vehicle_info` in the org.iso.7367.1 namespace.
+#[derive(Clone, Debug, FromJson, ToCbor)]
+pub struct BasicVehicleInfo {
+    /// (D.1)
+    pub make: Latin1,
+    /// Type, variant and version (D.2).
+    pub vehicle_type: Option<Latin1>,
+    /// The model of the vehicle (D.3).
+    pub commercial_name: Latin1,
+}
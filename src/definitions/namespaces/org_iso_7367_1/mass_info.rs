@@ -0,0 +1,12 @@
+use crate::macros::{FromJson, ToCbor};
+
+/// `mass_info` in the org.iso.7367.1 namespace, with each mass in kg.
+#[derive(Clone, Debug, FromJson, ToCbor)]
+pub struct MassInfo {
+    /// (F.1)
+    pub technically_permissible_maximum_laden_mass: u32,
+    /// (F.2)
+    pub permissible_maximum_laden_mass: Option<u32>,
+    /// (G)
+    pub mass_in_service: Option<u32>,
+}
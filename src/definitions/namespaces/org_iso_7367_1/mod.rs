@@ -0,0 +1,174 @@
+mod basic_vehicle_info;
+mod mass_info;
+mod vehicle_holder;
+mod vehicle_identification_number;
+
+pub use super::org_iso_18013_5_1::{Alpha2, TDate, TDateOrFullDate, UNDistinguishingSign};
+pub use super::{fulldate::FullDate, latin1::Latin1};
+
+pub use basic_vehicle_info::BasicVehicleInfo;
+pub use mass_info::MassInfo;
+pub use vehicle_holder::VehicleHolder;
+pub use vehicle_identification_number::VehicleIdentificationNumber;
+
+use crate::{
+    definitions::{
+        namespaces::registry::{ElementType, NamespaceSchema},
+        traits::Namespace,
+    },
+    macros::{FromJson, ToCbor},
+};
+
+/// The `org.iso.7367.1` namespace of the `org.iso.7367.1.mVRC` mobile vehicle registration
+/// certificate.
+///
+/// The letters in the documentation of each element refer to the harmonised codes of the
+/// registration certificate in Council Directive 1999/37/EC.
+#[derive(Debug, Clone, FromJson, ToCbor)]
+pub struct OrgIso73671 {
+    pub issue_date: TDateOrFullDate,
+    pub expiry_date: Option<TDateOrFullDate>,
+    pub issuing_country: Alpha2,
+    pub issuing_authority: Latin1,
+    pub document_number: Latin1,
+    pub un_distinguishing_sign: UNDistinguishingSign,
+    /// (A)
+    pub registration_number: Latin1,
+    /// (I)
+    pub date_of_registration: FullDate,
+    /// (B)
+    pub date_of_first_registration: FullDate,
+    /// (E)
+    pub vehicle_identification_number: VehicleIdentificationNumber,
+    /// (C.1)
+    pub vehicle_holder: VehicleHolder,
+    /// (D)
+    pub basic_vehicle_info: BasicVehicleInfo,
+    /// (F and G)
+    pub mass_info: Option<MassInfo>,
+    /// (J)
+    pub vehicle_category_code: Option<Latin1>,
+    /// (R)
+    pub colour: Option<Latin1>,
+    /// (S.1)
+    pub number_of_seats: Option<u32>,
+    /// (P.1) In cm³.
+    pub engine_capacity: Option<u32>,
+    /// (P.2) In kW.
+    pub maximum_net_power: Option<u32>,
+    /// (P.3)
+    pub fuel_type: Option<Latin1>,
+}
+
+impl Namespace for OrgIso73671 {
+    const NAMESPACE: &'static str = "org.iso.7367.1";
+}
+
+impl OrgIso73671 {
+    /// The schema of the data elements in this namespace, as per ISO/IEC 7367.
+    pub fn schema() -> NamespaceSchema {
+        NamespaceSchema::new()
+            .mandatory("issue_date", ElementType::TDateOrFullDate)
+            .optional("expiry_date", ElementType::TDateOrFullDate)
+            .mandatory("issuing_country", ElementType::Text)
+            .mandatory("issuing_authority", ElementType::Text)
+            .mandatory("document_number", ElementType::Text)
+            .mandatory("un_distinguishing_sign", ElementType::Text)
+            .mandatory("registration_number", ElementType::Text)
+            .mandatory("date_of_registration", ElementType::FullDate)
+            .mandatory("date_of_first_registration", ElementType::FullDate)
+            .mandatory("vehicle_identification_number", ElementType::Text)
+            .mandatory("vehicle_holder", ElementType::Map)
+            .mandatory("basic_vehicle_info", ElementType::Map)
+            .optional("mass_info", ElementType::Map)
+            .optional("vehicle_category_code", ElementType::Text)
+            .optional("colour", ElementType::Text)
+            .optional("number_of_seats", ElementType::Uint)
+            .optional("engine_capacity", ElementType::Uint)
+            .optional("maximum_net_power", ElementType::Uint)
+            .optional("fuel_type", ElementType::Text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::traits::{FromJson, ToNamespaceMap};
+
+    #[test]
+    fn all() {
+        let json = serde_json::json!({
+          "issue_date":"2024-01-01",
+          "expiry_date":"2034-01-01",
+          "issuing_country":"SE",
+          "issuing_authority":"Transportstyrelsen",
+          "document_number":"SE1234567",
+          "un_distinguishing_sign":"S",
+          "registration_number":"ABC123",
+          "date_of_registration":"2023-12-20",
+          "date_of_first_registration":"2019-05-02",
+          "vehicle_identification_number":"YV1LZ56D6Y2736472",
+          "vehicle_holder":{
+            "family_name":"Andersson",
+            "given_name":"Anna",
+            "resident_address":"Storgatan 1",
+            "resident_city":"Stockholm",
+            "resident_postal_code":"111 22",
+            "resident_country":"SE"
+          },
+          "basic_vehicle_info":{
+            "make":"Volvo",
+            "vehicle_type":"L",
+            "commercial_name":"V70"
+          },
+          "mass_info":{
+            "technically_permissible_maximum_laden_mass":2070,
+            "permissible_maximum_laden_mass":2070,
+            "mass_in_service":1520
+          },
+          "vehicle_category_code":"M1",
+          "colour":"Blue",
+          "number_of_seats":5,
+          "engine_capacity":2435,
+          "maximum_net_power":125,
+          "fuel_type":"Petrol"
+        });
+
+        let ns = OrgIso73671::from_json(&json).unwrap();
+        OrgIso73671::schema()
+            .validate(
+                OrgIso73671::NAMESPACE,
+                &ns.clone().to_ns_map(),
+                time::macros::date!(2024 - 01 - 01),
+            )
+            .unwrap();
+
+        assert_eq!(
+            ns.vehicle_identification_number.as_str(),
+            "YV1LZ56D6Y2736472"
+        );
+        assert!(ns.expiry_date.is_some());
+        assert!(ns.vehicle_holder.given_name.is_some());
+        assert!(ns.vehicle_holder.resident_city.is_some());
+        assert!(ns.vehicle_holder.resident_postal_code.is_some());
+        assert!(ns.vehicle_holder.resident_country.is_some());
+        assert!(ns.basic_vehicle_info.vehicle_type.is_some());
+        let mass_info = ns.mass_info.as_ref().unwrap();
+        assert!(mass_info.permissible_maximum_laden_mass.is_some());
+        assert!(mass_info.mass_in_service.is_some());
+        assert!(ns.vehicle_category_code.is_some());
+        assert!(ns.colour.is_some());
+        assert!(ns.number_of_seats.is_some());
+        assert!(ns.engine_capacity.is_some());
+        assert!(ns.maximum_net_power.is_some());
+        assert!(ns.fuel_type.is_some());
+    }
+
+    #[test]
+    fn schema_matches_fields() {
+        crate::definitions::namespaces::registry::assert_schema_covers(
+            &OrgIso73671::schema(),
+            OrgIso73671::ELEMENT_IDENTIFIERS,
+        );
+    }
+}
@@ -0,0 +1,16 @@
+use super::{Alpha2, Latin1};
+use crate::macros::{FromJson, ToCbor};
+
+/// `vehicle_holder` in the org.iso.7367.1 namespace.
+#[derive(Clone, Debug, FromJson, ToCbor)]
+pub struct VehicleHolder {
+    /// Surname or business name of the holder of the registration certificate (C.1.1).
+    pub family_name: Latin1,
+    /// (C.1.2)
+    pub given_name: Option<Latin1>,
+    /// (C.1.3)
+    pub resident_address: Latin1,
+    pub resident_city: Option<Latin1>,
+    pub resident_postal_code: Option<Latin1>,
+    pub resident_country: Option<Alpha2>,
+}
@@ -0,0 +1,79 @@
+use crate::definitions::traits::{FromJson, FromJsonError};
+use serde_cbor::Value as Cbor;
+use serde_json::Value as Json;
+use std::str::FromStr;
+
+/// `vehicle_identification_number` in the org.iso.7367.1 namespace, as per ISO 3779.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VehicleIdentificationNumber(String);
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error("expected 17 characters, found {0}")]
+    Length(usize),
+    #[error(
+        "contains characters other than the digits and capital letters excluding I, O and Q: {0:?}"
+    )]
+    InvalidCharacters(Vec<char>),
+}
+
+impl VehicleIdentificationNumber {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<VehicleIdentificationNumber> for Cbor {
+    fn from(v: VehicleIdentificationNumber) -> Cbor {
+        v.0.into()
+    }
+}
+
+impl FromJson for VehicleIdentificationNumber {
+    fn from_json(v: &Json) -> Result<Self, FromJsonError> {
+        String::from_json(v)?
+            .parse()
+            .map_err(Into::into)
+            .map_err(FromJsonError::Parsing)
+    }
+}
+
+impl FromStr for VehicleIdentificationNumber {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid: Vec<char> = s
+            .chars()
+            .filter(|c| !matches!(c, '0'..='9' | 'A'..='H' | 'J'..='N' | 'P' | 'R'..='Z'))
+            .collect();
+        if !invalid.is_empty() {
+            return Err(Error::InvalidCharacters(invalid));
+        }
+        let len = s.chars().count();
+        if len != 17 {
+            return Err(Error::Length(len));
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        assert!("YV1LZ56D6Y2736472"
+            .parse::<VehicleIdentificationNumber>()
+            .is_ok());
+        assert!("YV1LZ56D6Y273647"
+            .parse::<VehicleIdentificationNumber>()
+            .is_err());
+        assert!("YV1LZ56D6Y273647O"
+            .parse::<VehicleIdentificationNumber>()
+            .is_err());
+        assert!("yv1lz56d6y2736472"
+            .parse::<VehicleIdentificationNumber>()
+            .is_err());
+    }
+}
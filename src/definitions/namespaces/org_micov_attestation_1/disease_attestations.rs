@@ -0,0 +1,50 @@
+use crate::definitions::traits::{FromJson, FromJsonError, FromJsonMap, ToNamespaceMap};
+use anyhow::anyhow;
+use serde_cbor::Value as Cbor;
+use serde_json::{Map, Value as Json};
+use std::collections::BTreeMap;
+
+/// `<disease>_vaccinated` and `<disease>_test` in the org.micov.attestation.1 namespace, keyed
+/// by the ICD-11 code of the disease.
+#[derive(Clone, Debug, Default)]
+pub struct DiseaseAttestations {
+    /// Whether the holder is vaccinated against each disease.
+    pub vaccinated: BTreeMap<String, bool>,
+    /// Whether the holder has tested negative for each disease.
+    pub tested: BTreeMap<String, bool>,
+}
+
+impl FromJsonMap for DiseaseAttestations {
+    fn from_map(m: &Map<String, Json>) -> Result<Self, FromJsonError> {
+        let mut attestations = Self::default();
+        for (key, value) in m {
+            let (attested, disease) =
+                match (key.strip_suffix("_vaccinated"), key.strip_suffix("_test")) {
+                    (Some(disease), _) => (&mut attestations.vaccinated, disease),
+                    (_, Some(disease)) => (&mut attestations.tested, disease),
+                    _ => continue,
+                };
+            if disease.is_empty() || !disease.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(anyhow!("{key}: expected an ICD-11 code before the suffix").into());
+            }
+            let value = bool::from_json(value)
+                .map_err(|e| FromJsonError::Parsing(anyhow!("{key}: {e}")))?;
+            attested.insert(disease.to_string(), value);
+        }
+        Ok(attestations)
+    }
+}
+
+impl ToNamespaceMap for DiseaseAttestations {
+    fn to_ns_map(self) -> BTreeMap<String, Cbor> {
+        let vaccinated = self
+            .vaccinated
+            .into_iter()
+            .map(|(disease, v)| (format!("{disease}_vaccinated"), v.into()));
+        let tested = self
+            .tested
+            .into_iter()
+            .map(|(disease, v)| (format!("{disease}_test"), v.into()));
+        vaccinated.chain(tested).collect()
+    }
+}
@@ -0,0 +1,116 @@
+mod disease_attestations;
+
+pub use super::latin1::Latin1;
+pub use disease_attestations::DiseaseAttestations;
+
+use crate::{
+    definitions::{
+        helpers::ByteStr,
+        namespaces::registry::{ElementType, NamespaceSchema},
+        traits::Namespace,
+    },
+    macros::{FromJson, ToCbor},
+};
+
+/// The `org.micov.attestation.1` namespace of the `org.micov.1` mobile international certificate
+/// of vaccination, attesting to the protection of the holder without disclosing their records.
+#[derive(Debug, Clone, FromJson, ToCbor)]
+pub struct OrgMicovAttestation1 {
+    /// Every `<disease>_vaccinated` and `<disease>_test` element.
+    #[isomdl(many)]
+    pub attestations: DiseaseAttestations,
+    #[isomdl(rename = "safeEntry_Leisure")]
+    pub safe_entry_leisure: Option<bool>,
+    /// Facial image of the holder.
+    pub fac: ByteStr,
+    /// Family name initial.
+    pub fni: Latin1,
+    /// Given name initial.
+    pub gni: Latin1,
+    /// Birth year.
+    pub by: u32,
+    /// Birth month.
+    pub bm: Option<u32>,
+    /// Birth day.
+    pub bd: Option<u32>,
+}
+
+impl Namespace for OrgMicovAttestation1 {
+    const NAMESPACE: &'static str = "org.micov.attestation.1";
+}
+
+impl OrgMicovAttestation1 {
+    /// The schema of the data elements in this namespace.
+    pub fn schema() -> NamespaceSchema {
+        NamespaceSchema::new()
+            .optional("*_vaccinated", ElementType::Bool)
+            .optional("*_test", ElementType::Bool)
+            .optional("safeEntry_Leisure", ElementType::Bool)
+            .mandatory("fac", ElementType::Bytes)
+            .mandatory("fni", ElementType::Text)
+            .mandatory("gni", ElementType::Text)
+            .mandatory("by", ElementType::Uint)
+            .optional("bm", ElementType::Uint)
+            .optional("bd", ElementType::Uint)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::traits::{FromJson, ToCbor, ToNamespaceMap};
+    use serde_cbor::Value as Cbor;
+
+    #[test]
+    fn all() {
+        let json = serde_json::json!({
+          "1D47_vaccinated":true,
+          "RA01_vaccinated":true,
+          "RA01_test":false,
+          "safeEntry_Leisure":true,
+          "fac":include_str!("../../../../test/issuance/portrait.b64"),
+          "fni":"M",
+          "gni":"E",
+          "by":1964,
+          "bm":8,
+          "bd":12
+        });
+
+        let ns = OrgMicovAttestation1::from_json(&json).unwrap();
+        OrgMicovAttestation1::schema()
+            .validate(
+                OrgMicovAttestation1::NAMESPACE,
+                &ns.clone().to_ns_map(),
+                time::macros::date!(2024 - 01 - 01),
+            )
+            .unwrap();
+
+        assert_eq!(ns.attestations.vaccinated.get("1D47"), Some(&true));
+        assert_eq!(ns.attestations.vaccinated.get("RA01"), Some(&true));
+        assert_eq!(ns.attestations.tested.get("RA01"), Some(&false));
+        assert!(ns.safe_entry_leisure.is_some());
+        assert!(ns.bm.is_some());
+        assert!(ns.bd.is_some());
+
+        let cbor = match ns.to_cbor() {
+            Cbor::Map(map) => map,
+            _ => panic!("expected a map"),
+        };
+        for key in [
+            "1D47_vaccinated",
+            "RA01_vaccinated",
+            "RA01_test",
+            "safeEntry_Leisure",
+        ] {
+            assert!(cbor.contains_key(&Cbor::Text(key.into())), "missing {key}");
+        }
+    }
+
+    #[test]
+    fn schema_matches_fields() {
+        crate::definitions::namespaces::registry::assert_schema_covers(
+            &OrgMicovAttestation1::schema(),
+            OrgMicovAttestation1::ELEMENT_IDENTIFIERS,
+        );
+    }
+}
@@ -0,0 +1,230 @@
+mod person_identifier;
+mod vaccination_event;
+
+pub use super::fulldate::FullDate;
+pub use super::org_iso_18013_5_1::{Alpha2, Sex};
+
+pub use person_identifier::{PersonIdentifier, PersonIdentifiers};
+pub use vaccination_event::{VaccinationEvent, VaccinationEvents};
+
+use crate::{
+    definitions::{
+        namespaces::registry::{ElementType, NamespaceSchema},
+        traits::Namespace,
+    },
+    macros::{FromJson, ToCbor},
+};
+
+/// The `org.micov.vtr.1` namespace of the `org.micov.1` mobile international certificate of
+/// vaccination, holding the vaccination and test records of the holder.
+#[derive(Debug, Clone, FromJson, ToCbor)]
+pub struct OrgMicovVtr1 {
+    #[isomdl(rename = "fn")]
+    pub family_name: String,
+    #[isomdl(rename = "gn")]
+    pub given_name: String,
+    #[isomdl(rename = "dob")]
+    pub birth_date: FullDate,
+    pub sex: Option<Sex>,
+    /// Every `v_<disease>_<n>` element.
+    #[isomdl(many)]
+    pub vaccinations: VaccinationEvents,
+    /// Every `pid_<type>` element.
+    #[isomdl(many)]
+    pub person_identifiers: PersonIdentifiers,
+}
+
+impl Namespace for OrgMicovVtr1 {
+    const NAMESPACE: &'static str = "org.micov.vtr.1";
+}
+
+impl OrgMicovVtr1 {
+    /// The schema of the data elements in this namespace.
+    pub fn schema() -> NamespaceSchema {
+        NamespaceSchema::new()
+            .mandatory("fn", ElementType::Text)
+            .mandatory("gn", ElementType::Text)
+            .mandatory("dob", ElementType::FullDate)
+            .optional("sex", ElementType::Uint)
+            .optional("v_*", ElementType::Map)
+            .optional("pid_*", ElementType::Map)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::traits::{FromJson, ToCbor, ToNamespaceMap};
+    use serde_cbor::Value as Cbor;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn all() {
+        let json = serde_json::json!({
+          "fn":"Mustermann",
+          "gn":"Erika",
+          "dob":"1964-08-12",
+          "sex":2,
+          "v_RA01_1":{
+            "tg":"840539006",
+            "vp":"1119349007",
+            "mp":"EU/1/20/1528",
+            "ma":"ORG-100030215",
+            "bn":"B12345/67",
+            "dn":1,
+            "sd":2,
+            "dt":"2021-04-08",
+            "co":"BE",
+            "ao":"RHI Antwerpen",
+            "nx":"2021-05-20",
+            "is":"SC17",
+            "ci":"urn:uvci:01:BE:187/37512422923"
+          },
+          "v_RA01_2":{
+            "tg":"840539006",
+            "vp":"1119349007",
+            "mp":"EU/1/20/1528",
+            "ma":"ORG-100030215",
+            "bn":"B67890/12",
+            "dn":2,
+            "sd":2,
+            "dt":"2021-05-18",
+            "co":"BE",
+            "ao":"RHI Antwerpen",
+            "is":"SC17",
+            "ci":"urn:uvci:01:BE:187/37512533044"
+          },
+          "pid_PPN":{
+            "pty":"PPN",
+            "pnr":"476284728",
+            "pic":"BE"
+          },
+          "pid_DL":{
+            "pty":"DL",
+            "pnr":"987654321",
+            "pic":"BE"
+          }
+        });
+
+        let ns = OrgMicovVtr1::from_json(&json).unwrap();
+        OrgMicovVtr1::schema()
+            .validate(
+                OrgMicovVtr1::NAMESPACE,
+                &ns.clone().to_ns_map(),
+                time::macros::date!(2024 - 01 - 01),
+            )
+            .unwrap();
+
+        assert!(ns.sex.is_some());
+        let doses: Vec<_> = ns.vaccinations["RA01"]
+            .iter()
+            .map(|event| event.dn)
+            .collect();
+        assert_eq!(doses, [Some(1), Some(2)]);
+        assert!(ns.vaccinations["RA01"][0].nx.is_some());
+        assert_eq!(ns.person_identifiers["PPN"].pnr, "476284728");
+        assert!(ns.person_identifiers.contains_key("DL"));
+
+        let cbor = match ns.to_cbor() {
+            Cbor::Map(map) => map,
+            _ => panic!("expected a map"),
+        };
+        for key in [
+            "fn", "gn", "dob", "sex", "v_RA01_1", "v_RA01_2", "pid_PPN", "pid_DL",
+        ] {
+            assert!(cbor.contains_key(&Cbor::Text(key.into())), "missing {key}");
+        }
+    }
+
+    #[test]
+    fn vaccination_numbering() {
+        let mut json = serde_json::json!({
+          "fn":"Mustermann",
+          "gn":"Erika",
+          "dob":"1964-08-12",
+          "v_RA01_2":{
+            "tg":"840539006",
+            "vp":"1119349007",
+            "mp":"EU/1/20/1528",
+            "ma":"ORG-100030215",
+            "dt":"2021-05-18",
+            "co":"BE",
+            "is":"SC17",
+            "ci":"urn:uvci:01:BE:187/37512533044"
+          }
+        });
+        assert!(OrgMicovVtr1::from_json(&json).is_err());
+
+        json["v_RA01_1"] = json["v_RA01_2"].clone();
+        let ns = OrgMicovVtr1::from_json(&json).unwrap();
+        assert_eq!(ns.vaccinations["RA01"].len(), 2);
+        assert!(ns.person_identifiers.is_empty());
+    }
+
+    #[test]
+    fn person_identifier_type() {
+        let mut json = serde_json::json!({
+          "fn":"Mustermann",
+          "gn":"Erika",
+          "dob":"1964-08-12",
+          "pid_PPN":{
+            "pty":"DL",
+            "pnr":"476284728",
+            "pic":"BE"
+          }
+        });
+        assert!(OrgMicovVtr1::from_json(&json).is_err());
+
+        json["pid_PPN"]["pty"] = "PPN".into();
+        let ns = OrgMicovVtr1::from_json(&json).unwrap();
+        assert_eq!(ns.person_identifiers["PPN"].pnr, "476284728");
+    }
+
+    #[test]
+    fn construction() {
+        let event = VaccinationEvent::from_json(&serde_json::json!({
+          "tg":"840539006",
+          "vp":"1119349007",
+          "mp":"EU/1/20/1528",
+          "ma":"ORG-100030215",
+          "dt":"2021-04-08",
+          "co":"BE",
+          "is":"SC17",
+          "ci":"urn:uvci:01:BE:187/37512422923"
+        }))
+        .unwrap();
+        let identifier = PersonIdentifier::from_json(&serde_json::json!({
+          "pty":"PPN",
+          "pnr":"C01X00T47",
+          "pic":"DE"
+        }))
+        .unwrap();
+
+        let mut vaccinations =
+            VaccinationEvents::from(BTreeMap::from([("RA01".to_string(), vec![event.clone()])]));
+        vaccinations.insert("XN678".to_string(), vec![event.clone(), event]);
+        let mut person_identifiers = PersonIdentifiers::default();
+        person_identifiers.insert(identifier);
+
+        let ns = OrgMicovVtr1 {
+            family_name: "Mustermann".to_string(),
+            given_name: "Erika".to_string(),
+            birth_date: FullDate::from_json(&"1964-08-12".into()).unwrap(),
+            sex: None,
+            vaccinations,
+            person_identifiers,
+        };
+        let map = ns.to_ns_map();
+        for key in ["v_RA01_1", "v_XN678_1", "v_XN678_2", "pid_PPN"] {
+            assert!(map.contains_key(key), "missing {key}");
+        }
+    }
+
+    #[test]
+    fn schema_matches_fields() {
+        crate::definitions::namespaces::registry::assert_schema_covers(
+            &OrgMicovVtr1::schema(),
+            OrgMicovVtr1::ELEMENT_IDENTIFIERS,
+        );
+    }
+}
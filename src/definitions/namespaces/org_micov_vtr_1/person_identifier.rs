@@ -0,0 +1,82 @@
+use super::Alpha2;
+use crate::{
+    definitions::traits::{FromJson, FromJsonError, FromJsonMap, ToCbor, ToNamespaceMap},
+    macros::{FromJson, ToCbor},
+};
+use anyhow::anyhow;
+use serde_cbor::Value as Cbor;
+use serde_json::{Map, Value as Json};
+use std::{collections::BTreeMap, ops::Deref};
+
+/// `pid_<type>` in the org.micov.vtr.1 namespace: an identity document of the holder, such as a
+/// passport (`PPN`) or a driving licence (`DL`).
+#[derive(Clone, Debug, FromJson, ToCbor)]
+pub struct PersonIdentifier {
+    /// Type of the identity document.
+    pub pty: String,
+    /// Number of the identity document.
+    pub pnr: String,
+    /// Issuing country of the identity document.
+    pub pic: Alpha2,
+    /// Issuing authority of the identity document.
+    pub pia: Option<String>,
+}
+
+/// Every `pid_<type>` element, keyed by type.
+#[derive(Clone, Debug, Default)]
+pub struct PersonIdentifiers(BTreeMap<String, PersonIdentifier>);
+
+impl PersonIdentifiers {
+    /// Set the identity document of its type, returning the identity document previously set for
+    /// that type.
+    pub fn insert(&mut self, identifier: PersonIdentifier) -> Option<PersonIdentifier> {
+        self.0.insert(identifier.pty.clone(), identifier)
+    }
+}
+
+impl FromIterator<PersonIdentifier> for PersonIdentifiers {
+    fn from_iter<I: IntoIterator<Item = PersonIdentifier>>(identifiers: I) -> Self {
+        let mut person_identifiers = Self::default();
+        for identifier in identifiers {
+            person_identifiers.insert(identifier);
+        }
+        person_identifiers
+    }
+}
+
+impl Deref for PersonIdentifiers {
+    type Target = BTreeMap<String, PersonIdentifier>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromJsonMap for PersonIdentifiers {
+    fn from_map(m: &Map<String, Json>) -> Result<Self, FromJsonError> {
+        m.iter()
+            .filter_map(|(k, v)| k.strip_prefix("pid_").map(|pty| (k, pty, v)))
+            .map(|(k, pty, v)| {
+                let identifier = PersonIdentifier::from_json(v)
+                    .map_err(|e| FromJsonError::Parsing(anyhow!("{k}: {e}")))?;
+                if identifier.pty != pty {
+                    return Err(FromJsonError::Parsing(anyhow!(
+                        "{k}: pty '{}' does not match the element identifier",
+                        identifier.pty
+                    )));
+                }
+                Ok((pty.to_string(), identifier))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl ToNamespaceMap for PersonIdentifiers {
+    fn to_ns_map(self) -> BTreeMap<String, Cbor> {
+        self.0
+            .into_iter()
+            .map(|(pty, identifier)| (format!("pid_{pty}"), identifier.to_cbor()))
+            .collect()
+    }
+}
@@ -0,0 +1,122 @@
+use super::{Alpha2, FullDate};
+use crate::{
+    definitions::traits::{FromJson, FromJsonError, FromJsonMap, ToCbor, ToNamespaceMap},
+    macros::{FromJson, ToCbor},
+};
+use anyhow::anyhow;
+use serde_cbor::Value as Cbor;
+use serde_json::{Map, Value as Json};
+use std::{collections::BTreeMap, ops::Deref};
+
+/// `v_<disease>_<n>` in the org.micov.vtr.1 namespace: the `n`th vaccination against the
+/// disease with the ICD-11 code `disease`.
+#[derive(Clone, Debug, FromJson, ToCbor)]
+pub struct VaccinationEvent {
+    /// Disease or agent targeted.
+    pub tg: String,
+    /// Vaccine or prophylaxis.
+    pub vp: String,
+    /// Vaccine medicinal product.
+    pub mp: String,
+    /// Vaccine marketing authorization holder or manufacturer.
+    pub ma: String,
+    /// Batch number.
+    pub bn: Option<String>,
+    /// Dose number.
+    pub dn: Option<u32>,
+    /// Total number of doses in the series.
+    pub sd: Option<u32>,
+    /// Date of vaccination.
+    pub dt: FullDate,
+    /// Country of vaccination.
+    pub co: Alpha2,
+    /// Administering organization.
+    pub ao: Option<String>,
+    /// Due date of the next dose.
+    pub nx: Option<FullDate>,
+    /// Certificate issuer.
+    pub is: String,
+    /// Unique certificate identifier.
+    pub ci: String,
+}
+
+/// Every `v_<disease>_<n>` element, as the vaccination events against each disease in order of
+/// `n`.
+#[derive(Clone, Debug, Default)]
+pub struct VaccinationEvents(BTreeMap<String, Vec<VaccinationEvent>>);
+
+impl VaccinationEvents {
+    /// Set the vaccination events against a disease, returning the events previously set for it.
+    pub fn insert(
+        &mut self,
+        disease: String,
+        events: Vec<VaccinationEvent>,
+    ) -> Option<Vec<VaccinationEvent>> {
+        self.0.insert(disease, events)
+    }
+}
+
+impl From<BTreeMap<String, Vec<VaccinationEvent>>> for VaccinationEvents {
+    fn from(events: BTreeMap<String, Vec<VaccinationEvent>>) -> Self {
+        Self(events)
+    }
+}
+
+impl Deref for VaccinationEvents {
+    type Target = BTreeMap<String, Vec<VaccinationEvent>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromJsonMap for VaccinationEvents {
+    fn from_map(m: &Map<String, Json>) -> Result<Self, FromJsonError> {
+        let mut events: BTreeMap<String, BTreeMap<u32, VaccinationEvent>> = BTreeMap::new();
+        for (key, value) in m {
+            let (disease, n) = match key.strip_prefix("v_").and_then(|s| s.rsplit_once('_')) {
+                Some((disease, n)) if !disease.is_empty() => (disease, n),
+                _ => continue,
+            };
+            let n: u32 = n
+                .parse()
+                .map_err(|_| anyhow!("{key}: expected the vaccination number to be an integer"))?;
+            let event = VaccinationEvent::from_json(value)
+                .map_err(|e| FromJsonError::Parsing(anyhow!("{key}: {e}")))?;
+            events
+                .entry(disease.to_string())
+                .or_default()
+                .insert(n, event);
+        }
+
+        // The events against each disease are numbered from 1 without gaps, so that they can be
+        // held as an array.
+        events
+            .into_iter()
+            .map(|(disease, events)| {
+                if !events.keys().copied().eq(1..=events.len() as u32) {
+                    return Err(anyhow!(
+                        "v_{disease}_<n>: expected vaccinations to be numbered from 1 without gaps"
+                    )
+                    .into());
+                }
+                Ok((disease, events.into_values().collect()))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl ToNamespaceMap for VaccinationEvents {
+    fn to_ns_map(self) -> BTreeMap<String, Cbor> {
+        self.0
+            .into_iter()
+            .flat_map(|(disease, events)| {
+                events
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(i, event)| (format!("v_{disease}_{}", i + 1), event.to_cbor()))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,701 @@
+//! A registry of the data elements defined in each namespace, and of the namespaces that make up
+//! each document type.
+//!
+//! [Registry::default] knows every document type and namespace defined in this crate. Additional
+//! namespaces and document types can be registered on top, so that issuance, request validation
+//! and the reader can handle them without being changed.
+use super::{
+    eu_europa_ec_eudi_pid_1::EuEuropaEcEudiPid1, org_iso_18013_5_1::OrgIso1801351,
+    org_iso_18013_5_1_aamva::OrgIso1801351Aamva, org_iso_23220_1::OrgIso232201,
+    org_iso_23220_photoid_1::OrgIso23220Photoid1, org_iso_7367_1::OrgIso73671,
+    org_micov_attestation_1::OrgMicovAttestation1, org_micov_vtr_1::OrgMicovVtr1,
+};
+use crate::definitions::{device_request, traits::Namespace};
+use serde::Serialize;
+use serde_cbor::Value as CborValue;
+use std::{collections::BTreeMap, fmt};
+use time::Date;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    #[error("unknown document type: '{0}'")]
+    UnknownDocType(String),
+    #[error("missing namespace: '{0}'")]
+    MissingNamespace(String),
+    #[error("missing mandatory data element '{1}' in the {0} namespace")]
+    MissingElement(String, String),
+    #[error("unknown data element '{1}' in the {0} namespace")]
+    UnknownElement(String, String),
+    #[error("data element '{1}' in the {0} namespace must be a {2}")]
+    UnexpectedType(String, String, ElementType),
+    #[error("data element '{1}' in the {0} namespace must not be retained")]
+    RetentionProhibited(String, String),
+    #[error("inconsistent data elements in the {0} namespace: {1}")]
+    Inconsistent(String, String),
+}
+
+/// A check that the data elements of a namespace are consistent with each other on the given
+/// date, run after the elements have been checked against their definitions.
+pub type Check = fn(&BTreeMap<String, CborValue>, Date) -> Result<(), String>;
+
+/// The CBOR type of a data element value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ElementType {
+    Bool,
+    Uint,
+    Text,
+    Bytes,
+    /// A `full-date`, i.e. a text string with tag 1004.
+    FullDate,
+    /// A `tdate`, i.e. a text string with tag 0.
+    TDate,
+    TDateOrFullDate,
+    Array,
+    Map,
+    /// Any value, for elements with more than one permitted type.
+    Any,
+}
+
+/// Whether a reader may request to retain a data element.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntentToRetain {
+    #[default]
+    Permitted,
+    Prohibited,
+}
+
+/// The definition of a data element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ElementSchema {
+    pub element_type: ElementType,
+    pub mandatory: bool,
+    pub intent_to_retain: IntentToRetain,
+}
+
+/// The data elements defined in a namespace.
+///
+/// An element identifier containing a `*` defines a family of optional elements, such as
+/// `age_over_*`, where the `*` stands for any non-empty text.
+#[derive(Debug, Clone, Default)]
+pub struct NamespaceSchema {
+    elements: BTreeMap<String, ElementSchema>,
+    checks: Vec<Check>,
+}
+
+/// The namespaces in a document type, each of which is either required or optional.
+#[derive(Debug, Clone, Default)]
+pub struct DocTypeSchema {
+    namespaces: BTreeMap<String, bool>,
+}
+
+/// Schemas for document types and namespaces.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    doc_types: BTreeMap<String, DocTypeSchema>,
+    namespaces: BTreeMap<String, NamespaceSchema>,
+}
+
+impl ElementType {
+    /// Whether the value is of this type.
+    pub fn matches(self, value: &CborValue) -> bool {
+        match (self, value) {
+            (Self::Any, _) => true,
+            (Self::Bool, CborValue::Bool(_)) => true,
+            (Self::Uint, CborValue::Integer(i)) => *i >= 0,
+            (Self::Text, CborValue::Text(_)) => true,
+            (Self::Bytes, CborValue::Bytes(_)) => true,
+            (Self::FullDate, CborValue::Tag(1004, v)) => matches!(**v, CborValue::Text(_)),
+            (Self::TDate, CborValue::Tag(0, v)) => matches!(**v, CborValue::Text(_)),
+            (Self::TDateOrFullDate, v) => Self::TDate.matches(v) || Self::FullDate.matches(v),
+            (Self::Array, CborValue::Array(_)) => true,
+            (Self::Map, CborValue::Map(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ElementType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Bool => "bool",
+            Self::Uint => "uint",
+            Self::Text => "tstr",
+            Self::Bytes => "bstr",
+            Self::FullDate => "full-date",
+            Self::TDate => "tdate",
+            Self::TDateOrFullDate => "tdate or full-date",
+            Self::Array => "array",
+            Self::Map => "map",
+            Self::Any => "any",
+        };
+        f.write_str(name)
+    }
+}
+
+impl ElementSchema {
+    /// A data element which must be present in every document.
+    pub fn mandatory(element_type: ElementType) -> Self {
+        Self {
+            element_type,
+            mandatory: true,
+            intent_to_retain: IntentToRetain::Permitted,
+        }
+    }
+
+    /// A data element which may be omitted.
+    pub fn optional(element_type: ElementType) -> Self {
+        Self {
+            element_type,
+            mandatory: false,
+            intent_to_retain: IntentToRetain::Permitted,
+        }
+    }
+
+    /// Forbid readers from requesting to retain this data element.
+    pub fn retention_prohibited(mut self) -> Self {
+        self.intent_to_retain = IntentToRetain::Prohibited;
+        self
+    }
+}
+
+impl NamespaceSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define a data element, replacing any previous definition with the same identifier.
+    pub fn element(mut self, element_identifier: impl Into<String>, schema: ElementSchema) -> Self {
+        self.elements.insert(element_identifier.into(), schema);
+        self
+    }
+
+    /// Define a mandatory data element.
+    pub fn mandatory(
+        self,
+        element_identifier: impl Into<String>,
+        element_type: ElementType,
+    ) -> Self {
+        self.element(element_identifier, ElementSchema::mandatory(element_type))
+    }
+
+    /// Define an optional data element.
+    pub fn optional(
+        self,
+        element_identifier: impl Into<String>,
+        element_type: ElementType,
+    ) -> Self {
+        self.element(element_identifier, ElementSchema::optional(element_type))
+    }
+
+    /// Add a check of the consistency of the data elements, such as between a date of birth and
+    /// the age attestations.
+    pub fn check(mut self, check: Check) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    /// The definitions of the data elements, keyed by element identifier or pattern.
+    pub fn elements(&self) -> &BTreeMap<String, ElementSchema> {
+        &self.elements
+    }
+
+    /// Look up the definition of a data element, falling back to the patterns if there is no
+    /// definition for the exact element identifier.
+    pub fn get(&self, element_identifier: &str) -> Option<&ElementSchema> {
+        self.elements.get(element_identifier).or_else(|| {
+            self.elements
+                .iter()
+                .find(|(pattern, _)| matches_pattern(pattern, element_identifier))
+                .map(|(_, schema)| schema)
+        })
+    }
+
+    /// Check that the mandatory data elements are present, that every data element is defined
+    /// and of the defined type, and that the data elements pass the consistency checks on the
+    /// given date.
+    pub fn validate(
+        &self,
+        namespace: &str,
+        elements: &BTreeMap<String, CborValue>,
+        on: Date,
+    ) -> Result<(), Error> {
+        if let Some(missing) = self
+            .elements
+            .iter()
+            .filter(|(_, schema)| schema.mandatory)
+            .find(|(element_identifier, _)| !elements.contains_key(*element_identifier))
+        {
+            return Err(Error::MissingElement(
+                namespace.to_string(),
+                missing.0.clone(),
+            ));
+        }
+
+        for (element_identifier, value) in elements {
+            let schema = self.get(element_identifier).ok_or_else(|| {
+                Error::UnknownElement(namespace.to_string(), element_identifier.clone())
+            })?;
+            if !schema.element_type.matches(value) {
+                return Err(Error::UnexpectedType(
+                    namespace.to_string(),
+                    element_identifier.clone(),
+                    schema.element_type,
+                ));
+            }
+        }
+
+        for check in &self.checks {
+            check(elements, on)
+                .map_err(|reason| Error::Inconsistent(namespace.to_string(), reason))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl DocTypeSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a namespace which must be present in every document of this type.
+    pub fn required(mut self, namespace: impl Into<String>) -> Self {
+        self.namespaces.insert(namespace.into(), true);
+        self
+    }
+
+    /// Add a namespace which may be omitted.
+    pub fn optional(mut self, namespace: impl Into<String>) -> Self {
+        self.namespaces.insert(namespace.into(), false);
+        self
+    }
+
+    /// The namespaces in this document type, and whether each is required.
+    pub fn namespaces(&self) -> &BTreeMap<String, bool> {
+        &self.namespaces
+    }
+}
+
+impl Registry {
+    /// A registry without any document types or namespaces.
+    pub fn empty() -> Self {
+        Self {
+            doc_types: BTreeMap::new(),
+            namespaces: BTreeMap::new(),
+        }
+    }
+
+    /// Register a document type, replacing any previous registration.
+    pub fn register_doc_type(&mut self, doc_type: impl Into<String>, schema: DocTypeSchema) {
+        self.doc_types.insert(doc_type.into(), schema);
+    }
+
+    /// Register a namespace, replacing any previous registration.
+    pub fn register_namespace(&mut self, namespace: impl Into<String>, schema: NamespaceSchema) {
+        self.namespaces.insert(namespace.into(), schema);
+    }
+
+    pub fn doc_type(&self, doc_type: &str) -> Option<&DocTypeSchema> {
+        self.doc_types.get(doc_type)
+    }
+
+    pub fn namespace(&self, namespace: &str) -> Option<&NamespaceSchema> {
+        self.namespaces.get(namespace)
+    }
+
+    /// Check the data elements of a document prior to issuance, on the date of signing.
+    ///
+    /// Every required namespace of the document type must be present, and every registered
+    /// namespace must satisfy its schema. Namespaces which are not registered are not checked.
+    pub fn validate_namespaces(
+        &self,
+        doc_type: &str,
+        namespaces: &BTreeMap<String, BTreeMap<String, CborValue>>,
+        on: Date,
+    ) -> Result<(), Error> {
+        let doc_type_schema = self
+            .doc_type(doc_type)
+            .ok_or_else(|| Error::UnknownDocType(doc_type.to_string()))?;
+
+        if let Some((missing, _)) = doc_type_schema
+            .namespaces
+            .iter()
+            .find(|(namespace, required)| **required && !namespaces.contains_key(*namespace))
+        {
+            return Err(Error::MissingNamespace(missing.clone()));
+        }
+
+        for (namespace, elements) in namespaces {
+            if let Some(schema) = self.namespace(namespace) {
+                schema.validate(namespace, elements, on)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that every data element requested in a registered namespace is defined, and that
+    /// the reader does not intend to retain any element which must not be retained.
+    pub fn validate_request(
+        &self,
+        doc_type: &str,
+        namespaces: &device_request::Namespaces,
+    ) -> Result<(), Error> {
+        if self.doc_type(doc_type).is_none() {
+            return Err(Error::UnknownDocType(doc_type.to_string()));
+        }
+
+        for (namespace, elements) in namespaces.iter() {
+            let schema = match self.namespace(namespace) {
+                Some(schema) => schema,
+                None => continue,
+            };
+            if let Some(element_identifier) = elements
+                .keys()
+                .find(|element_identifier| schema.get(element_identifier).is_none())
+            {
+                return Err(Error::UnknownElement(
+                    namespace.clone(),
+                    element_identifier.clone(),
+                ));
+            }
+        }
+
+        self.validate_intent_to_retain(namespaces)
+    }
+
+    /// Check that the reader does not intend to retain any data element which must not be
+    /// retained.
+    ///
+    /// Unlike [Registry::validate_request], elements which are not registered are accepted, so
+    /// that an mdoc can apply the policy to requests for documents it does not know about.
+    pub fn validate_intent_to_retain(
+        &self,
+        namespaces: &device_request::Namespaces,
+    ) -> Result<(), Error> {
+        for (namespace, elements) in namespaces.iter() {
+            let schema = match self.namespace(namespace) {
+                Some(schema) => schema,
+                None => continue,
+            };
+            for (element_identifier, intent_to_retain) in elements.iter() {
+                let prohibited = schema
+                    .get(element_identifier)
+                    .is_some_and(|element| element.intent_to_retain == IntentToRetain::Prohibited);
+                if *intent_to_retain && prohibited {
+                    return Err(Error::RetentionProhibited(
+                        namespace.clone(),
+                        element_identifier.clone(),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Registry {
+    /// A registry of the document types and namespaces defined in this crate.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register_doc_type(
+            "org.iso.18013.5.1.mDL",
+            DocTypeSchema::new()
+                .required(OrgIso1801351::NAMESPACE)
+                .optional(OrgIso1801351Aamva::NAMESPACE),
+        );
+        registry.register_doc_type(
+            "eu.europa.ec.eudi.pid.1",
+            DocTypeSchema::new().required(EuEuropaEcEudiPid1::NAMESPACE),
+        );
+        registry.register_doc_type(
+            "org.iso.7367.1.mVRC",
+            DocTypeSchema::new().required(OrgIso73671::NAMESPACE),
+        );
+        registry.register_doc_type(
+            "org.micov.1",
+            DocTypeSchema::new()
+                .optional(OrgMicovVtr1::NAMESPACE)
+                .optional(OrgMicovAttestation1::NAMESPACE),
+        );
+        registry.register_doc_type(
+            "org.iso.23220.photoid.1",
+            DocTypeSchema::new()
+                .required(OrgIso232201::NAMESPACE)
+                .optional(OrgIso23220Photoid1::NAMESPACE),
+        );
+
+        registry.register_namespace(OrgIso1801351::NAMESPACE, OrgIso1801351::schema());
+        registry.register_namespace(OrgIso1801351Aamva::NAMESPACE, OrgIso1801351Aamva::schema());
+        registry.register_namespace(EuEuropaEcEudiPid1::NAMESPACE, EuEuropaEcEudiPid1::schema());
+        registry.register_namespace(OrgIso73671::NAMESPACE, OrgIso73671::schema());
+        registry.register_namespace(OrgMicovVtr1::NAMESPACE, OrgMicovVtr1::schema());
+        registry.register_namespace(
+            OrgMicovAttestation1::NAMESPACE,
+            OrgMicovAttestation1::schema(),
+        );
+        registry.register_namespace(OrgIso232201::NAMESPACE, OrgIso232201::schema());
+        registry.register_namespace(
+            OrgIso23220Photoid1::NAMESPACE,
+            OrgIso23220Photoid1::schema(),
+        );
+
+        registry
+    }
+}
+
+fn matches_pattern(pattern: &str, element_identifier: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            element_identifier.len() > prefix.len() + suffix.len()
+                && element_identifier.starts_with(prefix)
+                && element_identifier.ends_with(suffix)
+        }
+        None => false,
+    }
+}
+
+/// Assert that a namespace schema defines exactly the data elements of a namespace struct, as
+/// listed by the `ELEMENT_IDENTIFIERS` generated by `ToCbor`. Patterns are left to the `many`
+/// fields.
+#[cfg(test)]
+pub(crate) fn assert_schema_covers(schema: &NamespaceSchema, element_identifiers: &[&str]) {
+    for element_identifier in element_identifiers {
+        assert!(
+            schema.elements().contains_key(*element_identifier),
+            "{element_identifier} is missing from the schema"
+        );
+    }
+    for element_identifier in schema.elements().keys() {
+        assert!(
+            element_identifier.contains('*')
+                || element_identifiers.contains(&element_identifier.as_str()),
+            "{element_identifier} is missing from the struct"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::macros::{date, format_description};
+
+    fn registry() -> Registry {
+        let mut registry = Registry::empty();
+        registry.register_doc_type(
+            "com.example.loyalty.1",
+            DocTypeSchema::new()
+                .required("com.example.loyalty.1")
+                .optional("com.example.loyalty.extra.1"),
+        );
+        registry.register_namespace(
+            "com.example.loyalty.1",
+            NamespaceSchema::new()
+                .mandatory("member_id", ElementType::Text)
+                .optional("member_since", ElementType::FullDate)
+                .optional("points_*", ElementType::Uint)
+                .element(
+                    "portrait",
+                    ElementSchema::optional(ElementType::Bytes).retention_prohibited(),
+                )
+                .check(member_since_not_in_future),
+        );
+        registry
+    }
+
+    fn member_since_not_in_future(
+        elements: &BTreeMap<String, CborValue>,
+        on: Date,
+    ) -> Result<(), String> {
+        let member_since = match elements.get("member_since") {
+            Some(CborValue::Tag(_, v)) => match v.as_ref() {
+                CborValue::Text(s) => s,
+                _ => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+        let member_since = Date::parse(member_since, format_description!("[year]-[month]-[day]"))
+            .map_err(|e| e.to_string())?;
+        if member_since > on {
+            return Err("'member_since' is in the future".to_string());
+        }
+        Ok(())
+    }
+
+    fn full_date(date: &str) -> CborValue {
+        CborValue::Tag(1004, Box::new(CborValue::Text(date.to_string())))
+    }
+
+    #[test]
+    fn validate_namespaces() {
+        let registry = registry();
+        let on = date!(2024 - 01 - 01);
+        let elements: BTreeMap<String, CborValue> = [
+            ("member_id".to_string(), CborValue::Text("M123".into())),
+            ("member_since".to_string(), full_date("2020-01-01")),
+            ("points_2024".to_string(), CborValue::Integer(1200)),
+        ]
+        .into_iter()
+        .collect();
+        let mut namespaces = BTreeMap::from([("com.example.loyalty.1".to_string(), elements)]);
+        namespaces.insert("com.example.unregistered".to_string(), BTreeMap::new());
+
+        registry
+            .validate_namespaces("com.example.loyalty.1", &namespaces, on)
+            .unwrap();
+
+        assert_eq!(
+            registry.validate_namespaces("com.example.unknown", &namespaces, on),
+            Err(Error::UnknownDocType("com.example.unknown".to_string()))
+        );
+
+        let mut invalid = namespaces.clone();
+        invalid
+            .get_mut("com.example.loyalty.1")
+            .unwrap()
+            .insert("points_".to_string(), CborValue::Integer(1));
+        assert_eq!(
+            registry.validate_namespaces("com.example.loyalty.1", &invalid, on),
+            Err(Error::UnknownElement(
+                "com.example.loyalty.1".to_string(),
+                "points_".to_string()
+            ))
+        );
+
+        let mut invalid = namespaces.clone();
+        invalid.get_mut("com.example.loyalty.1").unwrap().insert(
+            "member_since".to_string(),
+            CborValue::Text("2020-01-01".into()),
+        );
+        assert_eq!(
+            registry.validate_namespaces("com.example.loyalty.1", &invalid, on),
+            Err(Error::UnexpectedType(
+                "com.example.loyalty.1".to_string(),
+                "member_since".to_string(),
+                ElementType::FullDate
+            ))
+        );
+
+        let mut invalid = namespaces.clone();
+        invalid
+            .get_mut("com.example.loyalty.1")
+            .unwrap()
+            .remove("member_id");
+        assert_eq!(
+            registry.validate_namespaces("com.example.loyalty.1", &invalid, on),
+            Err(Error::MissingElement(
+                "com.example.loyalty.1".to_string(),
+                "member_id".to_string()
+            ))
+        );
+
+        assert_eq!(
+            registry.validate_namespaces(
+                "com.example.loyalty.1",
+                &namespaces,
+                date!(2019 - 12 - 31)
+            ),
+            Err(Error::Inconsistent(
+                "com.example.loyalty.1".to_string(),
+                "'member_since' is in the future".to_string()
+            ))
+        );
+
+        let mut invalid = namespaces;
+        invalid.remove("com.example.loyalty.1");
+        assert_eq!(
+            registry.validate_namespaces("com.example.loyalty.1", &invalid, on),
+            Err(Error::MissingNamespace("com.example.loyalty.1".to_string()))
+        );
+    }
+
+    #[test]
+    fn validate_request() {
+        let registry = registry();
+        let request = |elements: serde_json::Value| -> device_request::Namespaces {
+            serde_json::from_value(serde_json::json!({ "com.example.loyalty.1": elements }))
+                .unwrap()
+        };
+
+        registry
+            .validate_request(
+                "com.example.loyalty.1",
+                &request(serde_json::json!({
+                    "member_id": true,
+                    "points_2024": true,
+                    "portrait": false,
+                })),
+            )
+            .unwrap();
+
+        assert_eq!(
+            registry.validate_request(
+                "com.example.loyalty.1",
+                &request(serde_json::json!({ "portrait": true }))
+            ),
+            Err(Error::RetentionProhibited(
+                "com.example.loyalty.1".to_string(),
+                "portrait".to_string()
+            ))
+        );
+        assert_eq!(
+            registry.validate_request(
+                "com.example.loyalty.1",
+                &request(serde_json::json!({ "nickname": false }))
+            ),
+            Err(Error::UnknownElement(
+                "com.example.loyalty.1".to_string(),
+                "nickname".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn default_retention_policy() {
+        let registry = Registry::default();
+        let request = |intent_to_retain: bool| -> device_request::Namespaces {
+            serde_json::from_value(serde_json::json!({
+                "org.iso.18013.5.1": {
+                    "family_name": true,
+                    "biometric_template_face": intent_to_retain,
+                }
+            }))
+            .unwrap()
+        };
+
+        registry
+            .validate_request("org.iso.18013.5.1.mDL", &request(false))
+            .unwrap();
+        assert_eq!(
+            registry.validate_request("org.iso.18013.5.1.mDL", &request(true)),
+            Err(Error::RetentionProhibited(
+                "org.iso.18013.5.1".to_string(),
+                "biometric_template_face".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn default_doc_types() {
+        let registry = Registry::default();
+        for (doc_type, schema) in &registry.doc_types {
+            for namespace in schema.namespaces().keys() {
+                assert!(
+                    registry.namespace(namespace).is_some(),
+                    "{doc_type} refers to unregistered namespace {namespace}"
+                );
+            }
+        }
+
+        let mdl = registry.namespace(OrgIso1801351::NAMESPACE).unwrap();
+        assert!(mdl.get("family_name").unwrap().mandatory);
+        assert_eq!(
+            mdl.get("age_over_21").unwrap().element_type,
+            ElementType::Bool
+        );
+    }
+}
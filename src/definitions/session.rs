@@ -47,7 +47,7 @@ pub struct SessionData {
     pub status: Option<Status>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(try_from = "u64", into = "u64")]
 pub enum Status {
     SessionEncryptionError,
@@ -101,6 +101,26 @@ pub enum Error {
     EphemeralKeyError,
 }
 
+/// Reasons a received session message could not be decrypted.
+///
+/// The message counter is only advanced when a message is decrypted successfully. Either side
+/// ends the session on any of these errors, with status 10 (session encryption error).
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DecryptionError {
+    #[error("message {0} has already been received")]
+    Replayed(u32),
+    #[error("received message {0} when message {1} was expected")]
+    OutOfOrder(u32, u32),
+    #[error("message could not be decrypted")]
+    Invalid,
+    #[error("the message counter is exhausted")]
+    CounterExhausted,
+}
+
+/// How many messages before and beyond the expected one are tried when identifying a message that
+/// was replayed or received out of order.
+const OUT_OF_ORDER_WINDOW: u32 = 16;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Handover {
@@ -209,7 +229,8 @@ fn encrypt(
     message_count: &mut u32,
     reader: bool,
 ) -> Result<Vec<u8>, aes_gcm::Error> {
-    let initialization_vector = get_initialization_vector(message_count, reader);
+    let initialization_vector =
+        get_initialization_vector(message_count, reader).ok_or(aes_gcm::Error)?;
     let nonce = Nonce::from(initialization_vector);
     Aes256Gcm::new(session_key).encrypt(&nonce, plaintext)
 }
@@ -218,7 +239,7 @@ pub fn decrypt_device_data(
     sk_device: &GenericArray<u8, U32>,
     ciphertext: &[u8],
     message_count: &mut u32,
-) -> Result<Vec<u8>, aes_gcm::Error> {
+) -> Result<Vec<u8>, DecryptionError> {
     decrypt(sk_device, ciphertext, message_count, false)
 }
 
@@ -226,7 +247,7 @@ pub fn decrypt_reader_data(
     sk_reader: &GenericArray<u8, U32>,
     ciphertext: &[u8],
     message_count: &mut u32,
-) -> Result<Vec<u8>, aes_gcm::Error> {
+) -> Result<Vec<u8>, DecryptionError> {
     decrypt(sk_reader, ciphertext, message_count, true)
 }
 
@@ -235,14 +256,41 @@ fn decrypt(
     ciphertext: &[u8],
     message_count: &mut u32,
     reader: bool,
-) -> Result<Vec<u8>, aes_gcm::Error> {
-    let initialization_vector = get_initialization_vector(message_count, reader);
-    let nonce = Nonce::from(initialization_vector);
-    Aes256Gcm::new(session_key).decrypt(&nonce, ciphertext)
+) -> Result<Vec<u8>, DecryptionError> {
+    let cipher = Aes256Gcm::new(session_key);
+    let decrypt_as = |mut count: u32| {
+        let nonce = Nonce::from(get_initialization_vector(&mut count, reader)?);
+        cipher.decrypt(&nonce, ciphertext).ok()
+    };
+
+    let next = message_count
+        .checked_add(1)
+        .ok_or(DecryptionError::CounterExhausted)?;
+    if let Some(plaintext) = decrypt_as(*message_count) {
+        *message_count = next;
+        return Ok(plaintext);
+    }
+
+    // The counter is not transmitted, so a replayed or reordered message only shows up as an
+    // authentication failure. Try the neighbouring counters to tell the caller which it was.
+    if let Some(count) = (message_count.saturating_sub(OUT_OF_ORDER_WINDOW)..*message_count)
+        .rev()
+        .find(|count| decrypt_as(*count).is_some())
+    {
+        return Err(DecryptionError::Replayed(count + 1));
+    }
+    (1..=OUT_OF_ORDER_WINDOW)
+        .filter_map(|offset| message_count.checked_add(offset))
+        .find(|count| decrypt_as(*count).is_some())
+        .map_or(Err(DecryptionError::Invalid), |count| {
+            Err(DecryptionError::OutOfOrder(count + 1, next))
+        })
 }
 
-pub fn get_initialization_vector(message_count: &mut u32, reader: bool) -> [u8; 12] {
-    *message_count += 1;
+/// Advance the message counter and derive the initialization vector of the next message, or
+/// `None` if the counter is exhausted.
+pub fn get_initialization_vector(message_count: &mut u32, reader: bool) -> Option<[u8; 12]> {
+    *message_count = message_count.checked_add(1)?;
     let counter = GenericArray::from(message_count.to_be_bytes());
     let identifier = if reader {
         GenericArray::from([0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8])
@@ -250,7 +298,7 @@ pub fn get_initialization_vector(message_count: &mut u32, reader: bool) -> [u8;
         GenericArray::from([0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 1u8])
     };
 
-    identifier.concat(counter).into()
+    Some(identifier.concat(counter).into())
 }
 
 #[cfg(test)]
@@ -431,6 +479,83 @@ mod test {
         assert_eq!(plaintext, decrypted_plaintext);
     }
 
+    #[test]
+    fn old_replayed_messages() {
+        let session_key = GenericArray::from([7u8; 32]);
+        let mut sender_count = 0;
+        let messages: Vec<Vec<u8>> = (0..=OUT_OF_ORDER_WINDOW)
+            .map(|_| encrypt_device_data(&session_key, &[], &mut sender_count).unwrap())
+            .collect();
+
+        let mut receiver_count = 0;
+        for message in &messages {
+            decrypt_device_data(&session_key, message, &mut receiver_count).unwrap();
+        }
+        // Only the most recent messages are checked for replays.
+        assert_eq!(
+            decrypt_device_data(&session_key, &messages[1], &mut receiver_count),
+            Err(DecryptionError::Replayed(2))
+        );
+        assert_eq!(
+            decrypt_device_data(&session_key, &messages[0], &mut receiver_count),
+            Err(DecryptionError::Invalid)
+        );
+    }
+
+    #[test]
+    fn replayed_and_out_of_order_messages() {
+        let session_key = GenericArray::from([7u8; 32]);
+        let mut sender_count = 0;
+        let messages: Vec<Vec<u8>> = (0..3u8)
+            .map(|i| encrypt_device_data(&session_key, &[i], &mut sender_count).unwrap())
+            .collect();
+
+        let mut receiver_count = 0;
+        assert_eq!(
+            decrypt_device_data(&session_key, &messages[1], &mut receiver_count),
+            Err(DecryptionError::OutOfOrder(2, 1))
+        );
+        assert_eq!(
+            decrypt_device_data(&session_key, &messages[0], &mut receiver_count),
+            Ok(vec![0])
+        );
+        assert_eq!(
+            decrypt_device_data(&session_key, &messages[0], &mut receiver_count),
+            Err(DecryptionError::Replayed(1))
+        );
+        assert_eq!(
+            decrypt_device_data(&session_key, &[0u8; 17], &mut receiver_count),
+            Err(DecryptionError::Invalid)
+        );
+        // Messages from the other party use a different nonce identifier.
+        assert_eq!(
+            decrypt_reader_data(&session_key, &messages[1], &mut receiver_count),
+            Err(DecryptionError::Invalid)
+        );
+        assert_eq!(
+            decrypt_device_data(&session_key, &messages[1], &mut receiver_count),
+            Ok(vec![1])
+        );
+        assert_eq!(receiver_count, 2);
+    }
+
+    #[test]
+    fn exhausted_message_counter() {
+        let session_key = GenericArray::from([7u8; 32]);
+        let mut sender_count = u32::MAX - 1;
+        let message = encrypt_device_data(&session_key, &[], &mut sender_count).unwrap();
+        assert_eq!(sender_count, u32::MAX);
+        assert!(encrypt_device_data(&session_key, &[], &mut sender_count).is_err());
+        assert_eq!(sender_count, u32::MAX);
+
+        let mut receiver_count = u32::MAX - 1;
+        decrypt_device_data(&session_key, &message, &mut receiver_count).unwrap();
+        assert_eq!(
+            decrypt_device_data(&session_key, &message, &mut receiver_count),
+            Err(DecryptionError::CounterExhausted)
+        );
+    }
+
     #[test]
     fn handle_session_establishment_and_decrypt_device_request() {
         const E_DEVICE_KEY: &str = include_str!("../../test/definitions/session/e_device_key.cbor");
@@ -0,0 +1,408 @@
+//! Credential status using the Token Status List mechanism (draft-ietf-oauth-status-list), as
+//! referenced from the `status` entry of the MSO in the second edition of ISO/IEC 18013-5.
+use crate::definitions::helpers::ByteStr;
+use cose_rs::CoseSign1;
+use flate2::read::ZlibDecoder;
+use serde::{Deserialize, Serialize};
+use serde_cbor::Value as CborValue;
+use std::{collections::BTreeMap, io::Read};
+use time::OffsetDateTime;
+
+/// CWT claim key for the subject, which is the URI of the status list.
+pub const SUB: i128 = 2;
+/// CWT claim key for the expiry time.
+pub const EXP: i128 = 4;
+/// CWT claim key for the time of issuance.
+pub const IAT: i128 = 6;
+/// CWT claim key for the time to live, in seconds.
+pub const TTL: i128 = 65534;
+/// CWT claim key for the status list.
+pub const STATUS_LIST: i128 = 65533;
+
+/// Status information carried in the MSO.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_list: Option<StatusListInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identifier_list: Option<IdentifierListInfo>,
+}
+
+/// A reference to the entry for the mdoc in a status list.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusListInfo {
+    pub idx: u64,
+    pub uri: String,
+}
+
+/// A reference to the identifier for the mdoc in an identifier list.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentifierListInfo {
+    pub id: ByteStr,
+    pub uri: String,
+}
+
+/// The number of bits used to encode each status in a status list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum Bits {
+    One,
+    Two,
+    Four,
+    Eight,
+}
+
+/// The status of a credential, as recorded in a status list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CredentialStatus {
+    Valid,
+    Invalid,
+    Suspended,
+    /// A status value that is application-specific or not yet registered.
+    Other(u8),
+}
+
+/// A compressed list of credential statuses.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusList {
+    pub bits: Bits,
+    pub lst: ByteStr,
+}
+
+/// The payload of a status list token, which is a CWT signed by the issuer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusListToken {
+    /// The URI that the status list is published at.
+    pub sub: String,
+    /// Time of issuance, in seconds since the epoch.
+    pub iat: i64,
+    /// Time of expiry, in seconds since the epoch.
+    pub exp: Option<i64>,
+    /// How long the token may be cached for, in seconds.
+    pub ttl: Option<u64>,
+    pub status_list: StatusList,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unable to decompress status list: {0}")]
+    Decompression(std::io::Error),
+    #[error("index {0} is not in the status list")]
+    IndexOutOfRange(u64),
+    #[error("the mdoc does not reference a status list")]
+    NoStatusList,
+    #[error("status list token is for '{0}', but the mdoc references '{1}'")]
+    UriMismatch(String, String),
+    #[error("status list token has expired")]
+    Expired,
+    #[error("status list token has no payload")]
+    MissingPayload,
+    #[error("unable to decode status list token: {0}")]
+    Malformed(&'static str),
+    #[error("unable to decode status list token: {0}")]
+    Cbor(#[from] serde_cbor::Error),
+}
+
+impl Bits {
+    fn mask(self) -> u8 {
+        match self {
+            Bits::One => 0b1,
+            Bits::Two => 0b11,
+            Bits::Four => 0b1111,
+            Bits::Eight => 0b1111_1111,
+        }
+    }
+}
+
+impl From<Bits> for u8 {
+    fn from(b: Bits) -> u8 {
+        match b {
+            Bits::One => 1,
+            Bits::Two => 2,
+            Bits::Four => 4,
+            Bits::Eight => 8,
+        }
+    }
+}
+
+impl TryFrom<u8> for Bits {
+    type Error = String;
+
+    fn try_from(n: u8) -> Result<Bits, String> {
+        match n {
+            1 => Ok(Bits::One),
+            2 => Ok(Bits::Two),
+            4 => Ok(Bits::Four),
+            8 => Ok(Bits::Eight),
+            _ => Err(format!("unsupported number of bits per status: {n}")),
+        }
+    }
+}
+
+impl From<u8> for CredentialStatus {
+    fn from(n: u8) -> CredentialStatus {
+        match n {
+            0 => CredentialStatus::Valid,
+            1 => CredentialStatus::Invalid,
+            2 => CredentialStatus::Suspended,
+            n => CredentialStatus::Other(n),
+        }
+    }
+}
+
+impl From<CredentialStatus> for u8 {
+    fn from(s: CredentialStatus) -> u8 {
+        match s {
+            CredentialStatus::Valid => 0,
+            CredentialStatus::Invalid => 1,
+            CredentialStatus::Suspended => 2,
+            CredentialStatus::Other(n) => n,
+        }
+    }
+}
+
+impl StatusList {
+    /// Decompress the list, returning the raw status values in index order.
+    pub fn statuses(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        ZlibDecoder::new(self.lst.as_ref())
+            .read_to_end(&mut bytes)
+            .map_err(Error::Decompression)?;
+
+        let bits = u8::from(self.bits);
+        let per_byte = 8 / bits;
+        let mask = self.bits.mask();
+        Ok(bytes
+            .into_iter()
+            .flat_map(|byte| (0..per_byte).map(move |i| (byte >> (i * bits)) & mask))
+            .collect())
+    }
+
+    /// Look up the status at the given index.
+    pub fn status(&self, idx: u64) -> Result<CredentialStatus, Error> {
+        let i = usize::try_from(idx).map_err(|_| Error::IndexOutOfRange(idx))?;
+        self.statuses()?
+            .get(i)
+            .map(|status| CredentialStatus::from(*status))
+            .ok_or(Error::IndexOutOfRange(idx))
+    }
+}
+
+impl StatusListToken {
+    /// Decode the status list token from its COSE_Sign1 envelope.
+    ///
+    /// The signature is not verified here, and should be checked against the issuer's
+    /// certificate before the token is relied upon.
+    pub fn from_cose_sign1(token: &CoseSign1) -> Result<Self, Error> {
+        let payload = token.payload().ok_or(Error::MissingPayload)?;
+        serde_cbor::from_slice::<CborValue>(payload)?.try_into()
+    }
+
+    /// Check the status of an mdoc, given the `status` entry from its MSO.
+    pub fn check(&self, status: &Status, now: OffsetDateTime) -> Result<CredentialStatus, Error> {
+        let info = status.status_list.as_ref().ok_or(Error::NoStatusList)?;
+        if info.uri != self.sub {
+            return Err(Error::UriMismatch(self.sub.clone(), info.uri.clone()));
+        }
+        if matches!(self.exp, Some(exp) if exp <= now.unix_timestamp()) {
+            return Err(Error::Expired);
+        }
+        self.status_list.status(info.idx)
+    }
+}
+
+impl From<StatusListToken> for CborValue {
+    fn from(token: StatusListToken) -> CborValue {
+        let mut claims = BTreeMap::new();
+        claims.insert(CborValue::Integer(SUB), CborValue::Text(token.sub));
+        claims.insert(
+            CborValue::Integer(IAT),
+            CborValue::Integer(token.iat.into()),
+        );
+        if let Some(exp) = token.exp {
+            claims.insert(CborValue::Integer(EXP), CborValue::Integer(exp.into()));
+        }
+        if let Some(ttl) = token.ttl {
+            claims.insert(CborValue::Integer(TTL), CborValue::Integer(ttl.into()));
+        }
+        claims.insert(
+            CborValue::Integer(STATUS_LIST),
+            CborValue::Map(
+                [
+                    (
+                        CborValue::Text("bits".to_string()),
+                        CborValue::Integer(u8::from(token.status_list.bits).into()),
+                    ),
+                    (
+                        CborValue::Text("lst".to_string()),
+                        token.status_list.lst.into(),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        );
+        CborValue::Map(claims)
+    }
+}
+
+impl TryFrom<CborValue> for StatusListToken {
+    type Error = Error;
+
+    fn try_from(v: CborValue) -> Result<Self, Error> {
+        let mut claims: BTreeMap<CborValue, CborValue> = match v {
+            CborValue::Map(claims) => claims,
+            _ => return Err(Error::Malformed("claims are not a map")),
+        };
+        let mut claim = |key: i128| claims.remove(&CborValue::Integer(key));
+
+        let sub = match claim(SUB) {
+            Some(CborValue::Text(sub)) => sub,
+            _ => return Err(Error::Malformed("missing or invalid 'sub' claim")),
+        };
+        let iat = match claim(IAT) {
+            Some(CborValue::Integer(iat)) => {
+                i64::try_from(iat).map_err(|_| Error::Malformed("'iat' claim is out of range"))?
+            }
+            _ => return Err(Error::Malformed("missing or invalid 'iat' claim")),
+        };
+        let exp = match claim(EXP) {
+            Some(CborValue::Integer(exp)) => Some(
+                i64::try_from(exp).map_err(|_| Error::Malformed("'exp' claim is out of range"))?,
+            ),
+            None => None,
+            _ => return Err(Error::Malformed("invalid 'exp' claim")),
+        };
+        let ttl = match claim(TTL) {
+            Some(CborValue::Integer(ttl)) => Some(
+                u64::try_from(ttl).map_err(|_| Error::Malformed("'ttl' claim is out of range"))?,
+            ),
+            None => None,
+            _ => return Err(Error::Malformed("invalid 'ttl' claim")),
+        };
+        let status_list = claim(STATUS_LIST)
+            .ok_or(Error::Malformed("missing 'status_list' claim"))
+            .and_then(|v| serde_cbor::value::from_value(v).map_err(Into::into))?;
+
+        Ok(StatusListToken {
+            sub,
+            iat,
+            exp,
+            ttl,
+            status_list,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    // The 1-bit example from draft-ietf-oauth-status-list:
+    // statuses [1, 0, 0, 1, 1, 1, 0, 1, 1, 1, 0, 0, 0, 1, 0, 1].
+    static ONE_BIT_LIST: &str = "78dadbb918000217015d";
+
+    fn compress(bytes: &[u8]) -> ByteStr {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap().into()
+    }
+
+    #[test]
+    fn one_bit() {
+        let list = StatusList {
+            bits: Bits::One,
+            lst: hex::decode(ONE_BIT_LIST).unwrap().into(),
+        };
+        assert_eq!(
+            list.statuses().unwrap(),
+            vec![1, 0, 0, 1, 1, 1, 0, 1, 1, 1, 0, 0, 0, 1, 0, 1]
+        );
+        assert_eq!(list.status(0).unwrap(), CredentialStatus::Invalid);
+        assert_eq!(list.status(1).unwrap(), CredentialStatus::Valid);
+        assert!(matches!(list.status(16), Err(Error::IndexOutOfRange(16))));
+    }
+
+    #[test]
+    fn two_bit() {
+        // statuses [1, 2, 0, 3, 0, 1, 0, 1, 1, 2, 3, 3]
+        let list = StatusList {
+            bits: Bits::Two,
+            lst: compress(&[0xc9, 0x44, 0xf9]),
+        };
+        assert_eq!(
+            list.statuses().unwrap(),
+            vec![1, 2, 0, 3, 0, 1, 0, 1, 1, 2, 3, 3]
+        );
+        assert_eq!(list.status(1).unwrap(), CredentialStatus::Suspended);
+        assert_eq!(list.status(3).unwrap(), CredentialStatus::Other(3));
+    }
+
+    #[test]
+    fn check_token() {
+        let token: StatusListToken = CborValue::Map(
+            [
+                (
+                    SUB,
+                    CborValue::Text("https://example.com/statuslists/1".into()),
+                ),
+                (IAT, CborValue::Integer(1686920170)),
+                (EXP, CborValue::Integer(2291720170)),
+                (
+                    STATUS_LIST,
+                    serde_cbor::value::to_value(StatusList {
+                        bits: Bits::One,
+                        lst: hex::decode(ONE_BIT_LIST).unwrap().into(),
+                    })
+                    .unwrap(),
+                ),
+            ]
+            .into_iter()
+            .map(|(k, v)| (CborValue::Integer(k), v))
+            .collect(),
+        )
+        .try_into()
+        .unwrap();
+
+        let status = |idx| Status {
+            status_list: Some(StatusListInfo {
+                idx,
+                uri: "https://example.com/statuslists/1".into(),
+            }),
+            identifier_list: None,
+        };
+        let now = OffsetDateTime::from_unix_timestamp(1700000000).unwrap();
+        assert_eq!(
+            token.check(&status(0), now).unwrap(),
+            CredentialStatus::Invalid
+        );
+        assert_eq!(
+            token.check(&status(2), now).unwrap(),
+            CredentialStatus::Valid
+        );
+
+        let expired = OffsetDateTime::from_unix_timestamp(2291720170).unwrap();
+        assert!(matches!(
+            token.check(&status(2), expired),
+            Err(Error::Expired)
+        ));
+
+        let other_list = Status {
+            status_list: Some(StatusListInfo {
+                idx: 0,
+                uri: "https://example.com/statuslists/2".into(),
+            }),
+            identifier_list: None,
+        };
+        assert!(matches!(
+            token.check(&other_list, now),
+            Err(Error::UriMismatch(..))
+        ));
+        assert!(matches!(
+            token.check(&Status::default(), now),
+            Err(Error::NoStatusList)
+        ));
+    }
+}
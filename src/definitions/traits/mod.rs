@@ -1,5 +1,7 @@
 mod from_json;
+mod namespace;
 mod to_cbor;
 
 pub use from_json::{FromJson, FromJsonError, FromJsonMap};
+pub use namespace::Namespace;
 pub use to_cbor::{ToCbor, ToCborError, ToCborMap, ToNamespaceMap};
@@ -0,0 +1,7 @@
+use super::ToNamespaceMap;
+
+/// A typed representation of the data elements of a namespace, which can be issued in an mdoc.
+pub trait Namespace: ToNamespaceMap {
+    /// The namespace identifier, e.g. `org.iso.18013.5.1`.
+    const NAMESPACE: &'static str;
+}
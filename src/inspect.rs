@@ -0,0 +1,970 @@
+//! Decoding of ISO/IEC 18013-5 CBOR structures for debugging.
+//!
+//! [inspect] detects which structure an encoded value is, checks that it decodes as that
+//! structure, and renders it in CBOR diagnostic notation ([RFC 8949 § 8](https://www.rfc-editor.org/rfc/rfc8949#section-8))
+//! or as JSON. Embedded CBOR, such as the contents of Tag24 values and the protected header and
+//! payload of COSE_Sign1 structures, is decoded in place, and the integer labels of COSE keys,
+//! COSE headers and device engagements are annotated with their names.
+//!
+//! [decrypt_session] derives the session keys of a captured exchange from one of its ephemeral
+//! private keys, and decrypts each of its messages for inspection.
+use crate::definitions::{
+    device_engagement::DeviceEngagement,
+    device_key::CoseKey,
+    device_request::DeviceRequest,
+    helpers::Tag24,
+    issuer_signed::IssuerSigned,
+    session::{
+        self, DecryptionError, DeviceEngagementBytes, Handover, SessionData, SessionEstablishment,
+        SessionTranscript180135,
+    },
+    DeviceResponse, Mso,
+};
+use anyhow::{anyhow, Result};
+use p256::elliptic_curve::sec1::FromEncodedPoint;
+use serde::de::DeserializeOwned;
+use serde_cbor::Value as CborValue;
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+const TAG24: u64 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The structures that can be inspected.
+pub enum Structure {
+    DeviceEngagement,
+    SessionEstablishment,
+    SessionData,
+    DeviceRequest,
+    DeviceResponse,
+    IssuerSigned,
+    Mso,
+    CoseKey,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Session keys with which to decrypt the data of SessionEstablishment and SessionData messages.
+pub struct SessionKeys {
+    pub sk_reader: Option<[u8; 32]>,
+    pub sk_device: Option<[u8; 32]>,
+    /// The number of messages previously encrypted with the same key. Messages within a few of
+    /// this are also found.
+    pub message_counter: u32,
+}
+
+#[derive(Debug, Clone)]
+/// A decoded structure.
+pub struct Inspection {
+    pub structure: Structure,
+    pub value: CborValue,
+    /// The decrypted data of a SessionEstablishment or SessionData message.
+    pub decrypted: Option<Box<Inspection>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The names of the integer labels of a map.
+enum Labels {
+    None,
+    CoseKey,
+    CoseHeaders,
+    DeviceEngagement,
+}
+
+impl Structure {
+    const ALL: [Structure; 8] = [
+        Structure::DeviceEngagement,
+        Structure::SessionEstablishment,
+        Structure::SessionData,
+        Structure::DeviceRequest,
+        Structure::DeviceResponse,
+        Structure::IssuerSigned,
+        Structure::Mso,
+        Structure::CoseKey,
+    ];
+
+    /// Detect the structure of a value from the keys of its map, after unwrapping any Tag24.
+    pub fn detect(value: &CborValue) -> Option<Self> {
+        let map = match unwrap_tag24(value)? {
+            CborValue::Map(map) => map,
+            _ => return None,
+        };
+        let has = |key: &str| map.contains_key(&CborValue::Text(key.to_string()));
+        let has_int = |key: i128| map.contains_key(&CborValue::Integer(key));
+        if has("eReaderKey") {
+            Some(Self::SessionEstablishment)
+        } else if has("docRequests") {
+            Some(Self::DeviceRequest)
+        } else if has("version") && has("status") {
+            Some(Self::DeviceResponse)
+        } else if has("issuerAuth") {
+            Some(Self::IssuerSigned)
+        } else if has("valueDigests") {
+            Some(Self::Mso)
+        } else if !map.is_empty()
+            && map
+                .keys()
+                .all(|key| matches!(key, CborValue::Text(key) if key == "data" || key == "status"))
+        {
+            Some(Self::SessionData)
+        } else if has_int(1) && (has_int(-1) || has_int(-2)) {
+            Some(Self::CoseKey)
+        } else if has_int(0) && has_int(1) {
+            Some(Self::DeviceEngagement)
+        } else {
+            None
+        }
+    }
+
+    /// Check that a value decodes as this structure.
+    fn validate(self, value: &CborValue) -> Result<()> {
+        fn decode<T: DeserializeOwned>(value: &CborValue) -> Result<()> {
+            serde_cbor::value::from_value::<T>(value.clone())
+                .map(|_| ())
+                .map_err(Into::into)
+        }
+        let value = &unwrap_tag24(value).ok_or_else(|| anyhow!("invalid Tag24 contents"))?;
+        match self {
+            Self::DeviceEngagement => decode::<DeviceEngagement>(value),
+            Self::SessionEstablishment => decode::<SessionEstablishment>(value),
+            Self::SessionData => decode::<SessionData>(value),
+            Self::DeviceRequest => decode::<DeviceRequest>(value),
+            Self::DeviceResponse => decode::<DeviceResponse>(value),
+            Self::IssuerSigned => decode::<IssuerSigned>(value),
+            Self::Mso => decode::<Mso>(value),
+            Self::CoseKey => CoseKey::try_from(value.clone())
+                .map(|_| ())
+                .map_err(|e| anyhow!("{:?}", e)),
+        }
+        .map_err(|e| anyhow!("not a valid {self}: {e}"))
+    }
+}
+
+impl fmt::Display for Structure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::DeviceEngagement => "DeviceEngagement",
+            Self::SessionEstablishment => "SessionEstablishment",
+            Self::SessionData => "SessionData",
+            Self::DeviceRequest => "DeviceRequest",
+            Self::DeviceResponse => "DeviceResponse",
+            Self::IssuerSigned => "IssuerSigned",
+            Self::Mso => "MobileSecurityObject",
+            Self::CoseKey => "COSE_Key",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Structure {
+    type Err = anyhow::Error;
+
+    /// Parse a structure from its name, ignoring case, underscores and hyphens.
+    fn from_str(s: &str) -> Result<Self> {
+        let normalize = |s: &str| s.replace(['_', '-'], "").to_lowercase();
+        let name = normalize(s);
+        Self::ALL
+            .into_iter()
+            .find(|structure| {
+                normalize(&structure.to_string()) == name
+                    || (*structure == Self::Mso && name == "mso")
+            })
+            .ok_or_else(|| anyhow!("unknown structure: {s}"))
+    }
+}
+
+/// Decode a structure, detecting which it is unless it is given.
+pub fn inspect(
+    bytes: &[u8],
+    structure: Option<Structure>,
+    keys: &SessionKeys,
+) -> Result<Inspection> {
+    let value: CborValue = serde_cbor::from_slice(bytes)?;
+    let structure = match structure {
+        Some(structure) => structure,
+        None => Structure::detect(&value)
+            .ok_or_else(|| anyhow!("unable to detect the structure of the value"))?,
+    };
+    structure.validate(&value)?;
+
+    let decrypted = match (structure, message_data(&value)) {
+        (Structure::SessionEstablishment | Structure::SessionData, Some(data)) => {
+            decrypt(&data, keys)?
+                .map(|plaintext| inspect(&plaintext, None, keys).map(Box::new))
+                .transpose()?
+        }
+        _ => None,
+    };
+
+    Ok(Inspection {
+        structure,
+        value,
+        decrypted,
+    })
+}
+
+/// Decode the DeviceEngagement of a QR code URI of the form `mdoc:<base64url>`.
+pub fn inspect_qr_code_uri(qr_code_uri: &str) -> Result<Inspection> {
+    let encoded = qr_code_uri
+        .strip_prefix("mdoc:")
+        .ok_or_else(|| anyhow!("qr code has invalid prefix"))?;
+    let bytes = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD)?;
+    inspect(
+        &bytes,
+        Some(Structure::DeviceEngagement),
+        &SessionKeys::default(),
+    )
+}
+
+/// Decrypt the data of a session message with whichever of the session keys it was encrypted
+/// with, or `None` if no session key was given.
+fn decrypt(data: &[u8], keys: &SessionKeys) -> Result<Option<Vec<u8>>> {
+    let candidates = [
+        keys.sk_reader
+            .map(|key| (key, session::decrypt_reader_data as DecryptFn)),
+        keys.sk_device
+            .map(|key| (key, session::decrypt_device_data as DecryptFn)),
+    ];
+    if candidates.iter().all(Option::is_none) {
+        return Ok(None);
+    }
+    for (key, decrypt) in candidates.into_iter().flatten() {
+        let mut counter = keys.message_counter;
+        if let Some((_, plaintext)) = decrypt_message(decrypt, &key, data, &mut counter) {
+            return Ok(Some(plaintext));
+        }
+    }
+    Err(anyhow!(
+        "unable to decrypt the session data with the given session keys"
+    ))
+}
+
+/// Decrypt a message, returning the counter it was encrypted under along with its plaintext.
+///
+/// A message that was encrypted under an earlier or later counter than expected is decrypted
+/// all the same, which is fine for inspection. The expected counter is advanced past the message.
+fn decrypt_message(
+    decrypt: DecryptFn,
+    key: &[u8; 32],
+    data: &[u8],
+    counter: &mut u32,
+) -> Option<(u32, Vec<u8>)> {
+    let mut count = *counter;
+    let plaintext = match decrypt(&(*key).into(), data, &mut count) {
+        Ok(plaintext) => plaintext,
+        Err(DecryptionError::Replayed(found) | DecryptionError::OutOfOrder(found, _)) => {
+            count = found - 1;
+            decrypt(&(*key).into(), data, &mut count).ok()?
+        }
+        Err(DecryptionError::Invalid | DecryptionError::CounterExhausted) => return None,
+    };
+    *counter = (*counter).max(count);
+    Some((count, plaintext))
+}
+
+type DecryptFn = fn(
+    &aes_gcm::aead::generic_array::GenericArray<u8, aes_gcm::aead::consts::U32>,
+    &[u8],
+    &mut u32,
+) -> Result<Vec<u8>, DecryptionError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The party that sent a session message.
+pub enum Sender {
+    Reader,
+    Device,
+}
+
+#[derive(Debug, Clone)]
+/// A session that has been decrypted with one of its ephemeral private keys.
+pub struct DecryptedSession {
+    /// The session keys derived from the ephemeral keys and the session transcript.
+    pub keys: SessionKeys,
+    /// The SessionEstablishment followed by each SessionData message, in the order given.
+    pub messages: Vec<SessionMessage>,
+}
+
+#[derive(Debug, Clone)]
+/// A SessionEstablishment or SessionData message, with its data decrypted if it could be.
+pub struct SessionMessage {
+    /// The sender of the data, if it could be decrypted.
+    pub sender: Option<Sender>,
+    /// The message counter that the data was encrypted under.
+    pub counter: Option<u32>,
+    pub inspection: Inspection,
+}
+
+/// Decrypt a captured session, given the ephemeral private key of either the device or the
+/// reader.
+///
+/// The session transcript is rebuilt from the device engagement, the eReaderKey of the
+/// SessionEstablishment and the handover, from which SKReader and SKDevice are derived. Messages
+/// that cannot be decrypted with either key are still decoded, without their data.
+pub fn decrypt_session(
+    device_engagement: DeviceEngagementBytes,
+    handover: Handover,
+    ephemeral_key: &p256::SecretKey,
+    session_establishment: &[u8],
+    messages: &[Vec<u8>],
+) -> Result<DecryptedSession> {
+    let establishment: SessionEstablishment = serde_cbor::from_slice(session_establishment)
+        .map_err(|e| anyhow!("not a valid SessionEstablishment: {e}"))?;
+    let e_device_key = device_engagement.as_ref().security.1.as_ref().clone();
+    let e_reader_key = establishment.e_reader_key.as_ref().clone();
+
+    let public_key = ephemeral_key.public_key();
+    let peer_key = if to_public_key(&e_device_key) == Some(public_key) {
+        e_reader_key
+    } else if to_public_key(&e_reader_key) == Some(public_key) {
+        e_device_key
+    } else {
+        return Err(anyhow!(
+            "the ephemeral key is neither the eDeviceKey nor the eReaderKey of the session"
+        ));
+    };
+
+    let session_transcript = Tag24::new(SessionTranscript180135(
+        device_engagement,
+        establishment.e_reader_key,
+        handover,
+    ))?;
+    let shared_secret = session::get_shared_secret(peer_key, &ephemeral_key.to_nonzero_scalar())?;
+    let sk_reader: [u8; 32] =
+        session::derive_session_key(&shared_secret, &session_transcript, true)?.into();
+    let sk_device: [u8; 32] =
+        session::derive_session_key(&shared_secret, &session_transcript, false)?.into();
+
+    let mut reader_counter = 0;
+    let mut device_counter = 0;
+    let messages = std::iter::once(session_establishment)
+        .chain(messages.iter().map(Vec::as_slice))
+        .enumerate()
+        .map(|(index, bytes)| {
+            let expected = if index == 0 {
+                Structure::SessionEstablishment
+            } else {
+                Structure::SessionData
+            };
+            let mut inspection = inspect(bytes, Some(expected), &SessionKeys::default())
+                .map_err(|e| anyhow!("message {index}: {e}"))?;
+            let data = match message_data(&inspection.value) {
+                Some(data) => data,
+                None => {
+                    return Ok(SessionMessage {
+                        sender: None,
+                        counter: None,
+                        inspection,
+                    })
+                }
+            };
+
+            let decrypted = decrypt_message(
+                session::decrypt_reader_data,
+                &sk_reader,
+                &data,
+                &mut reader_counter,
+            )
+            .map(|(counter, plaintext)| (Sender::Reader, counter, plaintext))
+            .or_else(|| {
+                decrypt_message(
+                    session::decrypt_device_data,
+                    &sk_device,
+                    &data,
+                    &mut device_counter,
+                )
+                .map(|(counter, plaintext)| (Sender::Device, counter, plaintext))
+            });
+            let (sender, counter) = match decrypted {
+                Some((sender, counter, plaintext)) => {
+                    let decrypted = inspect(&plaintext, None, &SessionKeys::default())
+                        .map_err(|e| anyhow!("message {index}: decrypted data: {e}"))?;
+                    inspection.decrypted = Some(Box::new(decrypted));
+                    (Some(sender), Some(counter))
+                }
+                None => (None, None),
+            };
+            Ok(SessionMessage {
+                sender,
+                counter,
+                inspection,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(DecryptedSession {
+        keys: SessionKeys {
+            sk_reader: Some(sk_reader),
+            sk_device: Some(sk_device),
+            message_counter: 0,
+        },
+        messages,
+    })
+}
+
+fn to_public_key(cose_key: &CoseKey) -> Option<p256::PublicKey> {
+    let encoded_point = p256::EncodedPoint::try_from(cose_key.clone()).ok()?;
+    p256::PublicKey::from_encoded_point(&encoded_point).into()
+}
+
+impl DecryptedSession {
+    /// Render each message in CBOR diagnostic notation, headed by its sender and counter.
+    pub fn to_diagnostic(&self) -> String {
+        self.messages
+            .iter()
+            .enumerate()
+            .map(|(index, message)| {
+                let heading = match (message.sender, message.counter) {
+                    (Some(sender), Some(counter)) => {
+                        format!("/ message {index}: {sender:?} message {counter} /\n")
+                    }
+                    _ if message_data(&message.inspection.value).is_some() => {
+                        format!("/ message {index}: data could not be decrypted /\n")
+                    }
+                    _ => format!("/ message {index} /\n"),
+                };
+                heading + &message.inspection.to_diagnostic()
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Render the session keys and each message as JSON.
+    pub fn to_json(&self) -> Value {
+        let key = |key: Option<[u8; 32]>| key.map(hex::encode);
+        json!({
+            "skReader": key(self.keys.sk_reader),
+            "skDevice": key(self.keys.sk_device),
+            "messages": self.messages.iter().map(|message| {
+                let mut object = message.inspection.to_json();
+                object["sender"] = json!(message.sender.map(|sender| format!("{sender:?}")));
+                object["counter"] = json!(message.counter);
+                object
+            }).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl Inspection {
+    /// Render the structure in CBOR diagnostic notation, with the names of integer labels given
+    /// in comments.
+    pub fn to_diagnostic(&self) -> String {
+        let mut out = format!("/ {} /\n", self.structure);
+        diagnostic(&self.value, self.labels(), 0, &mut out);
+        if let Some(decrypted) = &self.decrypted {
+            out.push_str("\n\n/ decrypted data /\n");
+            out.push_str(&decrypted.to_diagnostic());
+        }
+        out
+    }
+
+    /// Render the structure as JSON, with byte strings in hex and the integer labels of maps
+    /// replaced with their names.
+    pub fn to_json(&self) -> Value {
+        let mut object = json!({
+            "structure": self.structure.to_string(),
+            "value": to_json(&self.value, self.labels()),
+        });
+        if let Some(decrypted) = &self.decrypted {
+            object["decrypted"] = decrypted.to_json();
+        }
+        object
+    }
+
+    fn labels(&self) -> Labels {
+        match self.structure {
+            Structure::CoseKey => Labels::CoseKey,
+            Structure::DeviceEngagement => Labels::DeviceEngagement,
+            _ => Labels::None,
+        }
+    }
+}
+
+/// The data of a SessionEstablishment or SessionData message.
+fn message_data(value: &CborValue) -> Option<Vec<u8>> {
+    match unwrap_tag24(value)? {
+        CborValue::Map(map) => match map.get(&CborValue::Text("data".to_string()))? {
+            CborValue::Bytes(data) => Some(data.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The value itself, or the decoded contents of a Tag24 value.
+fn unwrap_tag24(value: &CborValue) -> Option<CborValue> {
+    match value {
+        CborValue::Tag(TAG24, inner) => match inner.as_ref() {
+            CborValue::Bytes(bytes) => serde_cbor::from_slice(bytes).ok(),
+            _ => None,
+        },
+        value => Some(value.clone()),
+    }
+}
+
+/// The parts of a COSE_Sign1 structure.
+struct CoseSign1Parts<'a> {
+    protected: &'a [u8],
+    unprotected: &'a CborValue,
+    payload: Option<&'a [u8]>,
+    signature: &'a [u8],
+}
+
+fn cose_sign1(items: &[CborValue]) -> Option<CoseSign1Parts<'_>> {
+    match items {
+        [CborValue::Bytes(protected), unprotected @ CborValue::Map(_), payload, CborValue::Bytes(signature)] =>
+        {
+            let payload = match payload {
+                CborValue::Bytes(payload) => Some(payload.as_slice()),
+                CborValue::Null => None,
+                _ => return None,
+            };
+            Some(CoseSign1Parts {
+                protected,
+                unprotected,
+                payload,
+                signature,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Decode embedded CBOR, if the bytes are exactly one CBOR value.
+fn embedded(bytes: &[u8]) -> Option<CborValue> {
+    if bytes.is_empty() {
+        return None;
+    }
+    serde_cbor::from_slice(bytes).ok()
+}
+
+fn diagnostic(value: &CborValue, labels: Labels, indent: usize, out: &mut String) {
+    let pad = |indent: usize| "  ".repeat(indent);
+    match value {
+        CborValue::Null => out.push_str("null"),
+        CborValue::Bool(b) => out.push_str(&b.to_string()),
+        CborValue::Integer(i) => out.push_str(&i.to_string()),
+        CborValue::Float(f) => out.push_str(&format!("{f:?}")),
+        CborValue::Bytes(bytes) => out.push_str(&format!("h'{}'", hex::encode(bytes))),
+        // JSON string escaping is also valid in diagnostic notation.
+        CborValue::Text(text) => out.push_str(&Value::String(text.clone()).to_string()),
+        CborValue::Array(items) if items.is_empty() => out.push_str("[]"),
+        CborValue::Array(items) => {
+            out.push_str("[\n");
+            if let Some(parts) = cose_sign1(items) {
+                out.push_str(&pad(indent + 1));
+                embedded_diagnostic(parts.protected, Labels::CoseHeaders, indent + 1, out);
+                out.push_str(" / protected /,\n");
+                out.push_str(&pad(indent + 1));
+                diagnostic(parts.unprotected, Labels::CoseHeaders, indent + 1, out);
+                out.push_str(" / unprotected /,\n");
+                out.push_str(&pad(indent + 1));
+                match parts.payload {
+                    Some(payload) => embedded_diagnostic(payload, Labels::None, indent + 1, out),
+                    None => out.push_str("null"),
+                }
+                out.push_str(" / payload /,\n");
+                out.push_str(&pad(indent + 1));
+                out.push_str(&format!(
+                    "h'{}' / signature /\n",
+                    hex::encode(parts.signature)
+                ));
+            } else {
+                for (i, item) in items.iter().enumerate() {
+                    out.push_str(&pad(indent + 1));
+                    diagnostic(item, Labels::None, indent + 1, out);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+            }
+            out.push_str(&pad(indent));
+            out.push(']');
+        }
+        CborValue::Map(map) if map.is_empty() => out.push_str("{}"),
+        CborValue::Map(map) => {
+            let labels = match labels {
+                Labels::None => Labels::detect(map),
+                labels => labels,
+            };
+            out.push_str("{\n");
+            for (i, (key, value)) in map.iter().enumerate() {
+                out.push_str(&pad(indent + 1));
+                diagnostic(key, Labels::None, indent + 1, out);
+                if let Some(name) = labels.name(key) {
+                    out.push_str(&format!(" / {name} /"));
+                }
+                out.push_str(": ");
+                diagnostic(value, Labels::None, indent + 1, out);
+                out.push_str(if i + 1 < map.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&pad(indent));
+            out.push('}');
+        }
+        CborValue::Tag(TAG24, inner) => {
+            out.push_str("24(");
+            match inner.as_ref() {
+                CborValue::Bytes(bytes) => embedded_diagnostic(bytes, Labels::None, indent, out),
+                inner => diagnostic(inner, Labels::None, indent, out),
+            }
+            out.push(')');
+        }
+        CborValue::Tag(tag, inner) => {
+            out.push_str(&format!("{tag}("));
+            diagnostic(inner, Labels::None, indent, out);
+            out.push(')');
+        }
+        value => out.push_str(&format!("{value:?}")),
+    }
+}
+
+/// Render embedded CBOR as `<< value >>`, or as a byte string if it does not decode.
+fn embedded_diagnostic(bytes: &[u8], labels: Labels, indent: usize, out: &mut String) {
+    match embedded(bytes) {
+        Some(value) => {
+            out.push_str("<< ");
+            diagnostic(&value, labels, indent, out);
+            out.push_str(" >>");
+        }
+        None => out.push_str(&format!("h'{}'", hex::encode(bytes))),
+    }
+}
+
+fn to_json(value: &CborValue, labels: Labels) -> Value {
+    match value {
+        CborValue::Null => Value::Null,
+        CborValue::Bool(b) => json!(b),
+        CborValue::Integer(i) => i64::try_from(*i)
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(i.to_string())),
+        CborValue::Float(f) => json!(f),
+        CborValue::Bytes(bytes) => Value::String(hex::encode(bytes)),
+        CborValue::Text(text) => Value::String(text.clone()),
+        CborValue::Array(items) => match cose_sign1(items) {
+            Some(parts) => json!({
+                "protected": embedded_json(parts.protected, Labels::CoseHeaders),
+                "unprotected": to_json(parts.unprotected, Labels::CoseHeaders),
+                "payload": parts.payload.map(|payload| embedded_json(payload, Labels::None)),
+                "signature": hex::encode(parts.signature),
+            }),
+            None => items
+                .iter()
+                .map(|item| to_json(item, Labels::None))
+                .collect(),
+        },
+        CborValue::Map(map) => {
+            let labels = match labels {
+                Labels::None => Labels::detect(map),
+                labels => labels,
+            };
+            map.iter()
+                .map(|(key, value)| {
+                    let key = match (labels.name(key), key) {
+                        (Some(name), _) => name.to_string(),
+                        (None, CborValue::Text(key)) => key.clone(),
+                        (None, CborValue::Integer(key)) => key.to_string(),
+                        (None, key) => {
+                            let mut out = String::new();
+                            diagnostic(key, Labels::None, 0, &mut out);
+                            out
+                        }
+                    };
+                    (key, to_json(value, Labels::None))
+                })
+                .collect::<serde_json::Map<_, _>>()
+                .into()
+        }
+        CborValue::Tag(TAG24, inner) => match inner.as_ref() {
+            CborValue::Bytes(bytes) => embedded_json(bytes, Labels::None),
+            inner => to_json(inner, Labels::None),
+        },
+        // Dates are clear enough from their text.
+        CborValue::Tag(0 | 1004, inner) => to_json(inner, Labels::None),
+        CborValue::Tag(tag, inner) => json!({ "tag": tag, "value": to_json(inner, Labels::None) }),
+        value => Value::String(format!("{value:?}")),
+    }
+}
+
+fn embedded_json(bytes: &[u8], labels: Labels) -> Value {
+    match embedded(bytes) {
+        Some(value) => to_json(&value, labels),
+        None => Value::String(hex::encode(bytes)),
+    }
+}
+
+impl Labels {
+    /// The labels of a map found in a position with no known labels.
+    fn detect(map: &BTreeMap<CborValue, CborValue>) -> Self {
+        let has_int = |key: i128| map.contains_key(&CborValue::Integer(key));
+        if has_int(1) && (has_int(-1) || has_int(-2)) {
+            Self::CoseKey
+        } else {
+            Self::None
+        }
+    }
+
+    fn name(self, key: &CborValue) -> Option<&'static str> {
+        let key = match key {
+            CborValue::Integer(key) => *key,
+            _ => return None,
+        };
+        Some(match (self, key) {
+            (Self::CoseKey, 1) => "kty",
+            (Self::CoseKey, 2) => "kid",
+            (Self::CoseKey, 3) => "alg",
+            (Self::CoseKey, -1) => "crv",
+            (Self::CoseKey, -2) => "x",
+            (Self::CoseKey, -3) => "y",
+            (Self::CoseKey, -4) => "d",
+            (Self::CoseHeaders, 1) => "alg",
+            (Self::CoseHeaders, 2) => "crit",
+            (Self::CoseHeaders, 3) => "content type",
+            (Self::CoseHeaders, 4) => "kid",
+            (Self::CoseHeaders, 33) => "x5chain",
+            (Self::DeviceEngagement, 0) => "version",
+            (Self::DeviceEngagement, 1) => "security",
+            (Self::DeviceEngagement, 2) => "deviceRetrievalMethods",
+            (Self::DeviceEngagement, 3) => "serverRetrievalMethods",
+            (Self::DeviceEngagement, 4) => "protocolInfo",
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::{
+        device_engagement::Security,
+        session::{create_p256_ephemeral_keys, SessionTranscriptBytes, Status},
+    };
+
+    static DEVICE_RESPONSE: &str = include_str!("../test/definitions/device_response.cbor");
+    static COSE_KEY: &str = include_str!("../test/definitions/cose_key/ec_p256.cbor");
+    static SESSION_ESTABLISHMENT: &str =
+        include_str!("../test/definitions/session/session_establishment.cbor");
+    static READER_SESSION_KEY: &str =
+        include_str!("../test/definitions/session/reader_session_key.cbor");
+    static E_DEVICE_KEY: &str = include_str!("../test/definitions/session/e_device_key.cbor");
+    static SESSION_TRANSCRIPT: &str =
+        include_str!("../test/definitions/session/session_transcript.cbor");
+
+    fn inspect_hex(hex: &str, keys: &SessionKeys) -> Inspection {
+        inspect(&hex::decode(hex.trim()).unwrap(), None, keys).unwrap()
+    }
+
+    #[test]
+    fn device_response() {
+        let inspection = inspect_hex(DEVICE_RESPONSE, &SessionKeys::default());
+        assert_eq!(inspection.structure, Structure::DeviceResponse);
+
+        let diagnostic = inspection.to_diagnostic();
+        assert!(diagnostic.starts_with("/ DeviceResponse /\n{"));
+        assert!(diagnostic.contains("<< {\n"));
+        assert!(diagnostic.contains("33 / x5chain /: h'"));
+        assert!(diagnostic.contains("/ payload /"));
+        assert!(diagnostic.contains("\"digestID\": "));
+
+        let json = inspection.to_json();
+        let document = &json["value"]["documents"][0];
+        assert_eq!(document["docType"], "org.iso.18013.5.1.mDL");
+        let issuer_auth = &document["issuerSigned"]["issuerAuth"];
+        assert_eq!(issuer_auth["protected"]["alg"], -7);
+        // The MSO is unwrapped from the payload and its Tag24.
+        assert_eq!(issuer_auth["payload"]["docType"], "org.iso.18013.5.1.mDL");
+        assert_eq!(
+            issuer_auth["payload"]["deviceKeyInfo"]["deviceKey"]["kty"],
+            2
+        );
+    }
+
+    #[test]
+    fn cose_key() {
+        let inspection = inspect_hex(COSE_KEY, &SessionKeys::default());
+        assert_eq!(inspection.structure, Structure::CoseKey);
+        assert!(inspection.to_diagnostic().contains("-1 / crv /: 1"));
+        assert!(inspect(
+            &hex::decode(COSE_KEY).unwrap(),
+            Some(Structure::DeviceResponse),
+            &SessionKeys::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn session_establishment() {
+        let keys = SessionKeys {
+            sk_reader: Some(hex::decode(READER_SESSION_KEY).unwrap().try_into().unwrap()),
+            ..Default::default()
+        };
+        let inspection = inspect_hex(SESSION_ESTABLISHMENT, &keys);
+        assert_eq!(inspection.structure, Structure::SessionEstablishment);
+        assert!(inspection.to_diagnostic().contains("1 / kty /: 2"));
+        let decrypted = inspection.decrypted.unwrap();
+        assert_eq!(decrypted.structure, Structure::DeviceRequest);
+        assert_eq!(decrypted.to_json()["value"]["version"], "1.0");
+
+        let keys = SessionKeys {
+            sk_device: Some([0; 32]),
+            ..Default::default()
+        };
+        let bytes = hex::decode(SESSION_ESTABLISHMENT).unwrap();
+        assert!(inspect(&bytes, None, &keys).is_err());
+        assert!(inspect(&bytes, None, &SessionKeys::default())
+            .unwrap()
+            .decrypted
+            .is_none());
+    }
+
+    #[test]
+    fn qr_code_uri() {
+        let (_, e_device_key) = create_p256_ephemeral_keys().unwrap();
+        let device_engagement = Tag24::new(DeviceEngagement {
+            version: "1.0".into(),
+            security: Security(1, Tag24::new(e_device_key).unwrap()),
+            device_retrieval_methods: None,
+            server_retrieval_methods: None,
+            protocol_info: None,
+        })
+        .unwrap();
+        let inspection = inspect_qr_code_uri(&device_engagement.to_qr_code_uri().unwrap()).unwrap();
+        assert_eq!(inspection.structure, Structure::DeviceEngagement);
+        let diagnostic = inspection.to_diagnostic();
+        assert!(diagnostic.contains("0 / version /: \"1.0\""));
+        assert!(diagnostic.contains("24(<< {\n"));
+        assert!(diagnostic.contains("-2 / x /: h'"));
+    }
+
+    #[test]
+    fn structure_names() {
+        assert_eq!("mso".parse::<Structure>().unwrap(), Structure::Mso);
+        assert_eq!("cose-key".parse::<Structure>().unwrap(), Structure::CoseKey);
+        assert_eq!(
+            "device_response".parse::<Structure>().unwrap(),
+            Structure::DeviceResponse
+        );
+        assert!("mdoc".parse::<Structure>().is_err());
+    }
+
+    #[test]
+    fn decrypt_session_with_device_key() {
+        let session_transcript: SessionTranscriptBytes =
+            serde_cbor::from_slice(&hex::decode(SESSION_TRANSCRIPT).unwrap()).unwrap();
+        let SessionTranscript180135(device_engagement, _, handover) =
+            session_transcript.into_inner();
+        let e_device_key =
+            p256::SecretKey::from_slice(&hex::decode(E_DEVICE_KEY).unwrap()).unwrap();
+
+        let session = decrypt_session(
+            device_engagement,
+            handover,
+            &e_device_key,
+            &hex::decode(SESSION_ESTABLISHMENT).unwrap(),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            session.keys.sk_reader.map(hex::encode).as_deref(),
+            Some(READER_SESSION_KEY)
+        );
+        let message = &session.messages[0];
+        assert_eq!(message.sender, Some(Sender::Reader));
+        assert_eq!(message.counter, Some(1));
+        let decrypted = message.inspection.decrypted.as_ref().unwrap();
+        assert_eq!(decrypted.structure, Structure::DeviceRequest);
+        assert!(session
+            .to_diagnostic()
+            .starts_with("/ message 0: Reader message 1 /\n/ SessionEstablishment /"));
+    }
+
+    #[test]
+    fn decrypt_session_with_reader_key() {
+        let (e_device_key, e_device_public_key) = create_p256_ephemeral_keys().unwrap();
+        let (e_reader_key, e_reader_public_key) = create_p256_ephemeral_keys().unwrap();
+        let device_engagement = Tag24::new(DeviceEngagement {
+            version: "1.0".into(),
+            security: Security(1, Tag24::new(e_device_public_key).unwrap()),
+            device_retrieval_methods: None,
+            server_retrieval_methods: None,
+            protocol_info: None,
+        })
+        .unwrap();
+        let e_reader_public_key = Tag24::new(e_reader_public_key).unwrap();
+        let session_transcript = Tag24::new(SessionTranscript180135(
+            device_engagement.clone(),
+            e_reader_public_key.clone(),
+            Handover::QR,
+        ))
+        .unwrap();
+        let shared_secret = session::get_shared_secret(
+            e_reader_public_key.as_ref().clone(),
+            &e_device_key.to_nonzero_scalar(),
+        )
+        .unwrap();
+        let sk_reader =
+            session::derive_session_key(&shared_secret, &session_transcript, true).unwrap();
+        let sk_device =
+            session::derive_session_key(&shared_secret, &session_transcript, false).unwrap();
+
+        let request = hex::decode(COSE_KEY).unwrap();
+        let response = hex::decode(DEVICE_RESPONSE.trim()).unwrap();
+        let session_establishment = serde_cbor::to_vec(&SessionEstablishment {
+            e_reader_key: e_reader_public_key,
+            data: session::encrypt_reader_data(&sk_reader, &request, &mut 0)
+                .unwrap()
+                .into(),
+        })
+        .unwrap();
+        let session_data = |data: Option<Vec<u8>>, status| {
+            serde_cbor::to_vec(&SessionData {
+                data: data.map(Into::into),
+                status,
+            })
+            .unwrap()
+        };
+        let messages = [
+            session_data(
+                Some(session::encrypt_device_data(&sk_device, &response, &mut 0).unwrap()),
+                None,
+            ),
+            session_data(Some(vec![0; 32]), None),
+            session_data(None, Some(Status::SessionTermination)),
+        ];
+
+        let session = decrypt_session(
+            device_engagement,
+            Handover::QR,
+            &e_reader_key,
+            &session_establishment,
+            &messages,
+        )
+        .unwrap();
+        let senders: Vec<_> = session
+            .messages
+            .iter()
+            .map(|message| (message.sender, message.counter))
+            .collect();
+        assert_eq!(
+            senders,
+            [
+                (Some(Sender::Reader), Some(1)),
+                (Some(Sender::Device), Some(1)),
+                (None, None),
+                (None, None),
+            ]
+        );
+        let decrypted = session.messages[1].inspection.decrypted.as_ref().unwrap();
+        assert_eq!(decrypted.structure, Structure::DeviceResponse);
+        assert!(session
+            .to_diagnostic()
+            .contains("/ message 2: data could not be decrypted /"));
+        assert_eq!(session.to_json()["messages"][1]["sender"], "Device");
+
+        let (other_key, _) = create_p256_ephemeral_keys().unwrap();
+        let device_engagement = session_transcript.into_inner().0;
+        assert!(decrypt_session(
+            device_engagement,
+            Handover::QR,
+            &other_key,
+            &session_establishment,
+            &messages,
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,344 @@
+//! Strategies for adding decoy digests to the MSO.
+//!
+//! The MSO contains a digest for every data element of the mdoc, and is disclosed in full with
+//! every presentation, however few data elements are released. Without decoys, a relying party
+//! learns how many data elements the mdoc holds in each namespace, which can distinguish holders
+//! with optional data elements from those without. Decoy digests are digests of random bytes that
+//! do not correspond to any data element, and are indistinguishable from the real digests.
+//!
+//! A [DecoyStrategy] controls how many decoys are added to each namespace ([DecoyCount]), and how
+//! digest IDs are allocated to the real and decoy digests ([DigestIdAllocation]).
+use crate::definitions::DigestId;
+use anyhow::{anyhow, Result};
+use rand::{seq::SliceRandom, CryptoRng, Rng, RngCore};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// How decoy digests are added to the MSO of an mdoc.
+///
+/// The default adds between 5 and 9 decoys to each namespace, and allocates random digest IDs.
+pub struct DecoyStrategy {
+    /// The number of decoys to add to each namespace without an entry in `namespaces`.
+    pub count: DecoyCount,
+    /// The number of decoys to add to specific namespaces, overriding `count`.
+    pub namespaces: BTreeMap<String, DecoyCount>,
+    /// How digest IDs are allocated in each namespace.
+    pub digest_ids: DigestIdAllocation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The number of decoy digests to add to a namespace containing `n` data elements.
+pub enum DecoyCount {
+    /// Add no decoys, so the number of digests is exactly `n`.
+    None,
+    /// Add a number of decoys drawn uniformly from `min..=max`, independently for each namespace
+    /// and each issuance.
+    ///
+    /// The number of digests is `n + d`, which hides `n` to within a range of `max - min + 1`
+    /// values. An observer of several mdocs issued with the same data elements can narrow this
+    /// down, as `n` is the smallest number of digests seen less `min`.
+    Random { min: usize, max: usize },
+    /// Add decoys until the namespace contains exactly this many digests.
+    ///
+    /// Every namespace issued with the same target has the same number of digests, so `n` is not
+    /// revealed at all. Preparing an mdoc with more than this many data elements in a namespace
+    /// fails rather than revealing `n`.
+    Target(usize),
+    /// Add decoys until the number of digests is the smallest multiple of this bucket size not
+    /// less than `n`.
+    ///
+    /// This only reveals which bucket `n` falls in, i.e. `ceil(n / size)`, at the cost of at most
+    /// `size - 1` decoys per namespace.
+    Bucket(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How digest IDs are allocated to the real and decoy digests of a namespace.
+///
+/// In both cases, real and decoy digests draw their IDs from the same distribution, so the ID of a
+/// digest does not reveal whether it is a decoy, nor which data element it belongs to.
+pub enum DigestIdAllocation {
+    /// Draw each digest ID uniformly from `0..2^31`, without repetition.
+    ///
+    /// The IDs reveal nothing beyond the number of digests, but each takes up to five bytes in
+    /// the CBOR encoding of the MSO and of every released data element.
+    #[default]
+    Random,
+    /// Allocate the digest IDs `0..d`, where `d` is the number of digests in the namespace, in a
+    /// random order.
+    ///
+    /// The set of IDs in a namespace is fixed by the number of digests, so it reveals nothing
+    /// further, and the IDs are smaller in the CBOR encoding. This works best alongside a
+    /// [DecoyCount] which hides the number of digests.
+    Shuffled,
+}
+
+impl Default for DecoyStrategy {
+    fn default() -> Self {
+        Self {
+            count: DecoyCount::Random { min: 5, max: 9 },
+            namespaces: BTreeMap::new(),
+            digest_ids: DigestIdAllocation::Random,
+        }
+    }
+}
+
+impl DecoyStrategy {
+    /// Add no decoy digests, and allocate random digest IDs.
+    pub fn none() -> Self {
+        Self {
+            count: DecoyCount::None,
+            ..Default::default()
+        }
+    }
+
+    /// Set the number of decoys to add to each namespace.
+    pub fn count(mut self, count: DecoyCount) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Set the number of decoys to add to a specific namespace.
+    pub fn namespace(mut self, namespace: String, count: DecoyCount) -> Self {
+        self.namespaces.insert(namespace, count);
+        self
+    }
+
+    /// Set how digest IDs are allocated.
+    pub fn digest_ids(mut self, digest_ids: DigestIdAllocation) -> Self {
+        self.digest_ids = digest_ids;
+        self
+    }
+
+    /// The number of decoys to add to a namespace containing `elements` data elements.
+    pub fn decoy_count<R: RngCore + CryptoRng>(
+        &self,
+        namespace: &str,
+        elements: usize,
+        rng: &mut R,
+    ) -> Result<usize> {
+        let count = self.namespaces.get(namespace).unwrap_or(&self.count);
+        match *count {
+            DecoyCount::None => Ok(0),
+            DecoyCount::Random { min, max } => {
+                if min > max {
+                    return Err(anyhow!(
+                        "invalid decoy digest range for namespace '{namespace}': {min} > {max}"
+                    ));
+                }
+                // Drawn as a u32, which keeps the draws of the default range unchanged from
+                // before the strategy was configurable.
+                let (Ok(min), Ok(max)) = (u32::try_from(min), u32::try_from(max)) else {
+                    return Err(anyhow!(
+                        "invalid decoy digest range for namespace '{namespace}': {min}..={max}"
+                    ));
+                };
+                Ok(rng.gen_range(min..=max) as usize)
+            }
+            DecoyCount::Target(target) => target.checked_sub(elements).ok_or_else(|| {
+                anyhow!(
+                    "namespace '{namespace}' has {elements} data elements, exceeding the target of {target} digests"
+                )
+            }),
+            DecoyCount::Bucket(0) => Err(anyhow!(
+                "invalid decoy digest bucket size for namespace '{namespace}': 0"
+            )),
+            DecoyCount::Bucket(size) => Ok((size - elements % size) % size),
+        }
+    }
+
+    /// Allocate `count` distinct digest IDs.
+    pub fn allocate_digest_ids<R: RngCore + CryptoRng>(
+        &self,
+        count: usize,
+        rng: &mut R,
+    ) -> Result<Vec<DigestId>> {
+        let max = i32::try_from(count)
+            .map_err(|_| anyhow!("too many digests in a namespace: {count}"))?;
+        match self.digest_ids {
+            DigestIdAllocation::Random => {
+                let mut used_ids = HashSet::new();
+                Ok((0..count)
+                    .map(|_| random_digest_id(&mut used_ids, rng))
+                    .collect())
+            }
+            DigestIdAllocation::Shuffled => {
+                let mut digest_ids: Vec<DigestId> = (0..max).map(DigestId::new).collect();
+                digest_ids.shuffle(rng);
+                Ok(digest_ids)
+            }
+        }
+    }
+
+    /// Start allocating the digest IDs of a namespace containing `elements` data elements.
+    pub(crate) fn allocator<R: RngCore + CryptoRng>(
+        &self,
+        namespace: &str,
+        elements: usize,
+        rng: &mut R,
+    ) -> Result<DigestIdAllocator> {
+        let shuffled = match self.digest_ids {
+            DigestIdAllocation::Random => None,
+            // The IDs depend on the number of decoys, so it is drawn up front.
+            DigestIdAllocation::Shuffled => {
+                let decoys = self.decoy_count(namespace, elements, rng)?;
+                let digest_ids = self.allocate_digest_ids(elements + decoys, rng)?;
+                Some((digest_ids.into_iter(), decoys))
+            }
+        };
+        Ok(DigestIdAllocator {
+            namespace: namespace.to_string(),
+            elements,
+            used_ids: HashSet::new(),
+            shuffled,
+        })
+    }
+}
+
+/// Allocates the digest IDs of one namespace, first for its data elements and then for its
+/// decoys.
+///
+/// With [DigestIdAllocation::Random], each ID and the number of decoys are only drawn when needed,
+/// so that issuance draws from the RNG in the same order as before the strategy was
+/// configurable.
+pub(crate) struct DigestIdAllocator {
+    namespace: String,
+    elements: usize,
+    used_ids: HashSet<DigestId>,
+    shuffled: Option<(std::vec::IntoIter<DigestId>, usize)>,
+}
+
+impl DigestIdAllocator {
+    /// The next digest ID.
+    pub(crate) fn next<R: RngCore + CryptoRng>(&mut self, rng: &mut R) -> Result<DigestId> {
+        match &mut self.shuffled {
+            Some((digest_ids, _)) => digest_ids
+                .next()
+                .ok_or_else(|| anyhow!("digest IDs exhausted in namespace '{}'", self.namespace)),
+            None => Ok(random_digest_id(&mut self.used_ids, rng)),
+        }
+    }
+
+    /// The number of decoys to add after the data elements.
+    pub(crate) fn decoys<R: RngCore + CryptoRng>(
+        &self,
+        strategy: &DecoyStrategy,
+        rng: &mut R,
+    ) -> Result<usize> {
+        match &self.shuffled {
+            Some((_, decoys)) => Ok(*decoys),
+            None => strategy.decoy_count(&self.namespace, self.elements, rng),
+        }
+    }
+}
+
+/// Draw a digest ID uniformly from `0..2^31` which is not yet in `used_ids`.
+fn random_digest_id<R: RngCore + CryptoRng>(
+    used_ids: &mut HashSet<DigestId>,
+    rng: &mut R,
+) -> DigestId {
+    loop {
+        // The absolute value of a random i32, as drawn since the first release. i32::MIN has no
+        // absolute value, and is redrawn.
+        let Some(i) = rng.gen::<i32>().checked_abs() else {
+            continue;
+        };
+        let digest_id = DigestId::new(i);
+        if used_ids.insert(digest_id) {
+            return digest_id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+    fn rng() -> ChaCha20Rng {
+        ChaCha20Rng::seed_from_u64(0)
+    }
+
+    fn decoy_counts(strategy: &DecoyStrategy, elements: usize) -> Vec<usize> {
+        let mut rng = rng();
+        (0..1000)
+            .map(|_| strategy.decoy_count("ns", elements, &mut rng).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn none() {
+        assert!(decoy_counts(&DecoyStrategy::none(), 7)
+            .iter()
+            .all(|d| *d == 0));
+    }
+
+    #[test]
+    fn random() {
+        let counts = decoy_counts(&DecoyStrategy::default(), 7);
+        let distinct: HashSet<usize> = counts.into_iter().collect();
+        assert_eq!(distinct, (5..=9).collect());
+
+        let strategy = DecoyStrategy::default().count(DecoyCount::Random { min: 3, max: 2 });
+        assert!(strategy.decoy_count("ns", 7, &mut rng()).is_err());
+    }
+
+    #[test]
+    fn target() {
+        let strategy = DecoyStrategy::default().count(DecoyCount::Target(32));
+        for elements in 1..=32 {
+            let decoys = strategy.decoy_count("ns", elements, &mut rng()).unwrap();
+            assert_eq!(elements + decoys, 32);
+        }
+        assert!(strategy.decoy_count("ns", 33, &mut rng()).is_err());
+    }
+
+    #[test]
+    fn bucket() {
+        let strategy = DecoyStrategy::default().count(DecoyCount::Bucket(8));
+        for elements in 1..=40 {
+            let decoys = strategy.decoy_count("ns", elements, &mut rng()).unwrap();
+            let total = elements + decoys;
+            assert_eq!(total % 8, 0);
+            assert!(decoys < 8);
+            assert_eq!(total / 8, elements.div_ceil(8));
+        }
+        let strategy = DecoyStrategy::default().count(DecoyCount::Bucket(0));
+        assert!(strategy.decoy_count("ns", 1, &mut rng()).is_err());
+    }
+
+    #[test]
+    fn namespace_override() {
+        let strategy =
+            DecoyStrategy::none().namespace("padded".to_string(), DecoyCount::Target(10));
+        assert_eq!(strategy.decoy_count("other", 4, &mut rng()).unwrap(), 0);
+        assert_eq!(strategy.decoy_count("padded", 4, &mut rng()).unwrap(), 6);
+    }
+
+    #[test]
+    fn random_digest_ids() {
+        let digest_ids = DecoyStrategy::default()
+            .allocate_digest_ids(100, &mut rng())
+            .unwrap();
+        let distinct: HashSet<DigestId> = digest_ids.iter().copied().collect();
+        assert_eq!(distinct.len(), 100);
+    }
+
+    #[test]
+    fn shuffled_digest_ids() {
+        let strategy = DecoyStrategy::default().digest_ids(DigestIdAllocation::Shuffled);
+        let mut rng = rng();
+        let digest_ids = strategy.allocate_digest_ids(16, &mut rng).unwrap();
+        let mut sorted = digest_ids.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..16).map(DigestId::new).collect::<Vec<_>>());
+
+        // Each ID should land in each position with roughly equal probability.
+        let mut first = [0; 16];
+        for _ in 0..16000 {
+            let digest_ids = strategy.allocate_digest_ids(16, &mut rng).unwrap();
+            first[sorted.iter().position(|id| *id == digest_ids[0]).unwrap()] += 1;
+        }
+        assert!(first.iter().all(|count| (800..1200).contains(count)));
+    }
+}
@@ -0,0 +1,71 @@
+//! Validation of the data elements of an `org.iso.18013.5.1.mDL` prior to issuance.
+use crate::{definitions::namespaces::registry::Registry, issuance::Namespaces};
+use anyhow::Result;
+use time::Date;
+
+pub const DOC_TYPE: &str = "org.iso.18013.5.1.mDL";
+
+/// Check the data elements of an mDL against [Registry::default]: the mandatory data elements
+/// must be present, and each `age_over_NN` element must agree with `birth_date` on the given date.
+pub fn validate(namespaces: &Namespaces, on: Date) -> Result<()> {
+    Registry::default().validate_namespaces(DOC_TYPE, namespaces, on)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::{
+        namespaces::org_iso_18013_5_1::OrgIso1801351,
+        traits::{FromJson, Namespace, ToNamespaceMap},
+    };
+    use crate::issuance::mdoc::test::isomdl_data;
+    use time::macros::date;
+
+    fn namespaces(json: serde_json::Value) -> Namespaces {
+        let elements = OrgIso1801351::from_json(&json).unwrap().to_ns_map();
+        [(OrgIso1801351::NAMESPACE.to_string(), elements)]
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn valid() {
+        validate(&namespaces(isomdl_data()), date!(2024 - 01 - 01)).unwrap();
+    }
+
+    #[test]
+    fn missing_mandatory_elements() {
+        let mut namespaces = namespaces(isomdl_data());
+        let elements = namespaces.get_mut(OrgIso1801351::NAMESPACE).unwrap();
+        elements.remove("portrait");
+        elements.remove("document_number");
+        let error = validate(&namespaces, date!(2024 - 01 - 01)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "missing mandatory data element 'document_number' in the org.iso.18013.5.1 namespace"
+        );
+    }
+
+    #[test]
+    fn mismatched_age_over() {
+        let mut json = isomdl_data();
+        json["age_over_65"] = true.into();
+        assert!(validate(&namespaces(json), date!(2024 - 01 - 01)).is_err());
+
+        let mut json = isomdl_data();
+        json["age_over_21"] = false.into();
+        assert!(validate(&namespaces(json), date!(2024 - 01 - 01)).is_err());
+    }
+
+    #[test]
+    fn age_over_on_birthday() {
+        let mut json = isomdl_data();
+        json["birth_date"] = "2006-06-15".into();
+        json["age_over_18"] = true.into();
+        json.as_object_mut().unwrap().remove("age_over_21");
+        let namespaces = namespaces(json);
+        assert!(validate(&namespaces, date!(2024 - 06 - 14)).is_err());
+        validate(&namespaces, date!(2024 - 06 - 15)).unwrap();
+    }
+}
@@ -1,22 +1,29 @@
 use crate::{
     definitions::{
-        helpers::{NonEmptyMap, NonEmptyVec, Tag24},
-        issuer_signed::{IssuerNamespaces, IssuerSignedItemBytes},
-        DeviceKeyInfo, DigestAlgorithm, DigestId, DigestIds, IssuerSignedItem, Mso, ValidityInfo,
+        helpers::{NonEmptyMap, NonEmptyVec, SignatureFormat, Tag24},
+        issuer_signed::IssuerNamespaces,
+        namespaces::registry::Registry,
+        status_list::Status,
+        traits::Namespace,
+        DeviceKeyInfo, DigestAlgorithm, DigestIds, IssuerSignedItem, Mso, ValidityInfo,
+    },
+    issuance::{
+        decoys::DecoyStrategy,
+        refresh::Refresh,
+        x5chain::{X5Chain, X5CHAIN_HEADER_LABEL},
     },
-    issuance::x5chain::{X5Chain, X5CHAIN_HEADER_LABEL},
 };
 use anyhow::{anyhow, Result};
 use async_signature::AsyncSigner;
 use cose_rs::{
     algorithm::{Algorithm, SignatureAlgorithm},
-    sign1::{CoseSign1, PreparedCoseSign1},
+    sign1::{CoseSign1, PreparedCoseSign1, VerificationResult},
 };
-use rand::Rng;
+use rand::{CryptoRng, Rng, RngCore};
 use serde::{Deserialize, Serialize};
 use serde_cbor::Value as CborValue;
 use sha2::{Digest, Sha256, Sha384, Sha512};
-use signature::{SignatureEncoding, Signer};
+use signature::{SignatureEncoding, Signer, Verifier};
 use std::collections::{BTreeMap, HashSet};
 
 pub type Namespaces = BTreeMap<String, BTreeMap<String, CborValue>>;
@@ -38,8 +45,15 @@ pub struct PreparedMdoc {
     mso: Mso,
     namespaces: IssuerNamespaces,
     prepared_sig: PreparedCoseSign1,
+    #[serde(default)]
+    signature_format: SignatureFormat,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A batch of incomplete mdocs sharing the same data elements, each issued to a different device
+/// key and requiring its own remotely signed signature to be completed.
+pub struct PreparedMdocBatch(NonEmptyVec<PreparedMdoc>);
+
 #[derive(Debug, Clone, Default)]
 pub struct Builder {
     doc_type: Option<String>,
@@ -47,7 +61,11 @@ pub struct Builder {
     validity_info: Option<ValidityInfo>,
     digest_algorithm: Option<DigestAlgorithm>,
     device_key_info: Option<DeviceKeyInfo>,
-    enable_decoy_digests: Option<bool>,
+    status: Option<Status>,
+    statuses: Option<Vec<Status>>,
+    decoy_strategy: Option<DecoyStrategy>,
+    registry: Option<Registry>,
+    skip_validation: bool,
 }
 
 impl Mdoc {
@@ -55,7 +73,103 @@ impl Mdoc {
         Builder::default()
     }
 
+    /// Begin re-issuing this mdoc to the same device key, with a new MSO and fresh salts.
+    pub fn refresh(&self) -> Refresh {
+        Refresh::new(self)
+    }
+
+    /// The data elements of this mdoc.
+    pub fn elements(&self) -> Namespaces {
+        self.namespaces
+            .iter()
+            .map(|(namespace, items)| {
+                let elements = items
+                    .iter()
+                    .map(|item| {
+                        let item = item.as_ref();
+                        (item.element_identifier.clone(), item.element_value.clone())
+                    })
+                    .collect();
+                (namespace.clone(), elements)
+            })
+            .collect()
+    }
+
+    /// Check that the mdoc is internally consistent, which is worthwhile after completing a
+    /// remotely signed mdoc and before it is delivered to the holder.
+    ///
+    /// This checks that:
+    /// * the issuer signature verifies with the public key of the end-entity certificate in the
+    ///   x5chain, which catches signatures supplied in the wrong encoding;
+    /// * the signed MSO matches the MSO of the mdoc;
+    /// * the digest of every data element matches the corresponding digest in the MSO;
+    /// * the docType and validity information of the MSO are consistent.
+    pub fn verify(&self) -> Result<()> {
+        verify_issuer_auth(&self.issuer_auth)?;
+
+        let signed_mso: Tag24<Mso> = serde_cbor::from_slice(
+            self.issuer_auth
+                .payload()
+                .ok_or_else(|| anyhow!("issuer signature has no payload"))?,
+        )
+        .map_err(|e| anyhow!("unable to decode the signed MSO: {}", e))?;
+        if serde_cbor::to_vec(signed_mso.as_ref())? != serde_cbor::to_vec(&self.mso)? {
+            return Err(anyhow!("the signed MSO does not match the MSO of the mdoc"));
+        }
+
+        if self.mso.doc_type != self.doc_type {
+            return Err(anyhow!(
+                "docType of the MSO '{}' does not match the mdoc '{}'",
+                self.mso.doc_type,
+                self.doc_type
+            ));
+        }
+
+        let validity_info = &self.mso.validity_info;
+        if validity_info.valid_from < validity_info.signed {
+            return Err(anyhow!("validFrom must not be earlier than signed"));
+        }
+        if validity_info.valid_until <= validity_info.valid_from {
+            return Err(anyhow!("validUntil must be later than validFrom"));
+        }
+
+        for (namespace, items) in self.namespaces.iter() {
+            let digests = self
+                .mso
+                .value_digests
+                .get(namespace)
+                .ok_or_else(|| anyhow!("the MSO has no digests for namespace '{namespace}'"))?;
+            let mut digest_ids = HashSet::new();
+            for item in items.iter() {
+                let IssuerSignedItem {
+                    digest_id,
+                    element_identifier,
+                    ..
+                } = item.as_ref();
+                if !digest_ids.insert(digest_id) {
+                    return Err(anyhow!(
+                        "digest ID of '{namespace}/{element_identifier}' is used more than once"
+                    ));
+                }
+                let expected = digests.get(digest_id).ok_or_else(|| {
+                    anyhow!("the MSO has no digest for '{namespace}/{element_identifier}'")
+                })?;
+                let bytes = serde_cbor::to_vec(item)?;
+                if digest(self.mso.digest_algorithm, &bytes) != expected.as_ref() {
+                    return Err(anyhow!(
+                        "digest of '{namespace}/{element_identifier}' does not match the MSO"
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Prepare mdoc for remote signing.
+    ///
+    /// Status information can only be set with [Builder::status], and a [DecoyStrategy] other
+    /// than the default with [Builder::decoy_strategy].
     pub fn prepare(
         doc_type: String,
         namespaces: Namespaces,
@@ -65,39 +179,43 @@ impl Mdoc {
         signature_algorithm: Algorithm,
         enable_decoy_digests: bool,
     ) -> Result<PreparedMdoc> {
-        if let Some(authorizations) = &device_key_info.key_authorizations {
-            authorizations.validate()?;
-        }
-
-        let issuer_namespaces = to_issuer_namespaces(namespaces)?;
-        let value_digests =
-            digest_namespaces(&issuer_namespaces, digest_algorithm, enable_decoy_digests)?;
-
-        let mso = Mso {
-            version: "1.0".to_string(),
+        Self::prepare_with_rng(
+            doc_type,
+            namespaces,
+            validity_info,
             digest_algorithm,
-            value_digests,
             device_key_info,
-            doc_type: doc_type.clone(),
-            validity_info,
-        };
-
-        let mso_bytes = serde_cbor::to_vec(&Tag24::new(&mso)?)?;
-
-        let prepared_sig = CoseSign1::builder()
-            .payload(mso_bytes)
-            .signature_algorithm(signature_algorithm)
-            .prepare()
-            .map_err(|e| anyhow!("error preparing cosesign1: {}", e))?;
+            signature_algorithm,
+            enable_decoy_digests,
+            &mut rand::thread_rng(),
+        )
+    }
 
-        let preparation_mdoc = PreparedMdoc {
+    /// Prepare mdoc for remote signing, drawing the salts, digest IDs and decoy digests from the
+    /// given random number generator.
+    ///
+    /// A seeded generator makes the output reproducible, which is useful for test vectors, but
+    /// production issuance must use a securely seeded generator so that the salts are unguessable.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare_with_rng<R: RngCore + CryptoRng>(
+        doc_type: String,
+        namespaces: Namespaces,
+        validity_info: ValidityInfo,
+        digest_algorithm: DigestAlgorithm,
+        device_key_info: DeviceKeyInfo,
+        signature_algorithm: Algorithm,
+        enable_decoy_digests: bool,
+        rng: &mut R,
+    ) -> Result<PreparedMdoc> {
+        builder_from(
             doc_type,
-            namespaces: issuer_namespaces,
-            mso,
-            prepared_sig,
-        };
-
-        Ok(preparation_mdoc)
+            namespaces,
+            validity_info,
+            digest_algorithm,
+            enable_decoy_digests,
+        )
+        .device_key_info(device_key_info)
+        .prepare_with_rng(signature_algorithm, rng)
     }
 
     /// Directly sign and issue an mdoc.
@@ -116,23 +234,15 @@ impl Mdoc {
         S: Signer<Sig> + SignatureAlgorithm,
         Sig: SignatureEncoding,
     {
-        let prepared_mdoc = Self::prepare(
+        builder_from(
             doc_type,
             namespaces,
             validity_info,
             digest_algorithm,
-            device_key_info,
-            signer.algorithm(),
             enable_decoy_digests,
-        )?;
-
-        let signature_payload = prepared_mdoc.signature_payload();
-        let signature = signer
-            .try_sign(signature_payload)
-            .map_err(|e| anyhow!("error signing cosesign1: {}", e))?
-            .to_vec();
-
-        Ok(prepared_mdoc.complete(x5chain, signature))
+        )
+        .device_key_info(device_key_info)
+        .issue(x5chain, signer)
     }
 
     /// Directly sign and issue an mdoc.
@@ -151,24 +261,141 @@ impl Mdoc {
         S: AsyncSigner<Sig> + SignatureAlgorithm,
         Sig: SignatureEncoding + Send + 'static,
     {
-        let prepared_mdoc = Self::prepare(
+        builder_from(
             doc_type,
             namespaces,
             validity_info,
             digest_algorithm,
-            device_key_info,
-            signer.algorithm(),
             enable_decoy_digests,
-        )?;
+        )
+        .device_key_info(device_key_info)
+        .issue_async(x5chain, signer)
+        .await
+    }
 
-        let signature_payload = prepared_mdoc.signature_payload();
-        let signature = signer
-            .sign_async(signature_payload)
-            .await
-            .map_err(|e| anyhow!("error signing cosesign1: {}", e))?
-            .to_vec();
+    /// Prepare a batch of mdocs for remote signing, one for each of the given device keys.
+    ///
+    /// Every mdoc in the batch carries the same data elements, but each is prepared independently
+    /// with freshly generated salts, digest IDs and decoy digests. The copies do share the
+    /// validity information, which is disclosed in every presentation, so a relying party can
+    /// still correlate presentations of copies from the same batch by its timestamps.
+    pub fn prepare_batch(
+        doc_type: String,
+        namespaces: Namespaces,
+        validity_info: ValidityInfo,
+        digest_algorithm: DigestAlgorithm,
+        device_key_infos: NonEmptyVec<DeviceKeyInfo>,
+        signature_algorithm: Algorithm,
+        enable_decoy_digests: bool,
+    ) -> Result<PreparedMdocBatch> {
+        builder_from(
+            doc_type,
+            namespaces,
+            validity_info,
+            digest_algorithm,
+            enable_decoy_digests,
+        )
+        .prepare_batch(device_key_infos, signature_algorithm)
+    }
+
+    /// Directly sign and issue a batch of mdocs, one for each of the given device keys.
+    #[allow(clippy::too_many_arguments)]
+    pub fn issue_batch<S, Sig>(
+        doc_type: String,
+        namespaces: Namespaces,
+        validity_info: ValidityInfo,
+        digest_algorithm: DigestAlgorithm,
+        device_key_infos: NonEmptyVec<DeviceKeyInfo>,
+        x5chain: X5Chain,
+        enable_decoy_digests: bool,
+        signer: S,
+    ) -> Result<Vec<Mdoc>>
+    where
+        S: Signer<Sig> + SignatureAlgorithm,
+        Sig: SignatureEncoding,
+    {
+        builder_from(
+            doc_type,
+            namespaces,
+            validity_info,
+            digest_algorithm,
+            enable_decoy_digests,
+        )
+        .issue_batch(device_key_infos, x5chain, signer)
+    }
+
+    /// Directly sign and issue a batch of mdocs, one for each of the given device keys.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn issue_batch_async<S, Sig>(
+        doc_type: String,
+        namespaces: Namespaces,
+        validity_info: ValidityInfo,
+        digest_algorithm: DigestAlgorithm,
+        device_key_infos: NonEmptyVec<DeviceKeyInfo>,
+        x5chain: X5Chain,
+        enable_decoy_digests: bool,
+        signer: S,
+    ) -> Result<Vec<Mdoc>>
+    where
+        S: AsyncSigner<Sig> + SignatureAlgorithm,
+        Sig: SignatureEncoding + Send + 'static,
+    {
+        builder_from(
+            doc_type,
+            namespaces,
+            validity_info,
+            digest_algorithm,
+            enable_decoy_digests,
+        )
+        .issue_batch_async(device_key_infos, x5chain, signer)
+        .await
+    }
+}
 
-        Ok(prepared_mdoc.complete(x5chain, signature))
+/// The builder behind the positional issuance functions of [Mdoc].
+fn builder_from(
+    doc_type: String,
+    namespaces: Namespaces,
+    validity_info: ValidityInfo,
+    digest_algorithm: DigestAlgorithm,
+    enable_decoy_digests: bool,
+) -> Builder {
+    Mdoc::builder()
+        .doc_type(doc_type)
+        .namespaces(namespaces)
+        .validity_info(validity_info)
+        .digest_algorithm(digest_algorithm)
+        .enable_decoy_digests(enable_decoy_digests)
+}
+
+impl PreparedMdocBatch {
+    /// Retrieve the payloads for the remote signatures, in the order of the device keys that the
+    /// batch was prepared with.
+    pub fn signature_payloads(&self) -> impl Iterator<Item = &[u8]> {
+        self.0.iter().map(PreparedMdoc::signature_payload)
+    }
+
+    /// Supply the remotely signed signatures, in the same order as the signature payloads, and the
+    /// x5chain containing the issuing certificate to complete and issue the batch.
+    pub fn complete(self, x5chain: X5Chain, signatures: Vec<Vec<u8>>) -> Result<Vec<Mdoc>> {
+        if signatures.len() != self.0.len() {
+            return Err(anyhow!(
+                "expected {} signatures, received {}",
+                self.0.len(),
+                signatures.len()
+            ));
+        }
+        self.0
+            .into_inner()
+            .into_iter()
+            .zip(signatures)
+            .map(|(prepared_mdoc, signature)| prepared_mdoc.complete(x5chain.clone(), signature))
+            .collect()
+    }
+
+    /// Retrieve the individual prepared mdocs.
+    pub fn into_inner(self) -> Vec<PreparedMdoc> {
+        self.0.into_inner()
     }
 }
 
@@ -180,25 +407,32 @@ impl PreparedMdoc {
 
     /// Supply the remotely signed signature and x5chain containing the issuing certificate
     /// to complete and issue the prepared mdoc.
-    pub fn complete(self, x5chain: X5Chain, signature: Vec<u8>) -> Mdoc {
+    ///
+    /// ECDSA signatures may be either `r || s` or DER encoded, as returned by most KMS and HSM
+    /// APIs, and are converted to the `r || s` encoding required by COSE.
+    pub fn complete(self, x5chain: X5Chain, signature: Vec<u8>) -> Result<Mdoc> {
         let PreparedMdoc {
             doc_type,
             namespaces,
             mso,
             prepared_sig,
+            signature_format,
         } = self;
 
+        let signature = signature_format
+            .normalize(signature)
+            .map_err(|e| anyhow!("invalid signature: {}", e))?;
         let mut issuer_auth = prepared_sig.finalize(signature);
         issuer_auth
             .unprotected_mut()
             .insert_i(X5CHAIN_HEADER_LABEL, x5chain.into_cbor());
 
-        Mdoc {
+        Ok(Mdoc {
             doc_type,
             mso,
             namespaces,
             issuer_auth,
-        }
+        })
     }
 }
 
@@ -215,6 +449,15 @@ impl Builder {
         self
     }
 
+    /// Set the data elements of a single namespace from its typed representation, replacing any
+    /// data elements previously set for that namespace.
+    pub fn namespace<N: Namespace>(mut self, elements: N) -> Self {
+        self.namespaces
+            .get_or_insert_with(Default::default)
+            .insert(N::NAMESPACE.to_string(), elements.to_ns_map());
+        self
+    }
+
     /// Set the validity information
     pub fn validity_info(mut self, validity_info: ValidityInfo) -> Self {
         self.validity_info = Some(validity_info);
@@ -233,9 +476,51 @@ impl Builder {
         self
     }
 
-    /// Enable the use of decoy digests.
+    /// Set the status information, such as a reference to a status list, to be carried in the
+    /// MSO so that the mdoc can later be revoked or suspended.
+    pub fn status(mut self, status: Status) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Set the status information of each mdoc of a batch, in the order of the device keys.
+    ///
+    /// Each mdoc needs its own index in the status list, for example allocated with
+    /// [StatusListPublisher::allocate](crate::issuance::StatusListPublisher::allocate), or
+    /// presentations of the copies could be correlated by it.
+    pub fn statuses(mut self, statuses: Vec<Status>) -> Self {
+        self.statuses = Some(statuses);
+        self
+    }
+
+    /// Enable the use of decoy digests, with the default [DecoyStrategy].
     pub fn enable_decoy_digests(mut self, enable_decoy_digests: bool) -> Self {
-        self.enable_decoy_digests = Some(enable_decoy_digests);
+        self.decoy_strategy = Some(if enable_decoy_digests {
+            DecoyStrategy::default()
+        } else {
+            DecoyStrategy::none()
+        });
+        self
+    }
+
+    /// Set the strategy for adding decoy digests to the MSO, which hide the number of data
+    /// elements in each namespace.
+    pub fn decoy_strategy(mut self, decoy_strategy: DecoyStrategy) -> Self {
+        self.decoy_strategy = Some(decoy_strategy);
+        self
+    }
+
+    /// Validate the data elements against the schemas in the given registry before the mdoc is
+    /// signed, instead of [Registry::default].
+    pub fn registry(mut self, registry: Registry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Sign the mdoc without validating the data elements against a registry, for document
+    /// types which are not registered.
+    pub fn skip_validation(mut self) -> Self {
+        self.skip_validation = true;
         self
     }
 
@@ -244,32 +529,28 @@ impl Builder {
     /// The signature algorithm which the mdoc will be signed with must be known ahead of time as
     /// it is a required field in the signature headers.
     pub fn prepare(self, signature_algorithm: Algorithm) -> Result<PreparedMdoc> {
-        let doc_type = self
-            .doc_type
-            .ok_or_else(|| anyhow!("missing parameter: 'doc_type'"))?;
-        let namespaces = self
-            .namespaces
-            .ok_or_else(|| anyhow!("missing parameter: 'namespaces'"))?;
-        let validity_info = self
-            .validity_info
-            .ok_or_else(|| anyhow!("missing parameter: 'validity_info'"))?;
-        let digest_algorithm = self
-            .digest_algorithm
-            .ok_or_else(|| anyhow!("missing parameter: 'digest_algorithm'"))?;
+        self.prepare_with_rng(signature_algorithm, &mut rand::thread_rng())
+    }
+
+    /// Prepare the mdoc for remote signing, drawing the salts, digest IDs and decoy digests from
+    /// the given random number generator.
+    pub fn prepare_with_rng<R: RngCore + CryptoRng>(
+        mut self,
+        signature_algorithm: Algorithm,
+        rng: &mut R,
+    ) -> Result<PreparedMdoc> {
         let device_key_info = self
             .device_key_info
+            .take()
             .ok_or_else(|| anyhow!("missing parameter: 'device_key_info'"))?;
-        let enable_decoy_digests = self.enable_decoy_digests.unwrap_or(true);
+        if self.statuses.is_some() {
+            return Err(anyhow!(
+                "unexpected parameter: 'statuses' is only used when issuing a batch"
+            ));
+        }
 
-        Mdoc::prepare(
-            doc_type,
-            namespaces,
-            validity_info,
-            digest_algorithm,
-            device_key_info,
-            signature_algorithm,
-            enable_decoy_digests,
-        )
+        self.parameters()?
+            .prepare(device_key_info, signature_algorithm, rng)
     }
 
     /// Directly issue an mdoc.
@@ -278,33 +559,15 @@ impl Builder {
         S: Signer<Sig> + SignatureAlgorithm,
         Sig: SignatureEncoding,
     {
-        let doc_type = self
-            .doc_type
-            .ok_or_else(|| anyhow!("missing parameter: 'doc_type'"))?;
-        let namespaces = self
-            .namespaces
-            .ok_or_else(|| anyhow!("missing parameter: 'namespaces'"))?;
-        let validity_info = self
-            .validity_info
-            .ok_or_else(|| anyhow!("missing parameter: 'validity_info'"))?;
-        let digest_algorithm = self
-            .digest_algorithm
-            .ok_or_else(|| anyhow!("missing parameter: 'digest_algorithm'"))?;
-        let device_key_info = self
-            .device_key_info
-            .ok_or_else(|| anyhow!("missing parameter: 'device_key_info'"))?;
-        let enable_decoy_digests = self.enable_decoy_digests.unwrap_or(true);
+        let prepared_mdoc = self.prepare(signer.algorithm())?;
 
-        Mdoc::issue(
-            doc_type,
-            namespaces,
-            validity_info,
-            digest_algorithm,
-            device_key_info,
-            x5chain,
-            enable_decoy_digests,
-            signer,
-        )
+        let signature_payload = prepared_mdoc.signature_payload();
+        let signature = signer
+            .try_sign(signature_payload)
+            .map_err(|e| anyhow!("error signing cosesign1: {}", e))?
+            .to_vec();
+
+        prepared_mdoc.complete(x5chain, signature)
     }
 
     /// Directly issue an mdoc.
@@ -313,6 +576,134 @@ impl Builder {
         S: AsyncSigner<Sig> + SignatureAlgorithm,
         Sig: SignatureEncoding + Send + 'static,
     {
+        let prepared_mdoc = self.prepare(signer.algorithm())?;
+
+        let signature_payload = prepared_mdoc.signature_payload();
+        let signature = signer
+            .sign_async(signature_payload)
+            .await
+            .map_err(|e| anyhow!("error signing cosesign1: {}", e))?
+            .to_vec();
+
+        prepared_mdoc.complete(x5chain, signature)
+    }
+
+    /// Prepare a batch of mdocs for remote signing, one for each of the given device keys.
+    ///
+    /// Each mdoc is issued to one of `device_key_infos`, so the device key info must not be set on
+    /// the builder.
+    pub fn prepare_batch(
+        mut self,
+        device_key_infos: NonEmptyVec<DeviceKeyInfo>,
+        signature_algorithm: Algorithm,
+    ) -> Result<PreparedMdocBatch> {
+        if self.device_key_info.is_some() {
+            return Err(anyhow!(
+                "unexpected parameter: 'device_key_info' is replaced by the batch's device keys"
+            ));
+        }
+        if self.status.is_some() {
+            return Err(anyhow!(
+                "unexpected parameter: 'status' would be shared by the batch, use 'statuses'"
+            ));
+        }
+        let statuses = match self.statuses.take() {
+            Some(statuses) => {
+                if statuses.len() != device_key_infos.len() {
+                    return Err(anyhow!(
+                        "expected {} statuses, received {}",
+                        device_key_infos.len(),
+                        statuses.len()
+                    ));
+                }
+                if (1..statuses.len()).any(|i| statuses[..i].contains(&statuses[i])) {
+                    return Err(anyhow!("every mdoc of a batch needs a distinct status"));
+                }
+                statuses.into_iter().map(Some).collect()
+            }
+            None => vec![None; device_key_infos.len()],
+        };
+
+        let parameters = self.parameters()?;
+        let mut rng = rand::thread_rng();
+        let prepared_mdocs = device_key_infos
+            .into_inner()
+            .into_iter()
+            .zip(statuses)
+            .map(|(device_key_info, status)| {
+                Parameters {
+                    status,
+                    ..parameters.clone()
+                }
+                .prepare(device_key_info, signature_algorithm, &mut rng)
+            })
+            .collect::<Result<Vec<PreparedMdoc>>>()?;
+
+        // Safe to unwrap as there is one prepared mdoc for each of the device keys.
+        Ok(PreparedMdocBatch(prepared_mdocs.try_into().unwrap()))
+    }
+
+    /// Directly issue a batch of mdocs, one for each of the given device keys.
+    ///
+    /// Each mdoc is issued to one of `device_key_infos`, so the device key info must not be set on
+    /// the builder.
+    pub fn issue_batch<S, Sig>(
+        self,
+        device_key_infos: NonEmptyVec<DeviceKeyInfo>,
+        x5chain: X5Chain,
+        signer: S,
+    ) -> Result<Vec<Mdoc>>
+    where
+        S: Signer<Sig> + SignatureAlgorithm,
+        Sig: SignatureEncoding,
+    {
+        let prepared_batch = self.prepare_batch(device_key_infos, signer.algorithm())?;
+
+        let signatures = prepared_batch
+            .signature_payloads()
+            .map(|signature_payload| {
+                signer
+                    .try_sign(signature_payload)
+                    .map(|signature| signature.to_vec())
+                    .map_err(|e| anyhow!("error signing cosesign1: {}", e))
+            })
+            .collect::<Result<Vec<Vec<u8>>>>()?;
+
+        prepared_batch.complete(x5chain, signatures)
+    }
+
+    /// Directly issue a batch of mdocs, one for each of the given device keys.
+    ///
+    /// Each mdoc is issued to one of `device_key_infos`, so the device key info must not be set on
+    /// the builder.
+    pub async fn issue_batch_async<S, Sig>(
+        self,
+        device_key_infos: NonEmptyVec<DeviceKeyInfo>,
+        x5chain: X5Chain,
+        signer: S,
+    ) -> Result<Vec<Mdoc>>
+    where
+        S: AsyncSigner<Sig> + SignatureAlgorithm,
+        Sig: SignatureEncoding + Send + 'static,
+    {
+        let prepared_batch = self.prepare_batch(device_key_infos, signer.algorithm())?;
+
+        let mut signatures = Vec::new();
+        for signature_payload in prepared_batch.signature_payloads() {
+            let signature = signer
+                .sign_async(signature_payload)
+                .await
+                .map_err(|e| anyhow!("error signing cosesign1: {}", e))?
+                .to_vec();
+            signatures.push(signature);
+        }
+
+        prepared_batch.complete(x5chain, signatures)
+    }
+
+    /// Take the parameters shared by every issuance path, validating the data elements against
+    /// the registry on the date of signing unless validation was skipped.
+    fn parameters(self) -> Result<Parameters> {
         let doc_type = self
             .doc_type
             .ok_or_else(|| anyhow!("missing parameter: 'doc_type'"))?;
@@ -322,132 +713,222 @@ impl Builder {
         let validity_info = self
             .validity_info
             .ok_or_else(|| anyhow!("missing parameter: 'validity_info'"))?;
+        if !self.skip_validation {
+            self.registry.unwrap_or_default().validate_namespaces(
+                &doc_type,
+                &namespaces,
+                validity_info.signed.date(),
+            )?;
+        }
         let digest_algorithm = self
             .digest_algorithm
             .ok_or_else(|| anyhow!("missing parameter: 'digest_algorithm'"))?;
-        let device_key_info = self
-            .device_key_info
-            .ok_or_else(|| anyhow!("missing parameter: 'device_key_info'"))?;
-        let enable_decoy_digests = self.enable_decoy_digests.unwrap_or(true);
+        let decoy_strategy = self.decoy_strategy.unwrap_or_default();
 
-        Mdoc::issue_async(
+        Ok(Parameters {
             doc_type,
             namespaces,
             validity_info,
             digest_algorithm,
-            device_key_info,
-            x5chain,
-            enable_decoy_digests,
-            signer,
-        )
-        .await
+            status: self.status,
+            decoy_strategy,
+        })
     }
 }
 
-fn to_issuer_namespaces(namespaces: Namespaces) -> Result<IssuerNamespaces> {
-    namespaces
-        .into_iter()
-        .map(|(name, elements)| {
-            to_issuer_signed_items(elements)
-                .map(Tag24::new)
-                .collect::<Result<Vec<Tag24<IssuerSignedItem>>, _>>()
-                .map_err(|err| anyhow!("unable to encode IssuerSignedItem as cbor: {}", err))
-                .and_then(|items| {
-                    NonEmptyVec::try_from(items)
-                        .map_err(|_| anyhow!("at least one element required in each namespace"))
-                })
-                .map(|elems| (name, elems))
-        })
-        .collect::<Result<BTreeMap<String, NonEmptyVec<Tag24<IssuerSignedItem>>>>>()
-        .and_then(|namespaces| {
-            NonEmptyMap::try_from(namespaces)
-                .map_err(|_| anyhow!("at least one namespace required"))
-        })
+/// The parameters of an mdoc taken from a [Builder], other than its device key.
+#[derive(Debug, Clone)]
+struct Parameters {
+    doc_type: String,
+    namespaces: Namespaces,
+    validity_info: ValidityInfo,
+    digest_algorithm: DigestAlgorithm,
+    status: Option<Status>,
+    decoy_strategy: DecoyStrategy,
 }
 
-fn to_issuer_signed_items(
-    elements: BTreeMap<String, CborValue>,
-) -> impl Iterator<Item = IssuerSignedItem> {
-    let mut used_ids = HashSet::new();
-    elements.into_iter().map(move |(key, value)| {
-        let digest_id = generate_digest_id(&mut used_ids);
-        let random = Vec::from(rand::thread_rng().gen::<[u8; 16]>()).into();
-        IssuerSignedItem {
-            digest_id,
-            random,
-            element_identifier: key,
-            element_value: value,
+impl Parameters {
+    fn prepare<R: RngCore + CryptoRng>(
+        self,
+        device_key_info: DeviceKeyInfo,
+        signature_algorithm: Algorithm,
+        rng: &mut R,
+    ) -> Result<PreparedMdoc> {
+        let Parameters {
+            doc_type,
+            namespaces,
+            validity_info,
+            digest_algorithm,
+            status,
+            decoy_strategy,
+        } = self;
+
+        if let Some(authorizations) = &device_key_info.key_authorizations {
+            authorizations.validate()?;
         }
-    })
+
+        let (issuer_namespaces, value_digests) =
+            to_issuer_namespaces(namespaces, digest_algorithm, &decoy_strategy, rng)?;
+
+        let mso = Mso {
+            version: "1.0".to_string(),
+            digest_algorithm,
+            value_digests,
+            device_key_info,
+            doc_type: doc_type.clone(),
+            validity_info,
+            status,
+        };
+
+        let mso_bytes = serde_cbor::to_vec(&Tag24::new(&mso)?)?;
+
+        let prepared_sig = CoseSign1::builder()
+            .payload(mso_bytes)
+            .signature_algorithm(signature_algorithm)
+            .prepare()
+            .map_err(|e| anyhow!("error preparing cosesign1: {}", e))?;
+
+        let preparation_mdoc = PreparedMdoc {
+            doc_type,
+            namespaces: issuer_namespaces,
+            mso,
+            prepared_sig,
+            signature_format: signature_algorithm.into(),
+        };
+
+        Ok(preparation_mdoc)
+    }
 }
 
-fn digest_namespaces(
-    namespaces: &IssuerNamespaces,
+fn to_issuer_namespaces<R: RngCore + CryptoRng>(
+    namespaces: Namespaces,
     digest_algorithm: DigestAlgorithm,
-    enable_decoy_digests: bool,
-) -> Result<BTreeMap<String, DigestIds>> {
-    namespaces
-        .iter()
-        .map(|(name, elements)| {
-            Ok((
-                name.clone(),
-                digest_namespace(elements, digest_algorithm, enable_decoy_digests)?,
-            ))
-        })
-        .collect()
+    decoy_strategy: &DecoyStrategy,
+    rng: &mut R,
+) -> Result<(IssuerNamespaces, BTreeMap<String, DigestIds>)> {
+    // The digest IDs and salts of the data elements of every namespace are drawn before any
+    // decoys, so that mdocs issued from a seeded RNG with the default strategy are reproducible
+    // across versions.
+    let mut prepared = Vec::new();
+    for (name, elements) in namespaces {
+        let mut digest_ids = decoy_strategy.allocator(&name, elements.len(), rng)?;
+        let items = elements
+            .into_iter()
+            .map(|(key, value)| {
+                let digest_id = digest_ids.next(rng)?;
+                let random = Vec::from(rng.gen::<[u8; 16]>()).into();
+                Tag24::new(IssuerSignedItem {
+                    digest_id,
+                    random,
+                    element_identifier: key,
+                    element_value: value,
+                })
+                .map_err(|err| anyhow!("unable to encode IssuerSignedItem as cbor: {}", err))
+            })
+            .collect::<Result<Vec<Tag24<IssuerSignedItem>>>>()
+            .and_then(|items| {
+                NonEmptyVec::try_from(items)
+                    .map_err(|_| anyhow!("at least one element required in each namespace"))
+            })?;
+        prepared.push((name, items, digest_ids));
+    }
+
+    let mut issuer_namespaces = BTreeMap::new();
+    let mut value_digests = BTreeMap::new();
+    for (name, items, mut digest_ids) in prepared {
+        // Decoys are digests of random bytes.
+        let mut decoys = Vec::new();
+        for _ in 0..digest_ids.decoys(decoy_strategy, rng)? {
+            let digest_id = digest_ids.next(rng)?;
+            let mut random_bytes = vec![0; 512];
+            rng.fill_bytes(&mut random_bytes);
+            decoys.push((digest_id, random_bytes));
+        }
+
+        let digests = items
+            .iter()
+            .map(|item| Ok((item.as_ref().digest_id, serde_cbor::to_vec(item)?)))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .chain(decoys)
+            .map(|(digest_id, bytes)| (digest_id, digest(digest_algorithm, &bytes).into()))
+            .collect();
+
+        value_digests.insert(name.clone(), digests);
+        issuer_namespaces.insert(name, items);
+    }
+
+    let issuer_namespaces = NonEmptyMap::try_from(issuer_namespaces)
+        .map_err(|_| anyhow!("at least one namespace required"))?;
+
+    Ok((issuer_namespaces, value_digests))
 }
 
-fn digest_namespace(
-    elements: &[IssuerSignedItemBytes],
-    digest_algorithm: DigestAlgorithm,
-    enable_decoy_digests: bool,
-) -> Result<DigestIds> {
-    let mut used_ids = elements
-        .iter()
-        .map(|item| item.as_ref().digest_id)
-        .collect();
+/// A P-521 verifying key, which does not itself declare its COSE algorithm.
+pub(crate) struct P521VerifyingKey(pub(crate) p521::ecdsa::VerifyingKey);
 
-    // Generate X random digests to avoid leaking information.
-    let random_ids = std::iter::repeat_with(|| generate_digest_id(&mut used_ids));
-    let random_bytes = std::iter::repeat_with(|| {
-        std::iter::repeat_with(|| rand::thread_rng().gen::<u8>())
-            .take(512)
-            .collect()
-    });
-    let random_digests = random_ids
-        .zip(random_bytes)
-        .map(Result::<_, anyhow::Error>::Ok)
-        .take(if enable_decoy_digests {
-            rand::thread_rng().gen_range(5..10)
-        } else {
-            0
-        });
+impl Verifier<p521::ecdsa::Signature> for P521VerifyingKey {
+    fn verify(&self, msg: &[u8], signature: &p521::ecdsa::Signature) -> signature::Result<()> {
+        self.0.verify(msg, signature)
+    }
+}
 
-    elements
-        .iter()
-        .map(|item| Ok((item.as_ref().digest_id, serde_cbor::to_vec(item)?)))
-        .chain(random_digests)
-        .map(|result| {
-            let (digest_id, bytes) = result?;
-            let digest = match digest_algorithm {
-                DigestAlgorithm::SHA256 => Sha256::digest(bytes).to_vec(),
-                DigestAlgorithm::SHA384 => Sha384::digest(bytes).to_vec(),
-                DigestAlgorithm::SHA512 => Sha512::digest(bytes).to_vec(),
-            };
-            Ok((digest_id, digest.into()))
-        })
-        .collect()
+impl SignatureAlgorithm for P521VerifyingKey {
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::ES512
+    }
 }
 
-fn generate_digest_id(used_ids: &mut HashSet<DigestId>) -> DigestId {
-    let mut digest_id;
-    loop {
-        digest_id = DigestId::new(rand::thread_rng().gen());
-        if used_ids.insert(digest_id) {
-            break;
+/// Verify an issuer signature with the public key of the end-entity certificate in its x5chain.
+pub(crate) fn verify_issuer_auth(issuer_auth: &CoseSign1) -> Result<()> {
+    let x5chain = issuer_auth
+        .unprotected()
+        .get_i(X5CHAIN_HEADER_LABEL)
+        .ok_or_else(|| anyhow!("issuer signature has no x5chain"))?;
+    let public_key = X5Chain::from_cbor(x5chain)?.end_entity_public_key()?;
+
+    // The curve is identified by the length of the uncompressed SEC1 point.
+    let result = match public_key.len() {
+        65 => {
+            let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&public_key)
+                .map_err(|e| anyhow!("unable to parse P-256 issuer key: {}", e))?;
+            issuer_auth.verify::<_, p256::ecdsa::Signature>(&key, None, None)
+        }
+        97 => {
+            let key = p384::ecdsa::VerifyingKey::from_sec1_bytes(&public_key)
+                .map_err(|e| anyhow!("unable to parse P-384 issuer key: {}", e))?;
+            issuer_auth.verify::<_, p384::ecdsa::Signature>(&key, None, None)
+        }
+        133 => {
+            let key = p521::ecdsa::VerifyingKey::from_sec1_bytes(&public_key)
+                .map_err(|e| anyhow!("unable to parse P-521 issuer key: {}", e))?;
+            issuer_auth.verify::<_, p521::ecdsa::Signature>(&P521VerifyingKey(key), None, None)
+        }
+        _ => return Err(anyhow!("unsupported issuer key in x5chain")),
+    };
+
+    match result {
+        VerificationResult::Success => Ok(()),
+        VerificationResult::Failure(reason) => {
+            // A DER-encoded ECDSA signature is a SEQUENCE, whereas COSE requires r || s.
+            if issuer_auth.signature().first() == Some(&0x30) {
+                Err(anyhow!(
+                    "issuer signature is invalid, and appears to be DER encoded rather than raw r || s: {reason}"
+                ))
+            } else {
+                Err(anyhow!("issuer signature is invalid: {reason}"))
+            }
         }
+        VerificationResult::Error(e) => Err(anyhow!("unable to verify issuer signature: {}", e)),
+    }
+}
+
+pub(crate) fn digest(digest_algorithm: DigestAlgorithm, bytes: &[u8]) -> Vec<u8> {
+    match digest_algorithm {
+        DigestAlgorithm::SHA256 => Sha256::digest(bytes).to_vec(),
+        DigestAlgorithm::SHA384 => Sha384::digest(bytes).to_vec(),
+        DigestAlgorithm::SHA512 => Sha512::digest(bytes).to_vec(),
     }
-    digest_id
 }
 
 #[cfg(test)]
@@ -455,20 +936,33 @@ pub mod test {
     use super::*;
     use crate::definitions::device_key::cose_key::{CoseKey, EC2Curve, EC2Y};
     use crate::definitions::namespaces::{
-        org_iso_18013_5_1::OrgIso1801351, org_iso_18013_5_1_aamva::OrgIso1801351Aamva,
+        org_iso_18013_5_1::OrgIso1801351,
+        org_iso_18013_5_1_aamva::OrgIso1801351Aamva,
+        registry::{DocTypeSchema, ElementType, NamespaceSchema},
     };
 
-    use crate::definitions::traits::{FromJson, ToNamespaceMap};
+    use crate::definitions::status_list::{Bits, StatusListInfo};
+    use crate::definitions::traits::FromJson;
+    use crate::definitions::DigestId;
+    use crate::issuance::decoys::{DecoyCount, DigestIdAllocation};
+    use crate::issuance::pki::test::P521Signer;
+    use crate::issuance::status_list::StatusListPublisher;
     use elliptic_curve::sec1::ToEncodedPoint;
     use p256::ecdsa::{Signature, SigningKey};
     use p256::pkcs8::DecodePrivateKey;
     use p256::SecretKey;
-    use time::OffsetDateTime;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+    use signature::Signer;
+    use time::{macros::datetime, OffsetDateTime};
 
     static ISSUER_CERT: &[u8] = include_bytes!("../../test/issuance/issuer-cert.pem");
     static ISSUER_KEY: &str = include_str!("../../test/issuance/issuer-key.pem");
+    // SHA-256 of the issuer-signed namespaces, which embed the sizeable portrait.
+    static SEEDED_NAMESPACES_SHA256: &str =
+        "cf04379e17a510623b50d18cef5c5f0b4ae8440f47ebc9c1522a4aa5b34b0d83";
+    static SEEDED_MSO: &str = include_str!("../../test/issuance/seeded_mso.cbor");
 
-    fn isomdl_data() -> serde_json::Value {
+    pub fn isomdl_data() -> serde_json::Value {
         serde_json::json!(
             {
               "family_name":"Smith",
@@ -565,34 +1059,7 @@ pub mod test {
         Ok(())
     }
 
-    fn minimal_test_mdoc_builder() -> Builder {
-        let doc_type = String::from("org.iso.18013.5.1.mDL");
-        let isomdl_namespace = String::from("org.iso.18013.5.1");
-        let aamva_namespace = String::from("org.iso.18013.5.1.aamva");
-
-        let isomdl_data = OrgIso1801351::from_json(&isomdl_data())
-            .unwrap()
-            .to_ns_map();
-        let aamva_data = OrgIso1801351Aamva::from_json(&aamva_isomdl_data())
-            .unwrap()
-            .to_ns_map();
-
-        let namespaces = [
-            (isomdl_namespace, isomdl_data),
-            (aamva_namespace, aamva_data),
-        ]
-        .into_iter()
-        .collect();
-
-        let validity_info = ValidityInfo {
-            signed: OffsetDateTime::now_utc(),
-            valid_from: OffsetDateTime::now_utc(),
-            valid_until: OffsetDateTime::now_utc(),
-            expected_update: None,
-        };
-
-        let digest_algorithm = DigestAlgorithm::SHA256;
-
+    pub fn minimal_test_mdoc_builder() -> Builder {
         let der = include_str!("../../test/issuance/device_key.b64");
         let der_bytes = base64::decode(der).unwrap();
         let key = p256::SecretKey::from_sec1_der(&der_bytes).unwrap();
@@ -612,12 +1079,31 @@ pub mod test {
             key_info: None,
         };
 
+        minimal_test_batch_builder().device_key_info(device_key_info)
+    }
+
+    /// The builder of [minimal_test_mdoc_builder] without a device key, for batch issuance.
+    pub fn minimal_test_batch_builder() -> Builder {
+        let doc_type = String::from("org.iso.18013.5.1.mDL");
+
+        let isomdl_data = OrgIso1801351::from_json(&isomdl_data()).unwrap();
+        let aamva_data = OrgIso1801351Aamva::from_json(&aamva_isomdl_data()).unwrap();
+
+        let validity_info = ValidityInfo {
+            signed: OffsetDateTime::now_utc(),
+            valid_from: OffsetDateTime::now_utc(),
+            valid_until: OffsetDateTime::now_utc(),
+            expected_update: None,
+        };
+
+        let digest_algorithm = DigestAlgorithm::SHA256;
+
         Mdoc::builder()
             .doc_type(doc_type)
-            .namespaces(namespaces)
+            .namespace(isomdl_data)
+            .namespace(aamva_data)
             .validity_info(validity_info)
             .digest_algorithm(digest_algorithm)
-            .device_key_info(device_key_info)
     }
 
     pub fn minimal_test_mdoc() -> anyhow::Result<Mdoc> {
@@ -637,6 +1123,262 @@ pub mod test {
             .expect("failed to issue mdoc"))
     }
 
+    #[test]
+    fn refuse_invalid_mdl() {
+        let mut json = isomdl_data();
+        json["age_over_65"] = true.into();
+        let isomdl_data = OrgIso1801351::from_json(&json).unwrap();
+        let builder = minimal_test_mdoc_builder().namespace(isomdl_data);
+        assert!(builder.clone().prepare(Algorithm::ES256).is_err());
+        builder.skip_validation().prepare(Algorithm::ES256).unwrap();
+
+        let mut namespaces = Namespaces::new();
+        namespaces.insert(
+            OrgIso1801351::NAMESPACE.to_string(),
+            [("family_name".to_string(), "Smith".to_string().into())]
+                .into_iter()
+                .collect(),
+        );
+        let result = minimal_test_mdoc_builder()
+            .namespaces(namespaces)
+            .prepare(Algorithm::ES256);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn registry_validation() {
+        minimal_test_mdoc_builder()
+            .prepare(Algorithm::ES256)
+            .unwrap();
+
+        let mut namespaces = Namespaces::new();
+        namespaces.insert(
+            "com.example.loyalty.1".to_string(),
+            [("member_id".to_string(), CborValue::Integer(123))]
+                .into_iter()
+                .collect(),
+        );
+        let builder = minimal_test_mdoc_builder()
+            .doc_type("com.example.loyalty.1".to_string())
+            .namespaces(namespaces);
+
+        // Unregistered document types are rejected unless validation is skipped.
+        assert!(builder.clone().prepare(Algorithm::ES256).is_err());
+        builder
+            .clone()
+            .skip_validation()
+            .prepare(Algorithm::ES256)
+            .unwrap();
+
+        let mut registry = Registry::empty();
+        registry.register_doc_type(
+            "com.example.loyalty.1",
+            DocTypeSchema::new().required("com.example.loyalty.1"),
+        );
+        registry.register_namespace(
+            "com.example.loyalty.1",
+            NamespaceSchema::new().mandatory("member_id", ElementType::Text),
+        );
+        assert!(builder
+            .registry(registry)
+            .prepare(Algorithm::ES256)
+            .is_err());
+    }
+
+    #[test]
+    fn batch_issuance() {
+        let device_key_infos: Vec<DeviceKeyInfo> = std::iter::repeat_with(|| {
+            let ec = SecretKey::random(&mut rand::rngs::OsRng)
+                .public_key()
+                .to_encoded_point(false);
+            DeviceKeyInfo {
+                device_key: CoseKey::EC2 {
+                    crv: EC2Curve::P256,
+                    x: ec.x().unwrap().to_vec(),
+                    y: EC2Y::Value(ec.y().unwrap().to_vec()),
+                },
+                key_authorizations: None,
+                key_info: None,
+            }
+        })
+        .take(3)
+        .collect();
+        let x5chain = X5Chain::builder()
+            .with_pem(ISSUER_CERT)
+            .unwrap()
+            .build()
+            .unwrap();
+        let signer: SigningKey = SecretKey::from_pkcs8_pem(ISSUER_KEY)
+            .expect("failed to parse pem")
+            .into();
+
+        assert!(minimal_test_mdoc_builder()
+            .issue_batch::<SigningKey, Signature>(
+                device_key_infos.clone().try_into().unwrap(),
+                x5chain.clone(),
+                signer.clone(),
+            )
+            .is_err());
+
+        let mdocs = minimal_test_batch_builder()
+            .issue_batch::<SigningKey, Signature>(
+                device_key_infos.clone().try_into().unwrap(),
+                x5chain,
+                signer,
+            )
+            .unwrap();
+        assert_eq!(mdocs.len(), 3);
+
+        let mut salts = HashSet::new();
+        for (mdoc, device_key_info) in mdocs.iter().zip(&device_key_infos) {
+            assert_eq!(
+                mdoc.mso.device_key_info.device_key,
+                device_key_info.device_key
+            );
+            for item in mdoc.namespaces.values().flat_map(|items| items.iter()) {
+                assert!(salts.insert(item.as_ref().random.as_ref().to_vec()));
+            }
+        }
+        assert_ne!(mdocs[0].mso.value_digests, mdocs[1].mso.value_digests);
+        assert_ne!(mdocs[1].mso.value_digests, mdocs[2].mso.value_digests);
+    }
+
+    #[test]
+    fn batch_statuses() {
+        let device_key_infos: NonEmptyVec<DeviceKeyInfo> = std::iter::repeat_with(|| {
+            let ec = SecretKey::random(&mut rand::rngs::OsRng)
+                .public_key()
+                .to_encoded_point(false);
+            DeviceKeyInfo {
+                device_key: CoseKey::EC2 {
+                    crv: EC2Curve::P256,
+                    x: ec.x().unwrap().to_vec(),
+                    y: EC2Y::Value(ec.y().unwrap().to_vec()),
+                },
+                key_authorizations: None,
+                key_info: None,
+            }
+        })
+        .take(3)
+        .collect::<Vec<_>>()
+        .try_into()
+        .unwrap();
+        let mut publisher = StatusListPublisher::new(
+            "https://example.com/statuslists/1".to_string(),
+            Bits::One,
+            1024,
+        );
+        let statuses: Vec<Status> = (0..3).map(|_| publisher.allocate().unwrap()).collect();
+
+        assert!(minimal_test_batch_builder()
+            .status(statuses[0].clone())
+            .prepare_batch(device_key_infos.clone(), Algorithm::ES256)
+            .is_err());
+        assert!(minimal_test_batch_builder()
+            .statuses(statuses[..2].to_vec())
+            .prepare_batch(device_key_infos.clone(), Algorithm::ES256)
+            .is_err());
+        assert!(minimal_test_batch_builder()
+            .statuses(vec![statuses[0].clone(); 3])
+            .prepare_batch(device_key_infos.clone(), Algorithm::ES256)
+            .is_err());
+        assert!(minimal_test_mdoc_builder()
+            .statuses(statuses.clone())
+            .prepare(Algorithm::ES256)
+            .is_err());
+
+        let prepared = minimal_test_batch_builder()
+            .statuses(statuses.clone())
+            .prepare_batch(device_key_infos, Algorithm::ES256)
+            .unwrap();
+        let mso_statuses: Vec<Option<Status>> = prepared
+            .into_inner()
+            .into_iter()
+            .map(|prepared_mdoc| prepared_mdoc.mso.status)
+            .collect();
+        assert_eq!(
+            mso_statuses,
+            statuses.into_iter().map(Some).collect::<Vec<_>>()
+        );
+        assert_ne!(mso_statuses[0], mso_statuses[1]);
+        assert_ne!(mso_statuses[1], mso_statuses[2]);
+    }
+
+    #[test]
+    fn status() {
+        let status = Status {
+            status_list: Some(StatusListInfo {
+                idx: 42,
+                uri: "https://example.com/statuslists/1".to_string(),
+            }),
+            identifier_list: None,
+        };
+        let x5chain = X5Chain::builder()
+            .with_pem(ISSUER_CERT)
+            .unwrap()
+            .build()
+            .unwrap();
+        let signer: SigningKey = SecretKey::from_pkcs8_pem(ISSUER_KEY)
+            .expect("failed to parse pem")
+            .into();
+
+        let mdoc = minimal_test_mdoc_builder()
+            .status(status.clone())
+            .issue::<SigningKey, Signature>(x5chain, signer)
+            .unwrap();
+
+        let mso: Tag24<Mso> = serde_cbor::from_slice(mdoc.issuer_auth.payload().unwrap()).unwrap();
+        assert_eq!(mso.into_inner().status, Some(status));
+    }
+
+    fn seeded_test_mdoc(seed: u64) -> Mdoc {
+        let validity_info = ValidityInfo {
+            signed: datetime!(2023-01-01 0:00 UTC),
+            valid_from: datetime!(2023-01-01 0:00 UTC),
+            valid_until: datetime!(2033-01-01 0:00 UTC),
+            expected_update: None,
+        };
+        let x5chain = X5Chain::builder()
+            .with_pem(ISSUER_CERT)
+            .unwrap()
+            .build()
+            .unwrap();
+        let signer: SigningKey = SecretKey::from_pkcs8_pem(ISSUER_KEY)
+            .expect("failed to parse pem")
+            .into();
+
+        let prepared_mdoc = minimal_test_mdoc_builder()
+            .validity_info(validity_info)
+            .prepare_with_rng(Algorithm::ES256, &mut ChaCha20Rng::seed_from_u64(seed))
+            .unwrap();
+        // ECDSA signatures are deterministic (RFC 6979), so the whole mdoc is reproducible.
+        let signature: Signature = signer.sign(prepared_mdoc.signature_payload());
+        prepared_mdoc.complete(x5chain, signature.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn seeded_issuance() {
+        let mdoc = seeded_test_mdoc(0);
+        assert_eq!(
+            hex::encode(Sha256::digest(
+                serde_cbor::to_vec(&mdoc.namespaces).unwrap()
+            )),
+            SEEDED_NAMESPACES_SHA256
+        );
+        assert_eq!(
+            hex::encode(serde_cbor::to_vec(&Tag24::new(&mdoc.mso).unwrap()).unwrap()),
+            SEEDED_MSO.trim()
+        );
+        assert_eq!(
+            serde_cbor::to_vec(&mdoc).unwrap(),
+            serde_cbor::to_vec(&seeded_test_mdoc(0)).unwrap()
+        );
+        assert_ne!(
+            mdoc.mso.value_digests,
+            seeded_test_mdoc(1).mso.value_digests
+        );
+    }
+
     #[test]
     fn decoy_digests() {
         let mdoc_builder = minimal_test_mdoc_builder();
@@ -699,4 +1441,127 @@ pub mod test {
                 .fold(0, |acc, x| acc + x.len()),
         );
     }
+
+    #[test]
+    fn decoy_strategy() {
+        let strategy = DecoyStrategy::default()
+            .count(DecoyCount::Bucket(16))
+            .namespace(
+                OrgIso1801351Aamva::NAMESPACE.to_string(),
+                DecoyCount::Target(32),
+            )
+            .digest_ids(DigestIdAllocation::Shuffled);
+        let mdoc = minimal_test_mdoc_builder()
+            .decoy_strategy(strategy)
+            .prepare(Algorithm::ES256)
+            .unwrap();
+
+        for (namespace, digests) in &mdoc.mso.value_digests {
+            if namespace == OrgIso1801351Aamva::NAMESPACE {
+                assert_eq!(digests.len(), 32);
+            } else {
+                assert_eq!(digests.len() % 16, 0);
+            }
+            // Shuffled digest IDs are exactly 0..d, shared between real and decoy digests.
+            let digest_ids: Vec<DigestId> = digests.keys().copied().collect();
+            assert_eq!(
+                digest_ids,
+                (0..digests.len() as i32)
+                    .map(DigestId::new)
+                    .collect::<Vec<_>>()
+            );
+            for item in mdoc.namespaces[namespace].iter() {
+                assert!(digests.contains_key(&item.as_ref().digest_id));
+            }
+        }
+
+        let result = minimal_test_mdoc_builder()
+            .decoy_strategy(DecoyStrategy::default().count(DecoyCount::Target(2)))
+            .prepare(Algorithm::ES256);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn verify() {
+        let mdoc = seeded_test_mdoc(0);
+        mdoc.verify().unwrap();
+
+        let mut tampered = mdoc.clone();
+        tampered.doc_type = "org.example.other".to_string();
+        assert!(tampered.verify().is_err());
+
+        let mut tampered = mdoc.clone();
+        let mut namespaces: BTreeMap<_, _> = tampered.namespaces.into();
+        let items = namespaces.get_mut(OrgIso1801351::NAMESPACE).unwrap();
+        let mut items: Vec<_> = items.clone().into_inner();
+        let mut item = items[0].clone().into_inner();
+        item.element_value = CborValue::Text("tampered".to_string());
+        items[0] = Tag24::new(item).unwrap();
+        namespaces.insert(
+            OrgIso1801351::NAMESPACE.to_string(),
+            items.try_into().unwrap(),
+        );
+        tampered.namespaces = namespaces.try_into().unwrap();
+        assert!(tampered
+            .verify()
+            .unwrap_err()
+            .to_string()
+            .contains("does not match the MSO"));
+    }
+
+    #[test]
+    fn p521_issuer() {
+        static CERT_521: &[u8] = include_bytes!("../../test/issuance/521-cert.pem");
+        static KEY_521: &str = include_str!("../../test/issuance/521-key.pem");
+        let x5chain = X5Chain::builder()
+            .with_pem(CERT_521)
+            .unwrap()
+            .build()
+            .unwrap();
+        let secret_key = p521::SecretKey::from_pkcs8_pem(KEY_521).expect("failed to parse pem");
+        let signer =
+            P521Signer(p521::ecdsa::SigningKey::from_bytes(&secret_key.to_bytes()).unwrap());
+        let mdoc = minimal_test_mdoc_builder().issue(x5chain, signer).unwrap();
+        assert_eq!(mdoc.issuer_auth.signature().len(), 132);
+        mdoc.verify().unwrap();
+
+        let mut tampered = mdoc;
+        tampered.doc_type = "org.example.other".to_string();
+        assert!(tampered.verify().is_err());
+    }
+
+    #[test]
+    fn der_signature() {
+        let x5chain = X5Chain::builder()
+            .with_pem(ISSUER_CERT)
+            .unwrap()
+            .build()
+            .unwrap();
+        let signer: SigningKey = SecretKey::from_pkcs8_pem(ISSUER_KEY)
+            .expect("failed to parse pem")
+            .into();
+        let prepared_mdoc = minimal_test_mdoc_builder()
+            .validity_info(ValidityInfo {
+                signed: datetime!(2023-01-01 0:00 UTC),
+                valid_from: datetime!(2023-01-01 0:00 UTC),
+                valid_until: datetime!(2033-01-01 0:00 UTC),
+                expected_update: None,
+            })
+            .prepare(Algorithm::ES256)
+            .unwrap();
+        let signature: Signature = signer.sign(prepared_mdoc.signature_payload());
+
+        let mut truncated = signature.to_vec();
+        truncated.pop();
+        assert!(prepared_mdoc
+            .clone()
+            .complete(x5chain.clone(), truncated)
+            .is_err());
+
+        let mdoc = prepared_mdoc
+            .complete(x5chain, signature.to_der().as_bytes().to_vec())
+            .unwrap();
+        assert_eq!(mdoc.issuer_auth.signature(), signature.to_vec());
+        mdoc.verify().unwrap();
+    }
 }
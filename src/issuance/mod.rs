@@ -1,5 +1,14 @@
+pub mod decoys;
+pub mod mdl;
 pub mod mdoc;
+pub mod pki;
+pub mod refresh;
+pub mod status_list;
 pub mod x5chain;
 
+pub use decoys::DecoyStrategy;
 pub use mdoc::{Mdoc, Namespaces};
+pub use pki::{DocumentSigner, Iaca};
+pub use refresh::{Changes, Refresh};
+pub use status_list::StatusListPublisher;
 pub use x5chain::{Builder, X5Chain};
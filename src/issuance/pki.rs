@@ -0,0 +1,856 @@
+//! Generation of the issuing authority certificate authority (IACA) and document signer
+//! certificates of an issuer's PKI, following the profiles in ISO/IEC 18013-5 Annex B.
+//!
+//! This is intended for integration tests and staging environments, where it removes the need to
+//! hand-craft certificates with external tools. Production PKIs will typically be operated with
+//! dedicated CA software.
+use crate::{definitions::helpers::SignatureFormat, issuance::x5chain::X5Chain};
+use anyhow::{anyhow, Result};
+use cose_rs::algorithm::{Algorithm, SignatureAlgorithm};
+use p256::pkcs8::EncodePublicKey;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use signature::{SignatureEncoding, Signer};
+use time::{Duration, OffsetDateTime};
+use x509_cert::{
+    attr::AttributeTypeAndValue,
+    der::{
+        asn1::{
+            Any, BitString, GeneralizedTime, Ia5String, OctetString, PrintableStringRef, UintRef,
+            UtcTime, Utf8StringRef,
+        },
+        oid::AssociatedOid,
+        DateTime, Encode,
+    },
+    ext::{
+        pkix::{
+            crl::dp::DistributionPoint,
+            name::{DistributionPointName, GeneralName},
+            AuthorityKeyIdentifier, BasicConstraints, CrlDistributionPoints, ExtendedKeyUsage,
+            IssuerAltName, KeyUsage, KeyUsages, SubjectKeyIdentifier,
+        },
+        Extension,
+    },
+    name::{Name, RdnSequence, RelativeDistinguishedName},
+    serial_number::SerialNumber,
+    spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SubjectPublicKeyInfoOwned},
+    time::{Time, Validity},
+    Certificate, TbsCertificate, Version,
+};
+
+/// The maximum validity period of an IACA certificate, as per Table B.1.
+pub const IACA_MAX_VALIDITY: Duration = Duration::days(20 * 365 + 5);
+/// The maximum validity period of a document signer certificate, as per Table B.3.
+pub const DOCUMENT_SIGNER_MAX_VALIDITY: Duration = Duration::days(1187);
+
+const COUNTRY_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.6");
+const STATE_OR_PROVINCE_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.8");
+const ORGANIZATION_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.10");
+const COMMON_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.3");
+const ECDSA_WITH_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const ECDSA_WITH_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+const ECDSA_WITH_SHA512: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.4");
+/// id-mdl-kp-mdlDS, the extended key usage of a document signer.
+const MDL_DS: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.0.18013.5.1.2");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The issuer alternative name, by which relying parties can contact the issuing authority.
+pub enum IssuerAlternativeName {
+    Email(String),
+    Uri(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IacaBuilder {
+    country: Option<String>,
+    state_or_province: Option<String>,
+    organization: Option<String>,
+    common_name: Option<String>,
+    issuer_alternative_name: Option<IssuerAlternativeName>,
+    crl_distribution_point: Option<String>,
+    not_before: Option<OffsetDateTime>,
+    not_after: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone)]
+/// A self-signed IACA root certificate.
+pub struct Iaca {
+    der: Vec<u8>,
+    country: String,
+    subject: Name,
+    key_identifier: Vec<u8>,
+    issuer_alternative_name: IssuerAlternativeName,
+    crl_distribution_point: String,
+    not_before: OffsetDateTime,
+    not_after: OffsetDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct DocumentSignerBuilder<'a> {
+    iaca: &'a Iaca,
+    state_or_province: Option<String>,
+    organization: Option<String>,
+    common_name: Option<String>,
+    issuer_alternative_name: Option<IssuerAlternativeName>,
+    crl_distribution_point: Option<String>,
+    not_before: Option<OffsetDateTime>,
+    not_after: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone)]
+/// A document signer certificate, issued by an IACA, for signing mdocs.
+pub struct DocumentSigner {
+    der: Vec<u8>,
+}
+
+impl Iaca {
+    pub fn builder() -> IacaBuilder {
+        IacaBuilder::default()
+    }
+
+    /// The DER encoding of the certificate.
+    pub fn to_der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// The PEM encoding of the certificate.
+    pub fn to_pem(&self) -> Result<String> {
+        to_pem(&self.der)
+    }
+
+    /// Begin issuing a document signer certificate from this IACA.
+    ///
+    /// The country, issuer alternative name and CRL distribution point default to those of the
+    /// IACA.
+    pub fn document_signer(&self) -> DocumentSignerBuilder<'_> {
+        DocumentSignerBuilder {
+            iaca: self,
+            state_or_province: None,
+            organization: None,
+            common_name: None,
+            issuer_alternative_name: None,
+            crl_distribution_point: None,
+            not_before: None,
+            not_after: None,
+        }
+    }
+}
+
+impl DocumentSigner {
+    /// The DER encoding of the certificate.
+    pub fn to_der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// The PEM encoding of the certificate.
+    pub fn to_pem(&self) -> Result<String> {
+        to_pem(&self.der)
+    }
+
+    /// The x5chain to include when issuing mdocs with this document signer.
+    ///
+    /// The IACA certificate is not included, as it is distributed to relying parties out of
+    /// band.
+    pub fn x5chain(&self) -> Result<X5Chain> {
+        X5Chain::builder().with_der(&self.der)?.build()
+    }
+}
+
+impl IacaBuilder {
+    /// Set the country, as an ISO 3166-1 alpha-2 code.
+    pub fn country(mut self, country: String) -> Self {
+        self.country = Some(country);
+        self
+    }
+
+    /// Set the state or province, which must be given if the issuing authority is not national.
+    pub fn state_or_province(mut self, state_or_province: String) -> Self {
+        self.state_or_province = Some(state_or_province);
+        self
+    }
+
+    /// Set the organization name.
+    pub fn organization(mut self, organization: String) -> Self {
+        self.organization = Some(organization);
+        self
+    }
+
+    /// Set the common name.
+    pub fn common_name(mut self, common_name: String) -> Self {
+        self.common_name = Some(common_name);
+        self
+    }
+
+    /// Set the issuer alternative name.
+    pub fn issuer_alternative_name(mut self, name: IssuerAlternativeName) -> Self {
+        self.issuer_alternative_name = Some(name);
+        self
+    }
+
+    /// Set the URI at which the certificate revocation list is published.
+    pub fn crl_distribution_point(mut self, uri: String) -> Self {
+        self.crl_distribution_point = Some(uri);
+        self
+    }
+
+    /// Set the validity period, which must not exceed [IACA_MAX_VALIDITY].
+    pub fn validity(mut self, not_before: OffsetDateTime, not_after: OffsetDateTime) -> Self {
+        self.not_before = Some(not_before);
+        self.not_after = Some(not_after);
+        self
+    }
+
+    /// Generate the self-signed IACA certificate for the given key pair.
+    pub fn build<K, S, Sig>(self, public_key: &K, signer: S) -> Result<Iaca>
+    where
+        K: EncodePublicKey,
+        S: Signer<Sig> + SignatureAlgorithm,
+        Sig: SignatureEncoding,
+    {
+        let country = self
+            .country
+            .ok_or_else(|| anyhow!("missing parameter: 'country'"))?;
+        let common_name = self
+            .common_name
+            .ok_or_else(|| anyhow!("missing parameter: 'common_name'"))?;
+        let issuer_alternative_name = self
+            .issuer_alternative_name
+            .ok_or_else(|| anyhow!("missing parameter: 'issuer_alternative_name'"))?;
+        let crl_distribution_point = self
+            .crl_distribution_point
+            .ok_or_else(|| anyhow!("missing parameter: 'crl_distribution_point'"))?;
+        let not_before = self
+            .not_before
+            .ok_or_else(|| anyhow!("missing parameter: 'validity'"))?;
+        let not_after = self
+            .not_after
+            .ok_or_else(|| anyhow!("missing parameter: 'validity'"))?;
+        validate_country(&country)?;
+        validate_validity(not_before, not_after, IACA_MAX_VALIDITY)?;
+
+        let subject = name(
+            &country,
+            self.state_or_province.as_deref(),
+            self.organization.as_deref(),
+            &common_name,
+        )?;
+        let spki = spki(public_key)?;
+        let key_identifier = key_identifier(&spki);
+
+        let extensions = vec![
+            extension(
+                &SubjectKeyIdentifier(OctetString::new(key_identifier.clone())?),
+                false,
+            )?,
+            extension(&KeyUsage(KeyUsages::KeyCertSign | KeyUsages::CRLSign), true)?,
+            // A CA which may only issue end-entity certificates.
+            extension(
+                &BasicConstraints {
+                    ca: true,
+                    path_len_constraint: Some(0),
+                },
+                true,
+            )?,
+            extension(
+                &IssuerAltName(vec![general_name(&issuer_alternative_name)?]),
+                false,
+            )?,
+            extension(&crl_distribution_points(&crl_distribution_point)?, false)?,
+        ];
+
+        let der = certificate(
+            subject.clone(),
+            subject.clone(),
+            not_before,
+            not_after,
+            spki,
+            extensions,
+            signer,
+        )?;
+
+        Ok(Iaca {
+            der,
+            country,
+            subject,
+            key_identifier,
+            issuer_alternative_name,
+            crl_distribution_point,
+            not_before,
+            not_after,
+        })
+    }
+}
+
+impl<'a> DocumentSignerBuilder<'a> {
+    /// Set the state or province.
+    pub fn state_or_province(mut self, state_or_province: String) -> Self {
+        self.state_or_province = Some(state_or_province);
+        self
+    }
+
+    /// Set the organization name.
+    pub fn organization(mut self, organization: String) -> Self {
+        self.organization = Some(organization);
+        self
+    }
+
+    /// Set the common name.
+    pub fn common_name(mut self, common_name: String) -> Self {
+        self.common_name = Some(common_name);
+        self
+    }
+
+    /// Set the issuer alternative name.
+    pub fn issuer_alternative_name(mut self, name: IssuerAlternativeName) -> Self {
+        self.issuer_alternative_name = Some(name);
+        self
+    }
+
+    /// Set the URI at which the certificate revocation list is published.
+    pub fn crl_distribution_point(mut self, uri: String) -> Self {
+        self.crl_distribution_point = Some(uri);
+        self
+    }
+
+    /// Set the validity period, which must not exceed [DOCUMENT_SIGNER_MAX_VALIDITY] and must
+    /// fall within the validity period of the IACA.
+    pub fn validity(mut self, not_before: OffsetDateTime, not_after: OffsetDateTime) -> Self {
+        self.not_before = Some(not_before);
+        self.not_after = Some(not_after);
+        self
+    }
+
+    /// Generate the document signer certificate for the given public key, signed by the IACA.
+    pub fn build<K, S, Sig>(self, public_key: &K, iaca_signer: S) -> Result<DocumentSigner>
+    where
+        K: EncodePublicKey,
+        S: Signer<Sig> + SignatureAlgorithm,
+        Sig: SignatureEncoding,
+    {
+        let iaca = self.iaca;
+        let common_name = self
+            .common_name
+            .ok_or_else(|| anyhow!("missing parameter: 'common_name'"))?;
+        let not_before = self
+            .not_before
+            .ok_or_else(|| anyhow!("missing parameter: 'validity'"))?;
+        let not_after = self
+            .not_after
+            .ok_or_else(|| anyhow!("missing parameter: 'validity'"))?;
+        validate_validity(not_before, not_after, DOCUMENT_SIGNER_MAX_VALIDITY)?;
+        if not_before < iaca.not_before || not_after > iaca.not_after {
+            return Err(anyhow!(
+                "document signer validity must fall within the validity of the IACA"
+            ));
+        }
+        let issuer_alternative_name = self
+            .issuer_alternative_name
+            .unwrap_or_else(|| iaca.issuer_alternative_name.clone());
+        let crl_distribution_point = self
+            .crl_distribution_point
+            .unwrap_or_else(|| iaca.crl_distribution_point.clone());
+
+        let subject = name(
+            &iaca.country,
+            self.state_or_province.as_deref(),
+            self.organization.as_deref(),
+            &common_name,
+        )?;
+        let spki = spki(public_key)?;
+
+        let extensions = vec![
+            extension(
+                &AuthorityKeyIdentifier {
+                    key_identifier: Some(OctetString::new(iaca.key_identifier.clone())?),
+                    authority_cert_issuer: None,
+                    authority_cert_serial_number: None,
+                },
+                false,
+            )?,
+            extension(
+                &SubjectKeyIdentifier(OctetString::new(key_identifier(&spki))?),
+                false,
+            )?,
+            extension(&KeyUsage(KeyUsages::DigitalSignature.into()), true)?,
+            extension(&ExtendedKeyUsage(vec![MDL_DS]), true)?,
+            extension(
+                &IssuerAltName(vec![general_name(&issuer_alternative_name)?]),
+                false,
+            )?,
+            extension(&crl_distribution_points(&crl_distribution_point)?, false)?,
+        ];
+
+        let der = certificate(
+            iaca.subject.clone(),
+            subject,
+            not_before,
+            not_after,
+            spki,
+            extensions,
+            iaca_signer,
+        )?;
+
+        Ok(DocumentSigner { der })
+    }
+}
+
+fn validate_country(country: &str) -> Result<()> {
+    if country.len() != 2 || !country.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err(anyhow!(
+            "country must be an ISO 3166-1 alpha-2 code, received '{country}'"
+        ));
+    }
+    Ok(())
+}
+
+fn validate_validity(
+    not_before: OffsetDateTime,
+    not_after: OffsetDateTime,
+    max_validity: Duration,
+) -> Result<()> {
+    if not_after <= not_before {
+        return Err(anyhow!("certificate must expire after it becomes valid"));
+    }
+    if not_after - not_before > max_validity {
+        return Err(anyhow!(
+            "certificate validity must not exceed {} days",
+            max_validity.whole_days()
+        ));
+    }
+    Ok(())
+}
+
+fn spki<K: EncodePublicKey>(public_key: &K) -> Result<SubjectPublicKeyInfoOwned> {
+    Ok(public_key
+        .to_public_key_der()
+        .map_err(|e| anyhow!("unable to encode public key: {}", e))?
+        .decode_msg()?)
+}
+
+/// The leftmost 160 bits of the SHA-256 hash of the subject public key, as per RFC 7093.
+fn key_identifier(spki: &SubjectPublicKeyInfoOwned) -> Vec<u8> {
+    Sha256::digest(spki.subject_public_key.raw_bytes())[..20].to_vec()
+}
+
+fn certificate<S, Sig>(
+    issuer: Name,
+    subject: Name,
+    not_before: OffsetDateTime,
+    not_after: OffsetDateTime,
+    subject_public_key_info: SubjectPublicKeyInfoOwned,
+    extensions: Vec<Extension>,
+    signer: S,
+) -> Result<Vec<u8>>
+where
+    S: Signer<Sig> + SignatureAlgorithm,
+    Sig: SignatureEncoding,
+{
+    let algorithm = signer.algorithm();
+    let oid = match algorithm {
+        Algorithm::ES256 => ECDSA_WITH_SHA256,
+        Algorithm::ES384 => ECDSA_WITH_SHA384,
+        Algorithm::ES512 => ECDSA_WITH_SHA512,
+        _ => return Err(anyhow!("unsupported signature algorithm: {:?}", algorithm)),
+    };
+    let signature_algorithm = AlgorithmIdentifierOwned {
+        oid,
+        parameters: None,
+    };
+
+    // A positive serial number with 127 bits of entropy, so that it is non-sequential.
+    let mut serial_number: [u8; 16] = rand::thread_rng().gen();
+    serial_number[0] = (serial_number[0] & 0x7f) | 0x40;
+
+    let tbs_certificate = TbsCertificate {
+        version: Version::V3,
+        serial_number: SerialNumber::new(&serial_number)?,
+        signature: signature_algorithm.clone(),
+        issuer,
+        validity: Validity {
+            not_before: time(not_before)?,
+            not_after: time(not_after)?,
+        },
+        subject,
+        subject_public_key_info,
+        issuer_unique_id: None,
+        subject_unique_id: None,
+        extensions: Some(extensions),
+    };
+
+    let signature = signer
+        .try_sign(&tbs_certificate.to_der()?)
+        .map_err(|e| anyhow!("error signing certificate: {}", e))?
+        .to_vec();
+    // X.509 requires DER encoded ECDSA signatures, so normalize to r || s before re-encoding.
+    let signature = SignatureFormat::from(algorithm)
+        .normalize(signature)
+        .map_err(|e| anyhow!("invalid signature: {}", e))?;
+    let (r, s) = signature.split_at(signature.len() / 2);
+    let signature = vec![UintRef::new(r)?, UintRef::new(s)?].to_der()?;
+
+    Ok(Certificate {
+        tbs_certificate,
+        signature_algorithm,
+        signature: BitString::from_bytes(&signature)?,
+    }
+    .to_der()?)
+}
+
+fn to_pem(der: &[u8]) -> Result<String> {
+    pem_rfc7468::encode_string("CERTIFICATE", pem_rfc7468::LineEnding::LF, der)
+        .map_err(|e| anyhow!("unable to encode certificate as pem: {}", e))
+}
+
+fn name(
+    country: &str,
+    state_or_province: Option<&str>,
+    organization: Option<&str>,
+    common_name: &str,
+) -> Result<Name> {
+    let attribute = |oid, value| {
+        RelativeDistinguishedName::try_from(vec![AttributeTypeAndValue { oid, value }])
+    };
+    let utf8_string = |value| Any::encode_from(&Utf8StringRef::new(value)?);
+    let mut rdns = vec![attribute(
+        COUNTRY_NAME,
+        Any::encode_from(&PrintableStringRef::new(country)?)?,
+    )?];
+    if let Some(state_or_province) = state_or_province {
+        rdns.push(attribute(
+            STATE_OR_PROVINCE_NAME,
+            utf8_string(state_or_province)?,
+        )?);
+    }
+    if let Some(organization) = organization {
+        rdns.push(attribute(ORGANIZATION_NAME, utf8_string(organization)?)?);
+    }
+    rdns.push(attribute(COMMON_NAME, utf8_string(common_name)?)?);
+    Ok(RdnSequence(rdns))
+}
+
+fn extension<E: AssociatedOid + Encode>(value: &E, critical: bool) -> Result<Extension> {
+    Ok(Extension {
+        extn_id: E::OID,
+        critical,
+        extn_value: OctetString::new(value.to_der()?)?,
+    })
+}
+
+fn general_name(name: &IssuerAlternativeName) -> Result<GeneralName> {
+    Ok(match name {
+        IssuerAlternativeName::Email(email) => GeneralName::Rfc822Name(Ia5String::new(email)?),
+        IssuerAlternativeName::Uri(uri) => {
+            GeneralName::UniformResourceIdentifier(Ia5String::new(uri)?)
+        }
+    })
+}
+
+fn crl_distribution_points(uri: &str) -> Result<CrlDistributionPoints> {
+    Ok(CrlDistributionPoints(vec![DistributionPoint {
+        distribution_point: Some(DistributionPointName::FullName(vec![
+            GeneralName::UniformResourceIdentifier(Ia5String::new(uri)?),
+        ])),
+        reasons: None,
+        crl_issuer: None,
+    }]))
+}
+
+/// Encode a time as UTCTime until 2049, and as GeneralizedTime thereafter, as per RFC 5280.
+fn time(time: OffsetDateTime) -> Result<Time> {
+    let since_epoch = (time - OffsetDateTime::UNIX_EPOCH)
+        .try_into()
+        .map_err(|_| anyhow!("time out of range: {}", time))?;
+    let date_time = DateTime::from_unix_duration(since_epoch)?;
+    Ok(if date_time.year() <= UtcTime::MAX_YEAR {
+        UtcTime::from_date_time(date_time)?.into()
+    } else {
+        GeneralizedTime::from_date_time(date_time).into()
+    })
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::definitions::ValidityInfo;
+    use crate::issuance::mdoc::test::minimal_test_mdoc_builder;
+    use p256::ecdsa::{DerSignature, Signature, SigningKey, VerifyingKey};
+    use signature::Verifier;
+    use time::macros::datetime;
+    use x509_cert::der::Decode;
+
+    fn iaca_key() -> SigningKey {
+        SigningKey::from_slice(&[1; 32]).unwrap()
+    }
+
+    fn ds_key() -> SigningKey {
+        SigningKey::from_slice(&[2; 32]).unwrap()
+    }
+
+    fn iaca() -> Iaca {
+        let key = iaca_key();
+        Iaca::builder()
+            .country("US".to_string())
+            .state_or_province("NY".to_string())
+            .organization("Example DMV".to_string())
+            .common_name("Example IACA".to_string())
+            .issuer_alternative_name(IssuerAlternativeName::Uri(
+                "https://dmv.example.com".to_string(),
+            ))
+            .crl_distribution_point("https://dmv.example.com/crl".to_string())
+            .validity(
+                datetime!(2023-01-01 0:00 UTC),
+                datetime!(2033-01-01 0:00 UTC),
+            )
+            .build::<_, SigningKey, Signature>(key.verifying_key(), key.clone())
+            .unwrap()
+    }
+
+    fn document_signer(iaca: &Iaca) -> DocumentSigner {
+        iaca.document_signer()
+            .common_name("Example Document Signer".to_string())
+            .validity(
+                datetime!(2023-01-01 0:00 UTC),
+                datetime!(2025-01-01 0:00 UTC),
+            )
+            .build::<_, SigningKey, Signature>(ds_key().verifying_key(), iaca_key())
+            .unwrap()
+    }
+
+    fn parse(der: &[u8]) -> Certificate {
+        Certificate::from_der(der).unwrap()
+    }
+
+    fn verify(certificate: &Certificate, key: &VerifyingKey) {
+        let signature = DerSignature::try_from(certificate.signature.raw_bytes()).unwrap();
+        key.verify(&certificate.tbs_certificate.to_der().unwrap(), &signature)
+            .unwrap();
+    }
+
+    /// An ES512 signer, as the P-521 signing key does not declare its COSE algorithm.
+    pub struct P521Signer(pub p521::ecdsa::SigningKey);
+
+    pub fn p521_key() -> p521::ecdsa::SigningKey {
+        let mut bytes = [1; 66];
+        bytes[0] = 0;
+        p521::ecdsa::SigningKey::from_slice(&bytes).unwrap()
+    }
+
+    /// An IACA with a P-521 key, which signs its certificates with ES512.
+    pub fn p521_iaca() -> Iaca {
+        let verifying_key = p521::ecdsa::VerifyingKey::from(&p521_key());
+        let public_key =
+            p521::PublicKey::from_sec1_bytes(verifying_key.to_encoded_point(false).as_bytes())
+                .unwrap();
+        Iaca::builder()
+            .country("US".to_string())
+            .common_name("Example IACA".to_string())
+            .issuer_alternative_name(IssuerAlternativeName::Email("iaca@example.com".to_string()))
+            .crl_distribution_point("https://example.com/crl".to_string())
+            .validity(
+                datetime!(2023-01-01 0:00 UTC),
+                datetime!(2033-01-01 0:00 UTC),
+            )
+            .build(&public_key, P521Signer(p521_key()))
+            .unwrap()
+    }
+
+    impl Signer<p521::ecdsa::Signature> for P521Signer {
+        fn try_sign(&self, msg: &[u8]) -> Result<p521::ecdsa::Signature, signature::Error> {
+            self.0.try_sign(msg)
+        }
+    }
+
+    impl SignatureAlgorithm for P521Signer {
+        fn algorithm(&self) -> Algorithm {
+            Algorithm::ES512
+        }
+    }
+
+    #[test]
+    fn iaca_certificate() {
+        let iaca = iaca();
+        let certificate = parse(iaca.to_der());
+        let tbs = &certificate.tbs_certificate;
+        verify(&certificate, iaca_key().verifying_key());
+
+        assert_eq!(tbs.version, Version::V3);
+        assert_eq!(tbs.issuer, tbs.subject);
+        assert_eq!(
+            tbs.subject.to_string(),
+            "CN=Example IACA,O=Example DMV,ST=NY,C=US"
+        );
+        assert_eq!(certificate.signature_algorithm.oid, ECDSA_WITH_SHA256);
+
+        let (critical, key_usage) = tbs.get::<KeyUsage>().unwrap().unwrap();
+        assert!(critical);
+        assert_eq!(
+            key_usage,
+            KeyUsage(KeyUsages::KeyCertSign | KeyUsages::CRLSign)
+        );
+        let (critical, basic_constraints) = tbs.get::<BasicConstraints>().unwrap().unwrap();
+        assert!(critical);
+        assert_eq!(
+            basic_constraints,
+            BasicConstraints {
+                ca: true,
+                path_len_constraint: Some(0),
+            }
+        );
+        let (_, subject_key_identifier) = tbs.get::<SubjectKeyIdentifier>().unwrap().unwrap();
+        assert_eq!(subject_key_identifier.0.as_bytes(), iaca.key_identifier);
+        let (_, issuer_alt_name) = tbs.get::<IssuerAltName>().unwrap().unwrap();
+        assert_eq!(
+            issuer_alt_name.0,
+            [general_name(&IssuerAlternativeName::Uri(
+                "https://dmv.example.com".to_string()
+            ))
+            .unwrap()]
+        );
+        let (_, crl_distribution_points) = tbs.get::<CrlDistributionPoints>().unwrap().unwrap();
+        assert_eq!(
+            crl_distribution_points,
+            super::crl_distribution_points("https://dmv.example.com/crl").unwrap()
+        );
+        assert!(tbs.get::<ExtendedKeyUsage>().unwrap().is_none());
+
+        assert!(iaca
+            .to_pem()
+            .unwrap()
+            .starts_with("-----BEGIN CERTIFICATE-----"));
+    }
+
+    #[test]
+    fn document_signer_certificate() {
+        let iaca = iaca();
+        let document_signer = document_signer(&iaca);
+        let certificate = parse(document_signer.to_der());
+        let tbs = &certificate.tbs_certificate;
+        verify(&certificate, iaca_key().verifying_key());
+
+        assert_eq!(tbs.issuer, iaca.subject);
+        assert_eq!(tbs.subject.to_string(), "CN=Example Document Signer,C=US");
+
+        let (critical, key_usage) = tbs.get::<KeyUsage>().unwrap().unwrap();
+        assert!(critical);
+        assert_eq!(key_usage, KeyUsage(KeyUsages::DigitalSignature.into()));
+        let (critical, extended_key_usage) = tbs.get::<ExtendedKeyUsage>().unwrap().unwrap();
+        assert!(critical);
+        assert_eq!(extended_key_usage.0, [MDL_DS]);
+        assert_eq!(MDL_DS.to_string(), "1.0.18013.5.1.2");
+        // Authority key identifier matches the subject key identifier of the IACA.
+        let (_, authority_key_identifier) = tbs.get::<AuthorityKeyIdentifier>().unwrap().unwrap();
+        assert_eq!(
+            authority_key_identifier.key_identifier.unwrap().as_bytes(),
+            iaca.key_identifier
+        );
+        assert!(tbs.get::<BasicConstraints>().unwrap().is_none());
+        // The IACA's issuer alternative name is inherited.
+        let (_, issuer_alt_name) = tbs.get::<IssuerAltName>().unwrap().unwrap();
+        assert_eq!(
+            issuer_alt_name.0,
+            [general_name(&IssuerAlternativeName::Uri(
+                "https://dmv.example.com".to_string()
+            ))
+            .unwrap()]
+        );
+
+        let x5chain = document_signer.x5chain().unwrap();
+        assert_eq!(
+            x5chain.end_entity_public_key().unwrap(),
+            ds_key()
+                .verifying_key()
+                .to_encoded_point(false)
+                .as_bytes()
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn es512() {
+        let iaca = p521_iaca();
+        let document_signer = iaca
+            .document_signer()
+            .common_name("Example Document Signer".to_string())
+            .validity(
+                datetime!(2023-01-01 0:00 UTC),
+                datetime!(2025-01-01 0:00 UTC),
+            )
+            .build(ds_key().verifying_key(), P521Signer(p521_key()))
+            .unwrap();
+        let verifying_key = p521::ecdsa::VerifyingKey::from(&p521_key());
+
+        for der in [iaca.to_der(), document_signer.to_der()] {
+            let certificate = parse(der);
+            assert_eq!(certificate.signature_algorithm.oid, ECDSA_WITH_SHA512);
+            let signature =
+                p521::ecdsa::Signature::from_der(certificate.signature.raw_bytes()).unwrap();
+            verifying_key
+                .verify(&certificate.tbs_certificate.to_der().unwrap(), &signature)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn issue_mdoc() {
+        let iaca = iaca();
+        let x5chain = document_signer(&iaca).x5chain().unwrap();
+        let mdoc = minimal_test_mdoc_builder()
+            .validity_info(ValidityInfo {
+                signed: datetime!(2023-06-01 0:00 UTC),
+                valid_from: datetime!(2023-06-01 0:00 UTC),
+                valid_until: datetime!(2024-06-01 0:00 UTC),
+                expected_update: None,
+            })
+            .issue::<SigningKey, Signature>(x5chain, ds_key())
+            .unwrap();
+        mdoc.verify().unwrap();
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let key = iaca_key();
+        let builder = Iaca::builder()
+            .country("usa".to_string())
+            .common_name("Example IACA".to_string())
+            .issuer_alternative_name(IssuerAlternativeName::Email("iaca@example.com".to_string()))
+            .crl_distribution_point("https://example.com/crl".to_string())
+            .validity(
+                datetime!(2023-01-01 0:00 UTC),
+                datetime!(2033-01-01 0:00 UTC),
+            );
+        assert!(builder
+            .clone()
+            .build::<_, SigningKey, Signature>(key.verifying_key(), key.clone())
+            .is_err());
+        assert!(builder
+            .country("US".to_string())
+            .validity(
+                datetime!(2023-01-01 0:00 UTC),
+                datetime!(2053-01-01 0:00 UTC),
+            )
+            .build::<_, SigningKey, Signature>(key.verifying_key(), key.clone())
+            .is_err());
+
+        let iaca = iaca();
+        // Longer than 1187 days.
+        assert!(iaca
+            .document_signer()
+            .common_name("Example Document Signer".to_string())
+            .validity(
+                datetime!(2023-01-01 0:00 UTC),
+                datetime!(2027-01-01 0:00 UTC),
+            )
+            .build::<_, SigningKey, Signature>(ds_key().verifying_key(), iaca_key())
+            .is_err());
+        // Outside of the IACA validity.
+        assert!(iaca
+            .document_signer()
+            .common_name("Example Document Signer".to_string())
+            .validity(
+                datetime!(2022-01-01 0:00 UTC),
+                datetime!(2024-01-01 0:00 UTC),
+            )
+            .build::<_, SigningKey, Signature>(ds_key().verifying_key(), iaca_key())
+            .is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! Re-issuance of an mdoc, for example when the MSO is approaching the `expected_update` date of
+//! its validity information, or when some of its data has changed.
+use crate::{
+    definitions::{status_list::Status, traits::Namespace, DeviceKeyInfo, ValidityInfo},
+    issuance::{
+        decoys::DecoyStrategy,
+        mdoc::{Builder, Mdoc, Namespaces, PreparedMdoc},
+        x5chain::X5Chain,
+    },
+};
+use anyhow::{anyhow, Result};
+use async_signature::AsyncSigner;
+use cose_rs::algorithm::{Algorithm, SignatureAlgorithm};
+use serde_cbor::Value as CborValue;
+use signature::{SignatureEncoding, Signer};
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone)]
+/// The re-issuance of an existing mdoc.
+///
+/// The refreshed mdoc keeps the document type, digest algorithm, device key and status
+/// information of the existing mdoc, and all of its data elements unless they are changed. A new
+/// MSO is generated with new validity information, and every data element receives a fresh salt
+/// and digest ID.
+///
+/// The device key and the status list index are the same in both MSOs, so presentations of the
+/// existing and the refreshed mdoc can be linked to each other unless both are replaced, with
+/// [Refresh::device_key_info] and [Refresh::status].
+pub struct Refresh {
+    previous: Namespaces,
+    namespaces: Namespaces,
+    validity_info: Option<ValidityInfo>,
+    builder: Builder,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The data elements which differ between two versions of an mdoc, identified by namespace and
+/// element identifier.
+pub struct Changes {
+    pub added: BTreeSet<(String, String)>,
+    pub modified: BTreeSet<(String, String)>,
+    pub removed: BTreeSet<(String, String)>,
+}
+
+impl Refresh {
+    pub(super) fn new(mdoc: &Mdoc) -> Self {
+        let previous = mdoc.elements();
+        let mut builder = Mdoc::builder()
+            .doc_type(mdoc.doc_type.clone())
+            .digest_algorithm(mdoc.mso.digest_algorithm)
+            .device_key_info(mdoc.mso.device_key_info.clone());
+        if let Some(status) = &mdoc.mso.status {
+            builder = builder.status(status.clone());
+        }
+        Self {
+            namespaces: previous.clone(),
+            previous,
+            validity_info: None,
+            builder,
+        }
+    }
+
+    /// Set the validity information of the refreshed mdoc.
+    pub fn validity_info(mut self, validity_info: ValidityInfo) -> Self {
+        self.validity_info = Some(validity_info);
+        self
+    }
+
+    /// Keep only the given namespaces, dropping the others from the refreshed mdoc.
+    pub fn retain_namespaces(mut self, namespaces: &[&str]) -> Self {
+        self.namespaces
+            .retain(|namespace, _| namespaces.contains(&namespace.as_str()));
+        self
+    }
+
+    /// Drop a namespace from the refreshed mdoc.
+    pub fn remove_namespace(mut self, namespace: &str) -> Self {
+        self.namespaces.remove(namespace);
+        self
+    }
+
+    /// Replace all of the data elements of a namespace from its typed representation.
+    pub fn namespace<N: Namespace>(mut self, elements: N) -> Self {
+        self.namespaces
+            .insert(N::NAMESPACE.to_string(), elements.to_ns_map());
+        self
+    }
+
+    /// Add or update a single data element.
+    pub fn set_element(
+        mut self,
+        namespace: String,
+        element_identifier: String,
+        element_value: CborValue,
+    ) -> Self {
+        self.namespaces
+            .entry(namespace)
+            .or_default()
+            .insert(element_identifier, element_value);
+        self
+    }
+
+    /// Remove a single data element.
+    pub fn remove_element(mut self, namespace: &str, element_identifier: &str) -> Self {
+        if let Some(elements) = self.namespaces.get_mut(namespace) {
+            elements.remove(element_identifier);
+        }
+        self
+    }
+
+    /// Bind the refreshed mdoc to a new device key instead of the device key of the existing mdoc.
+    pub fn device_key_info(mut self, device_key_info: DeviceKeyInfo) -> Self {
+        self.builder = self.builder.device_key_info(device_key_info);
+        self
+    }
+
+    /// Replace the status information of the existing mdoc, for example with a newly allocated
+    /// index in the status list.
+    pub fn status(mut self, status: Status) -> Self {
+        self.builder = self.builder.status(status);
+        self
+    }
+
+    /// Set the strategy for adding decoy digests to the new MSO.
+    pub fn decoy_strategy(mut self, decoy_strategy: DecoyStrategy) -> Self {
+        self.builder = self.builder.decoy_strategy(decoy_strategy);
+        self
+    }
+
+    /// The data elements which will differ between the existing and the refreshed mdoc.
+    pub fn changes(&self) -> Changes {
+        Changes::between(&self.previous, &self.namespaces)
+    }
+
+    /// Prepare the refreshed mdoc for remote signing.
+    pub fn prepare(self, signature_algorithm: Algorithm) -> Result<PreparedMdoc> {
+        self.into_builder()?.prepare(signature_algorithm)
+    }
+
+    /// Directly issue the refreshed mdoc.
+    pub fn issue<S, Sig>(self, x5chain: X5Chain, signer: S) -> Result<Mdoc>
+    where
+        S: Signer<Sig> + SignatureAlgorithm,
+        Sig: SignatureEncoding,
+    {
+        self.into_builder()?.issue(x5chain, signer)
+    }
+
+    /// Directly issue the refreshed mdoc.
+    pub async fn issue_async<S, Sig>(self, x5chain: X5Chain, signer: S) -> Result<Mdoc>
+    where
+        S: AsyncSigner<Sig> + SignatureAlgorithm,
+        Sig: SignatureEncoding + Send + 'static,
+    {
+        self.into_builder()?.issue_async(x5chain, signer).await
+    }
+
+    fn into_builder(self) -> Result<Builder> {
+        let validity_info = self
+            .validity_info
+            .ok_or_else(|| anyhow!("missing parameter: 'validity_info'"))?;
+        let mut namespaces = self.namespaces;
+        namespaces.retain(|_, elements| !elements.is_empty());
+        Ok(self
+            .builder
+            .namespaces(namespaces)
+            .validity_info(validity_info))
+    }
+}
+
+impl Changes {
+    /// Compare the data elements of two versions of an mdoc.
+    pub fn between(previous: &Namespaces, current: &Namespaces) -> Self {
+        let mut changes = Self::default();
+        for (namespace, elements) in current {
+            for (element_identifier, value) in elements {
+                let key = (namespace.clone(), element_identifier.clone());
+                match previous
+                    .get(namespace)
+                    .and_then(|elements| elements.get(element_identifier))
+                {
+                    None => changes.added.insert(key),
+                    Some(previous_value) if previous_value != value => changes.modified.insert(key),
+                    Some(_) => false,
+                };
+            }
+        }
+        for (namespace, elements) in previous {
+            for element_identifier in elements.keys() {
+                if !current
+                    .get(namespace)
+                    .is_some_and(|elements| elements.contains_key(element_identifier))
+                {
+                    changes
+                        .removed
+                        .insert((namespace.clone(), element_identifier.clone()));
+                }
+            }
+        }
+        changes
+    }
+
+    /// Whether both versions have the same data elements.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::namespaces::{
+        org_iso_18013_5_1::OrgIso1801351, org_iso_18013_5_1_aamva::OrgIso1801351Aamva,
+    };
+    use crate::definitions::{
+        device_key::cose_key::{CoseKey, EC2Curve, EC2Y},
+        helpers::Tag24,
+        Mso,
+    };
+    use crate::issuance::mdoc::test::minimal_test_mdoc;
+    use p256::ecdsa::{Signature, SigningKey};
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+    use p256::pkcs8::DecodePrivateKey;
+    use p256::SecretKey;
+    use std::collections::HashSet;
+    use time::{Duration, OffsetDateTime};
+
+    static ISSUER_CERT: &[u8] = include_bytes!("../../test/issuance/issuer-cert.pem");
+    static ISSUER_KEY: &str = include_str!("../../test/issuance/issuer-key.pem");
+
+    fn issue(refresh: Refresh) -> Mdoc {
+        let x5chain = X5Chain::builder()
+            .with_pem(ISSUER_CERT)
+            .unwrap()
+            .build()
+            .unwrap();
+        let signer: SigningKey = SecretKey::from_pkcs8_pem(ISSUER_KEY).unwrap().into();
+        refresh
+            .issue::<SigningKey, Signature>(x5chain, signer)
+            .unwrap()
+    }
+
+    fn validity_info() -> ValidityInfo {
+        let now = OffsetDateTime::now_utc();
+        ValidityInfo {
+            signed: now,
+            valid_from: now,
+            valid_until: now + Duration::days(365),
+            expected_update: Some(now + Duration::days(180)),
+        }
+    }
+
+    #[test]
+    fn refresh() {
+        let mdoc = minimal_test_mdoc().unwrap();
+        let refresh = mdoc
+            .refresh()
+            .validity_info(validity_info())
+            .set_element(
+                OrgIso1801351::NAMESPACE.to_string(),
+                "resident_address".to_string(),
+                CborValue::Text("1 Main Street".to_string()),
+            )
+            .remove_element(OrgIso1801351::NAMESPACE, "weight")
+            .retain_namespaces(&[OrgIso1801351::NAMESPACE]);
+
+        let changes = refresh.changes();
+        let refreshed = issue(refresh);
+
+        let iso = |element: &str| (OrgIso1801351::NAMESPACE.to_string(), element.to_string());
+        assert!(changes.added.is_empty());
+        assert_eq!(changes.modified, [iso("resident_address")].into());
+        assert!(changes.removed.contains(&iso("weight")));
+        assert!(changes.removed.iter().all(|(namespace, element)| namespace
+            == OrgIso1801351Aamva::NAMESPACE
+            || element == "weight"));
+        assert_eq!(
+            Changes::between(&mdoc.elements(), &refreshed.elements()),
+            changes
+        );
+
+        assert_eq!(
+            refreshed.mso.device_key_info.device_key,
+            mdoc.mso.device_key_info.device_key
+        );
+        let mso: Tag24<Mso> =
+            serde_cbor::from_slice(refreshed.issuer_auth.payload().unwrap()).unwrap();
+        assert!(mso.as_ref().validity_info.expected_update.is_some());
+        assert!(mso.as_ref().validity_info.valid_until > mdoc.mso.validity_info.valid_until);
+
+        let salts = |mdoc: &Mdoc| -> HashSet<Vec<u8>> {
+            mdoc.namespaces
+                .values()
+                .flat_map(|items| items.iter())
+                .map(|item| item.as_ref().random.as_ref().to_vec())
+                .collect()
+        };
+        assert!(salts(&mdoc).is_disjoint(&salts(&refreshed)));
+    }
+
+    #[test]
+    fn unchanged() {
+        let mdoc = minimal_test_mdoc().unwrap();
+        let refresh = mdoc.refresh().validity_info(validity_info());
+        assert!(refresh.changes().is_empty());
+        let refreshed = issue(refresh);
+        assert_eq!(mdoc.elements(), refreshed.elements());
+        assert_ne!(mdoc.mso.value_digests, refreshed.mso.value_digests);
+    }
+
+    #[test]
+    fn new_device_key() {
+        let mdoc = minimal_test_mdoc().unwrap();
+        let ec = SecretKey::random(&mut rand::rngs::OsRng)
+            .public_key()
+            .to_encoded_point(false);
+        let device_key = CoseKey::EC2 {
+            crv: EC2Curve::P256,
+            x: ec.x().unwrap().to_vec(),
+            y: EC2Y::Value(ec.y().unwrap().to_vec()),
+        };
+        let refreshed = issue(
+            mdoc.refresh()
+                .validity_info(validity_info())
+                .device_key_info(DeviceKeyInfo {
+                    device_key: device_key.clone(),
+                    key_authorizations: None,
+                    key_info: None,
+                }),
+        );
+        assert_eq!(refreshed.mso.device_key_info.device_key, device_key);
+        assert_ne!(
+            refreshed.mso.device_key_info.device_key,
+            mdoc.mso.device_key_info.device_key
+        );
+    }
+
+    #[test]
+    fn missing_validity_info() {
+        let mdoc = minimal_test_mdoc().unwrap();
+        assert!(mdoc.refresh().prepare(Algorithm::ES256).is_err());
+    }
+}
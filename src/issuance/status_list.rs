@@ -0,0 +1,359 @@
+//! Issuer-side management and publication of status lists, which allow issued mdocs to be
+//! revoked or suspended.
+use crate::{
+    definitions::{
+        helpers::SignatureFormat,
+        status_list::{
+            Bits, CredentialStatus, Status, StatusList, StatusListInfo, StatusListToken,
+        },
+    },
+    issuance::x5chain::{X5Chain, X5CHAIN_HEADER_LABEL},
+};
+use anyhow::{anyhow, Result};
+use cose_rs::{
+    algorithm::{Algorithm, SignatureAlgorithm},
+    header_map::HeaderMap,
+    sign1::{CoseSign1, PreparedCoseSign1},
+};
+use flate2::{write::ZlibEncoder, Compression};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_cbor::Value as CborValue;
+use signature::{SignatureEncoding, Signer};
+use std::{collections::BTreeSet, io::Write};
+use time::OffsetDateTime;
+
+pub const TYP_HEADER_LABEL: i128 = 16;
+pub const STATUS_LIST_CWT_TYPE: &str = "application/statuslist+cwt";
+
+/// The number of random indices tried when allocating before falling back to enumerating the
+/// free indices.
+const ALLOCATION_ATTEMPTS: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A status list maintained by the issuer, which should be persisted between issuances.
+pub struct StatusListPublisher {
+    uri: String,
+    bits: Bits,
+    statuses: Vec<u8>,
+    allocated: BTreeSet<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A status list token requiring a remotely signed signature to be completed.
+pub struct PreparedStatusListToken {
+    prepared_sig: PreparedCoseSign1,
+    #[serde(default)]
+    signature_format: SignatureFormat,
+}
+
+impl StatusListPublisher {
+    /// Create a status list which will be published at `uri`, with room for `capacity` mdocs.
+    ///
+    /// Every mdoc in the list is indistinguishable from the others, so a larger capacity gives
+    /// holders better privacy.
+    pub fn new(uri: String, bits: Bits, capacity: usize) -> Self {
+        Self {
+            uri,
+            bits,
+            statuses: vec![0; capacity],
+            allocated: BTreeSet::new(),
+        }
+    }
+
+    /// The number of mdocs that the list has room for.
+    pub fn capacity(&self) -> usize {
+        self.statuses.len()
+    }
+
+    /// Allocate an unused index for a new mdoc, returning the status information to be carried in
+    /// its MSO.
+    ///
+    /// Indices are allocated at random so that they do not reveal the order of issuance.
+    pub fn allocate(&mut self) -> Result<Status> {
+        let capacity = self.capacity() as u64;
+        let free = capacity.saturating_sub(self.allocated.len() as u64);
+        if free == 0 {
+            return Err(anyhow!("status list is full"));
+        }
+
+        // Rejection sampling takes a constant number of attempts on average unless the list is
+        // nearly full, in which case the free indices are enumerated instead.
+        let mut rng = rand::thread_rng();
+        let idx = match (0..ALLOCATION_ATTEMPTS)
+            .map(|_| rng.gen_range(0..capacity))
+            .find(|idx| !self.allocated.contains(idx))
+        {
+            Some(idx) => idx,
+            None => {
+                let nth = rng.gen_range(0..free) as usize;
+                (0..capacity)
+                    .filter(|idx| !self.allocated.contains(idx))
+                    .nth(nth)
+                    .ok_or_else(|| anyhow!("status list is full"))?
+            }
+        };
+        self.allocated.insert(idx);
+
+        Ok(Status {
+            status_list: Some(StatusListInfo {
+                idx,
+                uri: self.uri.clone(),
+            }),
+            identifier_list: None,
+        })
+    }
+
+    /// Update the status of the mdoc at the given index.
+    pub fn set(&mut self, idx: u64, status: CredentialStatus) -> Result<()> {
+        let value = u8::from(status);
+        if u16::from(value) >= 1u16 << u8::from(self.bits) {
+            return Err(anyhow!(
+                "status {value} cannot be represented with {} bits",
+                u8::from(self.bits)
+            ));
+        }
+        if !self.allocated.contains(&idx) {
+            return Err(anyhow!("index {idx} has not been allocated"));
+        }
+        self.statuses[idx as usize] = value;
+        Ok(())
+    }
+
+    /// Retrieve the status of the mdoc at the given index.
+    pub fn get(&self, idx: u64) -> Option<CredentialStatus> {
+        usize::try_from(idx)
+            .ok()
+            .and_then(|idx| self.statuses.get(idx))
+            .map(|status| CredentialStatus::from(*status))
+    }
+
+    /// Pack and compress the current statuses.
+    pub fn status_list(&self) -> Result<StatusList> {
+        let bits = u8::from(self.bits);
+        let per_byte = (8 / bits) as usize;
+        let packed: Vec<u8> = self
+            .statuses
+            .chunks(per_byte)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (i, status)| byte | status << (i as u8 * bits))
+            })
+            .collect();
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&packed)?;
+        let lst = encoder.finish()?.into();
+
+        Ok(StatusList {
+            bits: self.bits,
+            lst,
+        })
+    }
+
+    /// Build the payload of a status list token for the current statuses.
+    pub fn token(
+        &self,
+        iat: OffsetDateTime,
+        exp: Option<OffsetDateTime>,
+        ttl: Option<u64>,
+    ) -> Result<StatusListToken> {
+        Ok(StatusListToken {
+            sub: self.uri.clone(),
+            iat: iat.unix_timestamp(),
+            exp: exp.map(OffsetDateTime::unix_timestamp),
+            ttl,
+            status_list: self.status_list()?,
+        })
+    }
+
+    /// Prepare a status list token for remote signing.
+    pub fn prepare(
+        &self,
+        iat: OffsetDateTime,
+        exp: Option<OffsetDateTime>,
+        ttl: Option<u64>,
+        signature_algorithm: Algorithm,
+    ) -> Result<PreparedStatusListToken> {
+        let token = self.token(iat, exp, ttl)?;
+        let payload = serde_cbor::to_vec(&CborValue::from(token))?;
+
+        // The type must be covered by the signature, so it goes in the protected header.
+        let mut protected = HeaderMap::default();
+        protected.insert_i(
+            TYP_HEADER_LABEL,
+            CborValue::Text(STATUS_LIST_CWT_TYPE.to_string()),
+        );
+
+        let prepared_sig = CoseSign1::builder()
+            .protected(protected)
+            .payload(payload)
+            .signature_algorithm(signature_algorithm)
+            .prepare()
+            .map_err(|e| anyhow!("error preparing cosesign1: {}", e))?;
+
+        Ok(PreparedStatusListToken {
+            prepared_sig,
+            signature_format: signature_algorithm.into(),
+        })
+    }
+
+    /// Directly sign a status list token.
+    pub fn sign<S, Sig>(
+        &self,
+        iat: OffsetDateTime,
+        exp: Option<OffsetDateTime>,
+        ttl: Option<u64>,
+        x5chain: X5Chain,
+        signer: S,
+    ) -> Result<CoseSign1>
+    where
+        S: Signer<Sig> + SignatureAlgorithm,
+        Sig: SignatureEncoding,
+    {
+        let prepared_token = self.prepare(iat, exp, ttl, signer.algorithm())?;
+
+        let signature = signer
+            .try_sign(prepared_token.signature_payload())
+            .map_err(|e| anyhow!("error signing cosesign1: {}", e))?
+            .to_vec();
+
+        prepared_token.complete(x5chain, signature)
+    }
+}
+
+impl PreparedStatusListToken {
+    /// Retrieve the payload for a remote signature.
+    pub fn signature_payload(&self) -> &[u8] {
+        self.prepared_sig.signature_payload()
+    }
+
+    /// Supply the remotely signed signature and x5chain containing the issuing certificate
+    /// to complete the status list token.
+    ///
+    /// ECDSA signatures may be either `r || s` or DER encoded.
+    pub fn complete(self, x5chain: X5Chain, signature: Vec<u8>) -> Result<CoseSign1> {
+        let signature = self
+            .signature_format
+            .normalize(signature)
+            .map_err(|e| anyhow!("invalid signature: {}", e))?;
+        let mut token = self.prepared_sig.finalize(signature);
+        token
+            .unprotected_mut()
+            .insert_i(X5CHAIN_HEADER_LABEL, x5chain.into_cbor());
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use p256::ecdsa::{Signature, SigningKey};
+    use p256::pkcs8::DecodePrivateKey;
+    use p256::SecretKey;
+
+    static ISSUER_CERT: &[u8] = include_bytes!("../../test/issuance/issuer-cert.pem");
+    static ISSUER_KEY: &str = include_str!("../../test/issuance/issuer-key.pem");
+    static URI: &str = "https://example.com/statuslists/1";
+
+    fn idx(status: &Status) -> u64 {
+        status.status_list.as_ref().unwrap().idx
+    }
+
+    #[test]
+    fn allocation() {
+        let mut publisher = StatusListPublisher::new(URI.to_string(), Bits::One, 8);
+        let indices: BTreeSet<u64> = std::iter::repeat_with(|| idx(&publisher.allocate().unwrap()))
+            .take(8)
+            .collect();
+        assert_eq!(indices, (0..8).collect());
+        assert!(publisher.allocate().is_err());
+    }
+
+    #[test]
+    fn allocation_from_large_list() {
+        let mut publisher = StatusListPublisher::new(URI.to_string(), Bits::One, 1 << 20);
+        let indices: BTreeSet<u64> = std::iter::repeat_with(|| idx(&publisher.allocate().unwrap()))
+            .take(10_000)
+            .collect();
+        assert_eq!(indices.len(), 10_000);
+    }
+
+    #[test]
+    fn unrepresentable_status() {
+        let mut publisher = StatusListPublisher::new(URI.to_string(), Bits::One, 8);
+        let status = publisher.allocate().unwrap();
+        assert!(publisher
+            .set(idx(&status), CredentialStatus::Suspended)
+            .is_err());
+        assert!(publisher
+            .set(idx(&status), CredentialStatus::Invalid)
+            .is_ok());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let now = OffsetDateTime::now_utc();
+        for bits in [Bits::One, Bits::Two, Bits::Four, Bits::Eight] {
+            let mut publisher = StatusListPublisher::new(URI.to_string(), bits, 100);
+            let valid = publisher.allocate().unwrap();
+            let invalid = publisher.allocate().unwrap();
+            publisher
+                .set(idx(&invalid), CredentialStatus::Invalid)
+                .unwrap();
+
+            let token = publisher.token(now, None, Some(3600)).unwrap();
+            let token = StatusListToken::try_from(CborValue::from(token)).unwrap();
+
+            assert!(token.status_list.statuses().unwrap().len() >= 100);
+            assert_eq!(token.check(&valid, now).unwrap(), CredentialStatus::Valid);
+            assert_eq!(
+                token.check(&invalid, now).unwrap(),
+                CredentialStatus::Invalid
+            );
+            if bits != Bits::One {
+                publisher
+                    .set(idx(&valid), CredentialStatus::Suspended)
+                    .unwrap();
+                let token = publisher.token(now, None, None).unwrap();
+                assert_eq!(
+                    token.check(&valid, now).unwrap(),
+                    CredentialStatus::Suspended
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn signed_roundtrip() {
+        let mut publisher = StatusListPublisher::new(URI.to_string(), Bits::Two, 16);
+        let status = publisher.allocate().unwrap();
+        publisher
+            .set(idx(&status), CredentialStatus::Suspended)
+            .unwrap();
+
+        let x5chain = X5Chain::builder()
+            .with_pem(ISSUER_CERT)
+            .unwrap()
+            .build()
+            .unwrap();
+        let signer: SigningKey = SecretKey::from_pkcs8_pem(ISSUER_KEY).unwrap().into();
+        let now = OffsetDateTime::now_utc();
+        let cose_sign1 = publisher
+            .sign::<SigningKey, Signature>(now, None, None, x5chain, signer)
+            .unwrap();
+
+        assert_eq!(
+            cose_sign1.protected().get_i(TYP_HEADER_LABEL),
+            Some(&CborValue::Text(STATUS_LIST_CWT_TYPE.to_string()))
+        );
+        assert!(cose_sign1.unprotected().get_i(TYP_HEADER_LABEL).is_none());
+        let token = StatusListToken::from_cose_sign1(&cose_sign1).unwrap();
+        assert_eq!(
+            token.check(&status, now).unwrap(),
+            CredentialStatus::Suspended
+        );
+    }
+}
@@ -41,6 +41,43 @@ impl X5Chain {
             ),
         }
     }
+
+    /// Parse an x5chain from the CBOR encoding used in COSE headers: either a single certificate,
+    /// or an array of certificates beginning with the end-entity certificate.
+    pub fn from_cbor(value: &CborValue) -> Result<Self> {
+        let certs = match value {
+            CborValue::Bytes(cert) => vec![cert],
+            CborValue::Array(certs) => certs
+                .iter()
+                .map(|cert| match cert {
+                    CborValue::Bytes(cert) => Ok(cert),
+                    _ => Err(anyhow!("x5chain certificates must be byte strings")),
+                })
+                .collect::<Result<_>>()?,
+            _ => return Err(anyhow!("x5chain must be a byte string or an array")),
+        };
+        certs
+            .into_iter()
+            .try_fold(X5Chain::builder(), |builder, cert| builder.with_der(cert))?
+            .build()
+    }
+
+    /// The DER encoded certificates, beginning with the end-entity certificate.
+    pub fn certificates(&self) -> impl Iterator<Item = &[u8]> {
+        self.0.iter().map(|x509| x509.bytes.as_slice())
+    }
+
+    /// The public key of the end-entity certificate, as encoded in its subject public key info.
+    pub fn end_entity_public_key(&self) -> Result<Vec<u8>> {
+        let cert = Certificate::from_der(&self.0[0].bytes)
+            .map_err(|e| anyhow!("unable to parse certificate from der encoding: {}", e))?;
+        Ok(cert
+            .tbs_certificate
+            .subject_public_key_info
+            .subject_public_key
+            .raw_bytes()
+            .to_vec())
+    }
 }
 
 #[derive(Default, Debug, Clone)]
@@ -57,7 +94,7 @@ impl Builder {
             .map_err(|e| anyhow!("unable to parse certificate from der: {}", e))?;
         let x509 = X509 {
             bytes: cert
-                .to_vec()
+                .to_der()
                 .map_err(|e| anyhow!("unable to convert certificate to bytes: {}", e))?,
         };
         self.certs.push(x509);
@@ -68,7 +105,7 @@ impl Builder {
             .map_err(|e| anyhow!("unable to parse certificate from der encoding: {}", e))?;
         let x509 = X509 {
             bytes: cert
-                .to_vec()
+                .to_der()
                 .map_err(|e| anyhow!("unable to convert certificate to bytes: {}", e))?,
         };
         self.certs.push(x509);
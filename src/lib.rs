@@ -1,6 +1,7 @@
 pub use cose_rs;
 
 pub mod definitions;
+pub mod inspect;
 pub mod issuance;
 pub mod presentation;
 
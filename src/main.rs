@@ -1,9 +1,45 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
 
-use anyhow::{Context, Error};
+use anyhow::{anyhow, bail, Context, Error};
 use clap::Parser;
 use clap_stdin::MaybeStdin;
-use isomdl::presentation::{device::Document, Stringify};
+use cose_rs::algorithm::Algorithm;
+use isomdl::{
+    definitions::{
+        device_request,
+        helpers::NonEmptyMap,
+        helpers::Tag24,
+        issuer_signed::IssuerSigned,
+        namespaces::{
+            eu_europa_ec_eudi_pid_1::EuEuropaEcEudiPid1, org_iso_18013_5_1::OrgIso1801351,
+            org_iso_18013_5_1_aamva::OrgIso1801351Aamva, org_iso_23220_1::OrgIso232201,
+            org_iso_23220_photoid_1::OrgIso23220Photoid1, org_iso_7367_1::OrgIso73671,
+            org_micov_attestation_1::OrgMicovAttestation1, org_micov_vtr_1::OrgMicovVtr1,
+            registry::Registry,
+        },
+        session::{Handover, SessionTranscript180135},
+        traits::{FromJson, Namespace},
+        CoseKey, DeviceEngagement, DeviceKeyInfo, DeviceResponse, DigestAlgorithm,
+        KeyAuthorizations, SessionEstablishment, ValidityInfo,
+    },
+    inspect::{self, Inspection, SessionKeys, Structure},
+    issuance::{Mdoc, X5Chain},
+    presentation::{
+        device::{self, Document},
+        reader,
+        verification::{self, TrustStore},
+        Stringify,
+    },
+};
+use p256::pkcs8::DecodePrivateKey;
+use serde_cbor::Value as CborValue;
+use signature::Signer;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -18,31 +54,1170 @@ enum Action {
     GetNamespaces {
         /// Base64 encoded mDL in the format used in the issuance module of this crate.
         mdl: MaybeStdin<String>,
+        /// Annotate each element with its type, whether it is mandatory and whether it may be
+        /// retained, or null if the element is not in a registered namespace.
+        #[arg(long)]
+        schema: bool,
+    },
+    /// Issue an mDL from JSON claims, signed with the given issuer key and certificate chain.
+    Issue {
+        /// JSON object of claims keyed by namespace, e.g. `{"org.iso.18013.5.1": {...}}`.
+        #[arg(long)]
+        claims: PathBuf,
+        /// Device public key, as a JWK or a CBOR encoded COSE_Key.
+        #[arg(long)]
+        device_key: PathBuf,
+        /// PKCS#8 PEM encoded P-256, P-384 or P-521 issuer private key.
+        #[arg(long)]
+        issuer_key: PathBuf,
+        /// PEM encoded issuer certificate chain, starting with the document signer certificate.
+        #[arg(long)]
+        issuer_cert: PathBuf,
+        /// Start of the validity period (RFC 3339), defaulting to now.
+        #[arg(long, value_parser = parse_datetime)]
+        valid_from: Option<OffsetDateTime>,
+        /// End of the validity period (RFC 3339).
+        #[arg(long, value_parser = parse_datetime)]
+        valid_until: OffsetDateTime,
+        /// Date at which the issuer expects to re-sign the MSO (RFC 3339).
+        #[arg(long, value_parser = parse_datetime)]
+        expected_update: Option<OffsetDateTime>,
+        #[arg(long, default_value = "org.iso.18013.5.1.mDL")]
+        doc_type: String,
+        #[arg(long, value_enum, default_value_t = Format::Stringified)]
+        format: Format,
+        /// File to write the mDL to, instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Present an mDL to a reader over QR engagement, releasing every requested element that the
+    /// mDL holds.
+    ///
+    /// The engagement QR code URI is written as the first line of the output, followed by each
+    /// response as a line of base64 encoded CBOR. Requests are read from the input in the same
+    /// format, until the reader ends the session.
+    Holder {
+        /// Stringified mDL, as output by the `issue` action.
+        #[arg(long)]
+        mdl: PathBuf,
+        /// PEM encoded P-256, P-384, P-521 or Ed25519 device private key, as written by `keygen`.
+        #[arg(long)]
+        device_key: PathBuf,
+        /// File or named pipe to read messages from the reader from, instead of stdin.
+        #[arg(long)]
+        input: Option<PathBuf>,
+        /// File or named pipe to write messages to the reader to, instead of stdout.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Read an mDL from a holder over QR engagement.
+    ///
+    /// The engagement QR code URI is read as the first line of the input, and each request is
+    /// written to the output as a line of base64 encoded CBOR. Responses are read from the input
+    /// in the same format, and the session is ended after the last request.
+    Reader {
+        /// JSON files of the elements to request in each round, e.g.
+        /// `{"org.iso.18013.5.1": {"family_name": false}}`, mapping each element identifier to the
+        /// intent to retain it.
+        #[arg(long, required = true)]
+        request: Vec<PathBuf>,
+        /// File or named pipe to read messages from the holder from, instead of stdin.
+        #[arg(long)]
+        input: Option<PathBuf>,
+        /// File or named pipe to write messages to the holder to, instead of stdout.
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// File to write the received elements to as a JSON array with an entry per request,
+        /// instead of stderr.
+        #[arg(long)]
+        responses: Option<PathBuf>,
     },
+    /// Verify a DeviceResponse or IssuerSigned structure, printing a report of every check.
+    ///
+    /// Exits with an error if any check fails.
+    Verify {
+        /// Hex, base64 or binary encoded DeviceResponse or IssuerSigned, or `-` for stdin.
+        input: PathBuf,
+        /// Hex, base64 or binary encoded SessionTranscript of the presentation, with or without
+        /// the Tag24 wrapping, to verify device authentication.
+        #[arg(long)]
+        session_transcript: Option<PathBuf>,
+        /// Directory of trusted IACA certificates, in PEM or DER encoding.
+        #[arg(long)]
+        trust: Option<PathBuf>,
+        /// Time at which to check validity (RFC 3339), defaulting to now.
+        #[arg(long, value_parser = parse_datetime)]
+        at: Option<OffsetDateTime>,
+    },
+    /// Decode an ISO 18013-5 CBOR structure and print it in diagnostic notation or as JSON.
+    ///
+    /// The structure is detected unless it is given, and the data of session messages is
+    /// decrypted when the session keys are given.
+    Inspect {
+        /// Hex, base64 or binary encoded structure, or an `mdoc:` QR code URI, or `-` for stdin.
+        input: PathBuf,
+        /// Structure to decode the input as, e.g. `DeviceResponse` or `mso`.
+        #[arg(long = "as")]
+        structure: Option<Structure>,
+        /// Hex encoded SKReader, to decrypt messages from the reader.
+        #[arg(long, value_parser = parse_session_key)]
+        sk_reader: Option<[u8; 32]>,
+        /// Hex encoded SKDevice, to decrypt messages from the device.
+        #[arg(long, value_parser = parse_session_key)]
+        sk_device: Option<[u8; 32]>,
+        /// Number of messages previously encrypted with the same session key.
+        #[arg(long, default_value_t = 0)]
+        message_counter: u32,
+        #[arg(long, value_enum, default_value_t = InspectFormat::Diagnostic)]
+        format: InspectFormat,
+    },
+    /// Decrypt a captured session with the ephemeral private key of either the device or the
+    /// reader, printing each message in diagnostic notation or as JSON.
+    DecryptSession {
+        /// The DeviceEngagement, as an `mdoc:` QR code URI or hex, base64 or binary encoded CBOR.
+        #[arg(long)]
+        device_engagement: PathBuf,
+        /// Hex, base64 or binary encoded Handover, defaulting to the QR handover.
+        #[arg(long)]
+        handover: Option<PathBuf>,
+        /// The eDeviceKey or eReaderKey private key, as PEM or a hex encoded scalar.
+        #[arg(long)]
+        ephemeral_key: PathBuf,
+        /// Hex, base64 or binary encoded SessionEstablishment.
+        session_establishment: PathBuf,
+        /// Hex, base64 or binary encoded SessionData messages, in the order they were sent.
+        messages: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t = InspectFormat::Diagnostic)]
+        format: InspectFormat,
+    },
+    /// Generate a device key, writing the private key and the public key as a CBOR encoded
+    /// COSE_Key, suitable for the `issue` action.
+    Keygen {
+        #[arg(long, value_enum, default_value_t = Curve::P256)]
+        curve: Curve,
+        #[arg(long, value_enum, default_value_t = KeyFormat::Pem)]
+        key_format: KeyFormat,
+        /// File to write the private key to, instead of stdout.
+        #[arg(long)]
+        private_key: Option<PathBuf>,
+        /// File to write the CBOR encoded COSE_Key to.
+        #[arg(long)]
+        public_key: PathBuf,
+        /// File to write a CBOR encoded DeviceKeyInfo containing the public key to.
+        #[arg(long)]
+        device_key_info: Option<PathBuf>,
+        /// JSON KeyAuthorizations to include in the DeviceKeyInfo, e.g.
+        /// `{"nameSpaces": ["org.iso.18013.5.1.aamva"], "dataElements": {"org.iso.18013.5.1": ["age_over_21"]}}`.
+        #[arg(long, requires = "device_key_info")]
+        key_authorizations: Option<PathBuf>,
+        /// JSON object of integer labels to values, to include as the key info of the
+        /// DeviceKeyInfo.
+        #[arg(long, requires = "device_key_info")]
+        key_info: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum Format {
+    /// The stringified `Document` used by the presentation module of this crate.
+    Stringified,
+    /// The CBOR encoded `Document`.
+    Cbor,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum InspectFormat {
+    /// CBOR diagnostic notation, annotated with the names of integer labels.
+    Diagnostic,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum Curve {
+    P256,
+    P384,
+    P521,
+    Ed25519,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum KeyFormat {
+    /// PKCS#8 PEM.
+    Pem,
+    Jwk,
+}
+
+struct IssueArgs {
+    claims: PathBuf,
+    device_key: PathBuf,
+    issuer_key: PathBuf,
+    issuer_cert: PathBuf,
+    validity_info: ValidityInfo,
+    doc_type: String,
+    format: Format,
+    output: Option<PathBuf>,
 }
 
 fn main() -> Result<(), Error> {
     match Args::parse().action {
-        Action::GetNamespaces { mdl } => print_namespaces(mdl.to_string()),
+        Action::GetNamespaces { mdl, schema } => print_namespaces(mdl.to_string(), schema),
+        Action::Issue {
+            claims,
+            device_key,
+            issuer_key,
+            issuer_cert,
+            valid_from,
+            valid_until,
+            expected_update,
+            doc_type,
+            format,
+            output,
+        } => {
+            let signed = OffsetDateTime::now_utc();
+            issue(IssueArgs {
+                claims,
+                device_key,
+                issuer_key,
+                issuer_cert,
+                validity_info: ValidityInfo {
+                    signed,
+                    valid_from: valid_from.unwrap_or(signed),
+                    valid_until,
+                    expected_update,
+                },
+                doc_type,
+                format,
+                output,
+            })
+        }
+        // Each side opens its channels in the order the other side needs them, so that both can
+        // be named pipes without deadlocking on open.
+        Action::Holder {
+            mdl,
+            device_key,
+            input,
+            output,
+        } => {
+            let document = Document::parse(fs::read_to_string(mdl).context("could not read mdl")?)
+                .context("could not parse mdl")?;
+            let device_key = read_device_private_key(
+                &fs::read_to_string(device_key).context("could not read device key")?,
+            )?;
+            let output = open_output(output.as_deref())?;
+            let input = open_input(input.as_deref())?;
+            run_holder(document, device_key, input, output)
+        }
+        Action::Reader {
+            request,
+            input,
+            output,
+            responses,
+        } => {
+            let requests = request
+                .iter()
+                .map(|path| {
+                    let request = fs::read(path)
+                        .with_context(|| format!("could not read {}", path.display()))?;
+                    serde_json::from_slice(&request)
+                        .with_context(|| format!("could not parse {}", path.display()))
+                })
+                .collect::<Result<Vec<device_request::Namespaces>, Error>>()?;
+            let input = open_input(input.as_deref())?;
+            let output = open_output(output.as_deref())?;
+            let received = run_reader(requests, input, output)?;
+            let received = serde_json::to_string_pretty(&received)?;
+            match responses {
+                Some(path) => fs::write(path, received).context("could not write responses")?,
+                None => eprintln!("{received}"),
+            }
+            Ok(())
+        }
+        Action::Verify {
+            input,
+            session_transcript,
+            trust,
+            at,
+        } => verify(
+            &input,
+            session_transcript.as_deref(),
+            trust.as_deref(),
+            at.unwrap_or_else(OffsetDateTime::now_utc),
+        ),
+        Action::Inspect {
+            input,
+            structure,
+            sk_reader,
+            sk_device,
+            message_counter,
+            format,
+        } => {
+            let keys = SessionKeys {
+                sk_reader,
+                sk_device,
+                message_counter,
+            };
+            let inspection = inspect_input(read_input(&input)?, structure, &keys)?;
+            match format {
+                InspectFormat::Diagnostic => println!("{}", inspection.to_diagnostic()),
+                InspectFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&inspection.to_json())?)
+                }
+            }
+            Ok(())
+        }
+        Action::DecryptSession {
+            device_engagement,
+            handover,
+            ephemeral_key,
+            session_establishment,
+            messages,
+            format,
+        } => {
+            let device_engagement = read_device_engagement(read_input(&device_engagement)?)?;
+            let handover = handover
+                .map(|path| {
+                    serde_cbor::from_slice(&decode_binary(read_input(&path)?))
+                        .context("could not parse handover")
+                })
+                .transpose()?
+                .unwrap_or(Handover::QR);
+            let ephemeral_key = read_ephemeral_key(read_input(&ephemeral_key)?)?;
+            let session_establishment = decode_binary(read_input(&session_establishment)?);
+            let messages = messages
+                .iter()
+                .map(|path| read_input(path).map(decode_binary))
+                .collect::<Result<Vec<_>, _>>()?;
+            let session = inspect::decrypt_session(
+                device_engagement,
+                handover,
+                &ephemeral_key,
+                &session_establishment,
+                &messages,
+            )?;
+            match format {
+                InspectFormat::Diagnostic => println!("{}", session.to_diagnostic()),
+                InspectFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&session.to_json())?)
+                }
+            }
+            Ok(())
+        }
+        Action::Keygen {
+            curve,
+            key_format,
+            private_key,
+            public_key,
+            device_key_info,
+            key_authorizations,
+            key_info,
+        } => {
+            let (pem, jwk) = generate_key(curve)?;
+            let cose_key = CoseKey::try_from(jwk.clone()).context("unsupported device key")?;
+
+            let private = match key_format {
+                KeyFormat::Pem => pem,
+                KeyFormat::Jwk => serde_json::to_string_pretty(&jwk)?,
+            };
+            match private_key {
+                Some(path) => fs::write(path, private).context("could not write private key")?,
+                None => println!("{private}"),
+            }
+
+            if let Some(path) = device_key_info {
+                let key_authorizations = key_authorizations
+                    .map(|path| -> Result<KeyAuthorizations, Error> {
+                        let key_authorizations: KeyAuthorizations = serde_json::from_slice(
+                            &fs::read(path).context("could not read key authorizations")?,
+                        )
+                        .context("could not parse key authorizations")?;
+                        key_authorizations.validate()?;
+                        Ok(key_authorizations)
+                    })
+                    .transpose()?;
+                let key_info = key_info
+                    .map(|path| {
+                        serde_json::from_slice(&fs::read(path).context("could not read key info")?)
+                            .context("could not parse key info")
+                    })
+                    .transpose()?;
+                let device_key_info = DeviceKeyInfo {
+                    device_key: cose_key.clone(),
+                    key_authorizations,
+                    key_info,
+                };
+                fs::write(path, serde_cbor::to_vec(&device_key_info)?)
+                    .context("could not write device key info")?;
+            }
+            fs::write(public_key, serde_cbor::to_vec(&cose_key)?)
+                .context("could not write public key")?;
+            Ok(())
+        }
+    }
+}
+
+/// Generate a private key, returning it in PKCS#8 PEM and JWK encodings.
+/// The RFC 8410 OneAsymmetricKey encoding of an Ed25519 private key, up to the 32 byte seed: the
+/// id-Ed25519 algorithm followed by the seed as an octet string.
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+fn generate_key(curve: Curve) -> Result<(String, ssi_jwk::JWK), Error> {
+    match curve {
+        Curve::P256 => generate_ec_key::<p256::NistP256>("P-256"),
+        Curve::P384 => generate_ec_key::<p384::NistP384>("P-384"),
+        Curve::P521 => generate_ec_key::<p521::NistP521>("P-521"),
+        Curve::Ed25519 => {
+            let jwk = ssi_jwk::JWK::generate_ed25519().map_err(|e| anyhow!(e))?;
+            let seed = match &jwk.params {
+                ssi_jwk::Params::OKP(ssi_jwk::OctetParams {
+                    private_key: Some(d),
+                    ..
+                }) => d.0.clone(),
+                _ => bail!("generated key is not an Ed25519 private key"),
+            };
+            let mut der = ED25519_PKCS8_PREFIX.to_vec();
+            der.extend_from_slice(&seed);
+            let pem = pem_rfc7468::encode_string("PRIVATE KEY", pem_rfc7468::LineEnding::LF, &der)
+                .map_err(|e| anyhow!("could not encode private key: {e}"))?;
+            Ok((pem, jwk))
+        }
+    }
+}
+
+fn generate_ec_key<C>(crv: &str) -> Result<(String, ssi_jwk::JWK), Error>
+where
+    C: elliptic_curve::CurveArithmetic + elliptic_curve::pkcs8::AssociatedOid,
+    elliptic_curve::AffinePoint<C>:
+        elliptic_curve::sec1::FromEncodedPoint<C> + elliptic_curve::sec1::ToEncodedPoint<C>,
+    elliptic_curve::FieldBytesSize<C>: elliptic_curve::sec1::ModulusSize,
+{
+    use elliptic_curve::{pkcs8::EncodePrivateKey, sec1::ToEncodedPoint};
+
+    let secret_key = elliptic_curve::SecretKey::<C>::random(&mut rand::thread_rng());
+    let pem = secret_key
+        .to_pkcs8_pem(Default::default())
+        .map_err(|e| anyhow!("could not encode private key: {e}"))?;
+    let point = secret_key.public_key().to_encoded_point(false);
+    let coordinate =
+        |c: Option<&elliptic_curve::FieldBytes<C>>| c.map(|c| ssi_jwk::Base64urlUInt(c.to_vec()));
+    let jwk = ssi_jwk::JWK::from(ssi_jwk::Params::EC(ssi_jwk::ECParams {
+        curve: Some(crv.to_string()),
+        x_coordinate: coordinate(point.x()),
+        y_coordinate: coordinate(point.y()),
+        ecc_private_key: Some(ssi_jwk::Base64urlUInt(secret_key.to_bytes().to_vec())),
+    }));
+    Ok((pem.to_string(), jwk))
+}
+
+fn inspect_input(
+    input: Vec<u8>,
+    structure: Option<Structure>,
+    keys: &SessionKeys,
+) -> Result<Inspection, Error> {
+    if let Some(qr_code_uri) = std::str::from_utf8(&input)
+        .ok()
+        .map(str::trim)
+        .filter(|text| text.starts_with("mdoc:"))
+    {
+        return inspect::inspect_qr_code_uri(qr_code_uri);
+    }
+    inspect::inspect(&decode_binary(input), structure, keys)
+}
+
+fn read_device_engagement(input: Vec<u8>) -> Result<Tag24<DeviceEngagement>, Error> {
+    if let Some(qr_code_uri) = std::str::from_utf8(&input)
+        .ok()
+        .map(str::trim)
+        .filter(|text| text.starts_with("mdoc:"))
+    {
+        return Tag24::from_qr_code_uri(qr_code_uri).context("could not parse qr code uri");
+    }
+    let bytes = decode_binary(input);
+    // The engagement may or may not be wrapped in its Tag24, which is kept as given.
+    serde_cbor::from_slice::<Tag24<DeviceEngagement>>(&bytes)
+        .or_else(|_| Tag24::from_bytes(bytes))
+        .context("could not parse device engagement")
+}
+
+/// Read a P-256 private key from PEM, or from a hex encoded scalar.
+fn read_ephemeral_key(input: Vec<u8>) -> Result<p256::SecretKey, Error> {
+    match std::str::from_utf8(&input) {
+        Ok(pem) if pem.contains("-----BEGIN") => read_p256_private_key(pem),
+        _ => p256::SecretKey::from_slice(&decode_binary(input))
+            .context("ephemeral key must be PEM or a hex encoded P-256 scalar"),
     }
 }
 
-fn print_namespaces(mdl: String) -> Result<(), Error> {
-    let claims = Document::parse(mdl)
+/// Read a file, or stdin if the path is `-`.
+fn read_input(path: &Path) -> Result<Vec<u8>, Error> {
+    if path == Path::new("-") {
+        let mut input = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut input)?;
+        Ok(input)
+    } else {
+        fs::read(path).with_context(|| format!("could not read {}", path.display()))
+    }
+}
+
+fn parse_session_key(s: &str) -> Result<[u8; 32], Error> {
+    hex::decode(s.trim())?
+        .try_into()
+        .map_err(|_| anyhow!("session key must be 32 bytes"))
+}
+
+fn verify(
+    input: &Path,
+    session_transcript: Option<&Path>,
+    trust: Option<&Path>,
+    at: OffsetDateTime,
+) -> Result<(), Error> {
+    let input = decode_binary(read_input(input)?);
+
+    let session_transcript = session_transcript
+        .map(|path| -> Result<SessionTranscript180135, Error> {
+            let bytes = decode_binary(fs::read(path).context("could not read session transcript")?);
+            serde_cbor::from_slice::<Tag24<SessionTranscript180135>>(&bytes)
+                .map(Tag24::into_inner)
+                .or_else(|_| serde_cbor::from_slice(&bytes))
+                .context("could not parse session transcript")
+        })
+        .transpose()?;
+
+    let mut trust_store = TrustStore::default();
+    if let Some(trust) = trust {
+        for entry in fs::read_dir(trust).context("could not read trust directory")? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let bytes = fs::read(&path)?;
+            trust_store = match std::str::from_utf8(&bytes) {
+                Ok(pem) if pem.contains("-----BEGIN") => pem_blocks(pem)
+                    .try_fold(trust_store, |trust_store, block| {
+                        trust_store.with_pem(block.as_bytes())
+                    }),
+                _ => trust_store.with_der(&bytes),
+            }
+            .with_context(|| format!("could not parse {}", path.display()))?;
+        }
+    }
+
+    let (report, valid) = if let Ok(response) = serde_cbor::from_slice::<DeviceResponse>(&input) {
+        let report = verification::verify_device_response(
+            &response,
+            session_transcript.as_ref(),
+            &trust_store,
+            at,
+        );
+        (serde_json::to_string_pretty(&report)?, report.is_valid())
+    } else if let Ok(issuer_signed) = serde_cbor::from_slice::<IssuerSigned>(&input) {
+        let report = verification::verify_issuer_signed(&issuer_signed, &trust_store, at);
+        (serde_json::to_string_pretty(&report)?, report.is_valid())
+    } else {
+        bail!("input is neither a DeviceResponse nor an IssuerSigned structure")
+    };
+    println!("{report}");
+    if !valid {
+        bail!("verification failed");
+    }
+    Ok(())
+}
+
+/// Decode hex or base64 encoded input, or return it unchanged if it is neither.
+fn decode_binary(input: Vec<u8>) -> Vec<u8> {
+    let text = match std::str::from_utf8(&input) {
+        Ok(text) => text.split_whitespace().collect::<String>(),
+        Err(_) => return input,
+    };
+    hex::decode(&text)
+        .or_else(|_| base64::decode(&text))
+        .or_else(|_| base64::decode_config(&text, base64::URL_SAFE_NO_PAD))
+        .unwrap_or(input)
+}
+
+fn open_input(path: Option<&Path>) -> Result<Box<dyn BufRead>, Error> {
+    Ok(match path {
+        Some(path) => Box::new(BufReader::new(
+            fs::File::open(path).with_context(|| format!("could not open {}", path.display()))?,
+        )),
+        None => Box::new(std::io::stdin().lock()),
+    })
+}
+
+fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>, Error> {
+    Ok(match path {
+        Some(path) => Box::new(
+            fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+                .with_context(|| format!("could not open {}", path.display()))?,
+        ),
+        None => Box::new(std::io::stdout()),
+    })
+}
+
+fn parse_datetime(s: &str) -> Result<OffsetDateTime, time::error::Parse> {
+    OffsetDateTime::parse(s, &Rfc3339)
+}
+
+fn print_namespaces(mdl: String, schema: bool) -> Result<(), Error> {
+    let namespaces = Document::parse(mdl)
         .context("could not parse mdl")?
         .namespaces
         .into_inner()
         .into_iter()
         .map(|(ns, inner)| (ns, inner.into_inner().into_keys().collect()))
         .collect::<BTreeMap<String, Vec<String>>>();
-    println!("{}", serde_json::to_string_pretty(&claims)?);
+    if !schema {
+        println!("{}", serde_json::to_string_pretty(&namespaces)?);
+        return Ok(());
+    }
+
+    let registry = Registry::default();
+    let annotated = namespaces
+        .into_iter()
+        .map(|(ns, elements)| {
+            let schema = registry.namespace(&ns);
+            let elements = elements
+                .into_iter()
+                .map(|element| {
+                    let element_schema = schema.and_then(|s| s.get(&element)).copied();
+                    (element, element_schema)
+                })
+                .collect::<BTreeMap<_, _>>();
+            (ns, elements)
+        })
+        .collect::<BTreeMap<_, _>>();
+    println!("{}", serde_json::to_string_pretty(&annotated)?);
+    Ok(())
+}
+
+fn issue(args: IssueArgs) -> Result<(), Error> {
+    let claims: serde_json::Value =
+        serde_json::from_slice(&fs::read(&args.claims).context("could not read claims")?)
+            .context("could not parse claims")?;
+    let claims = claims
+        .as_object()
+        .ok_or_else(|| anyhow!("claims must be a JSON object keyed by namespace"))?;
+
+    macro_rules! parse_namespace {
+        ($builder:expr, $namespace:expr, $elements:expr, [$($ns:ident),*]) => {
+            match $namespace.as_str() {
+                $($ns::NAMESPACE => $builder.namespace(
+                    $ns::from_json($elements)
+                        .with_context(|| format!("invalid claims for {}", $namespace))?,
+                ),)*
+                _ => bail!("unsupported namespace: {}", $namespace),
+            }
+        };
+    }
+
+    // The builder validates the claims against the default registry before signing.
+    let mut builder = Mdoc::builder();
+    for (namespace, elements) in claims {
+        builder = parse_namespace!(
+            builder,
+            namespace,
+            elements,
+            [
+                OrgIso1801351,
+                OrgIso1801351Aamva,
+                EuEuropaEcEudiPid1,
+                OrgIso73671,
+                OrgMicovVtr1,
+                OrgMicovAttestation1,
+                OrgIso232201,
+                OrgIso23220Photoid1
+            ]
+        );
+    }
+
+    let device_key =
+        read_device_key(&fs::read(&args.device_key).context("could not read device key")?)?;
+    let builder = builder
+        .doc_type(args.doc_type)
+        .validity_info(args.validity_info)
+        .digest_algorithm(DigestAlgorithm::SHA256)
+        .device_key_info(DeviceKeyInfo {
+            device_key,
+            key_authorizations: None,
+            key_info: None,
+        });
+
+    let x5chain =
+        read_x5chain(&fs::read(&args.issuer_cert).context("could not read issuer certificate")?)?;
+    let issuer_key = fs::read_to_string(&args.issuer_key).context("could not read issuer key")?;
+    let mdoc = if let Ok(key) = p256::SecretKey::from_pkcs8_pem(&issuer_key) {
+        builder.issue::<p256::ecdsa::SigningKey, p256::ecdsa::Signature>(x5chain, key.into())
+    } else if let Ok(key) = p384::SecretKey::from_pkcs8_pem(&issuer_key) {
+        builder.issue::<p384::ecdsa::SigningKey, p384::ecdsa::Signature>(x5chain, key.into())
+    } else if let Ok(key) = p521::SecretKey::from_pkcs8_pem(&issuer_key) {
+        // The P-521 signing key does not declare its COSE algorithm, so sign the prepared mdoc.
+        let key = p521::ecdsa::SigningKey::from_slice(&key.to_bytes())
+            .map_err(|e| anyhow!("invalid P-521 issuer key: {e}"))?;
+        builder.prepare(Algorithm::ES512).and_then(|prepared| {
+            let signature: p521::ecdsa::Signature = key.sign(prepared.signature_payload());
+            prepared.complete(x5chain, signature.to_vec())
+        })
+    } else {
+        bail!("issuer key must be a PKCS#8 PEM encoded P-256, P-384 or P-521 private key")
+    }
+    .context("could not issue mdl")?;
+
+    let document = Document::from(mdoc);
+    let bytes = match args.format {
+        Format::Stringified => document.stringify()?.into_bytes(),
+        Format::Cbor => serde_cbor::to_vec(&document)?,
+    };
+    match args.output {
+        Some(path) => fs::write(path, bytes).context("could not write mdl")?,
+        None => match args.format {
+            Format::Stringified => println!("{}", String::from_utf8(bytes)?),
+            Format::Cbor => std::io::Write::write_all(&mut std::io::stdout(), &bytes)?,
+        },
+    }
     Ok(())
 }
 
+fn read_device_key(bytes: &[u8]) -> Result<CoseKey, Error> {
+    if let Ok(jwk) = serde_json::from_slice::<ssi_jwk::JWK>(bytes) {
+        return CoseKey::try_from(jwk).context("unsupported device key");
+    }
+    let cose_key: CborValue = serde_cbor::from_slice(bytes)
+        .context("device key must be a JWK or a CBOR encoded COSE_Key")?;
+    CoseKey::try_from(cose_key).context("unsupported device key")
+}
+
+fn read_x5chain(pem: &[u8]) -> Result<X5Chain, Error> {
+    let pem = std::str::from_utf8(pem).context("issuer certificate must be PEM encoded")?;
+    pem_blocks(pem)
+        .try_fold(X5Chain::builder(), |builder, block| {
+            builder.with_pem(block.as_bytes())
+        })?
+        .build()
+}
+
+/// Split a bundle of PEM encoded certificates into its individual certificates.
+fn pem_blocks(pem: &str) -> impl Iterator<Item = &str> {
+    let end = "-----END CERTIFICATE-----";
+    pem.split_inclusive(end)
+        .filter(move |block| block.contains(end))
+        .map(str::trim)
+}
+
+/// A device private key on one of the curves produced by `keygen`.
+enum DeviceSigningKey {
+    P256(p256::ecdsa::SigningKey),
+    P384(p384::ecdsa::SigningKey),
+    P521(p521::ecdsa::SigningKey),
+    Ed25519(ed25519_dalek::SigningKey),
+}
+
+impl DeviceSigningKey {
+    /// Sign the payload, returning the signature in the encoding used by COSE.
+    fn sign(&self, payload: &[u8]) -> Vec<u8> {
+        match self {
+            Self::P256(key) => Signer::<p256::ecdsa::Signature>::sign(key, payload).to_vec(),
+            Self::P384(key) => Signer::<p384::ecdsa::Signature>::sign(key, payload).to_vec(),
+            Self::P521(key) => Signer::<p521::ecdsa::Signature>::sign(key, payload).to_vec(),
+            Self::Ed25519(key) => key.sign(payload).to_bytes().to_vec(),
+        }
+    }
+}
+
+fn read_p256_private_key(pem: &str) -> Result<p256::SecretKey, Error> {
+    p256::SecretKey::from_pkcs8_pem(pem)
+        .or_else(|_| p256::SecretKey::from_sec1_pem(pem))
+        .map_err(|_| anyhow!("key must be a PKCS#8 or SEC1 PEM encoded P-256 private key"))
+}
+
+fn read_device_private_key(pem: &str) -> Result<DeviceSigningKey, Error> {
+    if let Ok(key) = read_p256_private_key(pem) {
+        return Ok(DeviceSigningKey::P256(key.into()));
+    }
+    if let Ok(key) =
+        p384::SecretKey::from_pkcs8_pem(pem).or_else(|_| p384::SecretKey::from_sec1_pem(pem))
+    {
+        return Ok(DeviceSigningKey::P384(key.into()));
+    }
+    if let Ok(key) =
+        p521::SecretKey::from_pkcs8_pem(pem).or_else(|_| p521::SecretKey::from_sec1_pem(pem))
+    {
+        return Ok(DeviceSigningKey::P521(
+            p521::ecdsa::SigningKey::from_slice(&key.to_bytes())
+                .map_err(|e| anyhow!("invalid P-521 private key: {e}"))?,
+        ));
+    }
+    if let Some(seed) = read_ed25519_private_key(pem) {
+        return Ok(DeviceSigningKey::Ed25519(
+            ed25519_dalek::SigningKey::from_bytes(&seed),
+        ));
+    }
+    bail!("device key must be a PEM encoded P-256, P-384, P-521 or Ed25519 private key")
+}
+
+/// Read the seed of an Ed25519 private key in the RFC 8410 PKCS#8 encoding produced by `keygen`.
+fn read_ed25519_private_key(pem: &str) -> Option<[u8; 32]> {
+    let (label, der) = pem_rfc7468::decode_vec(pem.trim().as_bytes()).ok()?;
+    if label != "PRIVATE KEY" {
+        return None;
+    }
+    der.strip_prefix(ED25519_PKCS8_PREFIX.as_slice())?
+        .try_into()
+        .ok()
+}
+
+/// Write a message as a line of base64 encoded CBOR.
+fn send(output: &mut impl Write, message: &[u8]) -> Result<(), Error> {
+    writeln!(output, "{}", base64::encode(message))?;
+    output.flush().map_err(Into::into)
+}
+
+/// Read the next line, or `None` if the other side has closed the channel.
+fn receive_line(input: &mut impl BufRead) -> Result<Option<String>, Error> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+fn receive(input: &mut impl BufRead) -> Result<Option<Vec<u8>>, Error> {
+    receive_line(input)?
+        .map(|line| base64::decode(line).context("message was not base64 encoded"))
+        .transpose()
+}
+
+fn run_holder(
+    document: Document,
+    device_key: DeviceSigningKey,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<(), Error> {
+    let documents = NonEmptyMap::new(document.mso.doc_type.clone(), document);
+    let (engaged, qr_code_uri) =
+        device::SessionManagerInit::initialise(documents, None, None)?.qr_engagement()?;
+    writeln!(output, "{qr_code_uri}")?;
+    output.flush()?;
+
+    let session_establishment: SessionEstablishment = serde_cbor::from_slice(
+        &receive(&mut input)?.ok_or_else(|| anyhow!("reader closed the channel"))?,
+    )
+    .context("could not parse session establishment")?;
+    let (mut session, mut requested) = engaged
+        .process_session_establishment(session_establishment)
+        .context("could not establish session")?;
+
+    loop {
+        let permitted = requested
+            .iter()
+            .map(|request| {
+                let namespaces = request
+                    .namespaces
+                    .iter()
+                    .map(|(namespace, elements)| {
+                        (namespace.clone(), elements.keys().cloned().collect())
+                    })
+                    .collect();
+                (request.doc_type.clone(), namespaces)
+            })
+            .collect();
+        eprintln!("releasing {}", serde_json::to_string(&permitted)?);
+        session.prepare_response(&requested, permitted)?;
+        while let Some((_, payload)) = session.get_next_signature_payload() {
+            session.submit_next_signature(device_key.sign(payload))?;
+        }
+        let response = session
+            .retrieve_response()
+            .ok_or_else(|| anyhow!("response was not ready"))?;
+        send(&mut output, &response)?;
+
+        let Some(message) = receive(&mut input)? else {
+            return Ok(());
+        };
+        match session.handle_request(&message)? {
+            device::SessionEvent::Request(items) => requested = items,
+            device::SessionEvent::Terminated => return Ok(()),
+            device::SessionEvent::Error(status) => bail!("reader reported an error: {status:?}"),
+        }
+    }
+}
+
+fn run_reader(
+    requests: Vec<device_request::Namespaces>,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<Vec<reader::ParsedResponse>, Error> {
+    let qr_code_uri =
+        receive_line(&mut input)?.ok_or_else(|| anyhow!("holder closed the channel"))?;
+    let mut requests = requests.into_iter();
+    let first = requests
+        .next()
+        .ok_or_else(|| anyhow!("at least one request is required"))?;
+    let (mut session, request, _ble_ident) =
+        reader::SessionManager::establish_session(qr_code_uri, first)?;
+    send(&mut output, &request)?;
+
+    let mut received = Vec::new();
+    loop {
+        let response = receive(&mut input)?.ok_or_else(|| anyhow!("holder closed the channel"))?;
+        match session.handle_response(&response)? {
+            reader::SessionEvent::Response(response) => received.push(response),
+            reader::SessionEvent::Terminated(response) => {
+                received.extend(response);
+                return Ok(received);
+            }
+            reader::SessionEvent::Error(status) => bail!("holder reported an error: {status:?}"),
+        }
+        match requests.next() {
+            Some(namespaces) => send(&mut output, &session.new_request(namespaces)?)?,
+            None => break,
+        }
+    }
+    send(&mut output, &session.terminate()?)?;
+    Ok(received)
+}
+
 #[cfg(test)]
 mod test {
+    use super::*;
+    use elliptic_curve::sec1::ToEncodedPoint;
+    use isomdl::definitions::device_key::cose_key::{EC2Curve, OKPCurve, EC2Y};
+
     #[test]
     fn print_namespaces() {
-        super::print_namespaces(include_str!("../test/stringified-mdl.txt").to_string()).unwrap()
+        super::print_namespaces(
+            include_str!("../test/stringified-mdl.txt").to_string(),
+            false,
+        )
+        .unwrap();
+        super::print_namespaces(
+            include_str!("../test/stringified-mdl.txt").to_string(),
+            true,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn issue() {
+        let dir = std::env::temp_dir().join(format!("isomdl-issue-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let claims = serde_json::json!({
+            "org.iso.18013.5.1": {
+                "family_name": "Smith",
+                "given_name": "Alice",
+                "birth_date": "1980-01-01",
+                "issue_date": "2020-01-01",
+                "expiry_date": "2030-01-01",
+                "issuing_country": "US",
+                "issuing_authority": "NY DMV",
+                "document_number": "DL12345678",
+                "portrait": include_str!("../test/issuance/portrait.b64"),
+                "driving_privileges": [],
+                "un_distinguishing_sign": "USA",
+            }
+        });
+        fs::write(dir.join("claims.json"), claims.to_string()).unwrap();
+
+        let der = base64::decode(include_str!("../test/issuance/device_key.b64")).unwrap();
+        let point = p256::SecretKey::from_sec1_der(&der)
+            .unwrap()
+            .public_key()
+            .to_encoded_point(false);
+        let jwk = serde_json::json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": base64::encode_config(point.x().unwrap(), base64::URL_SAFE_NO_PAD),
+            "y": base64::encode_config(point.y().unwrap(), base64::URL_SAFE_NO_PAD),
+        });
+        fs::write(dir.join("device_key.jwk"), jwk.to_string()).unwrap();
+
+        let now = OffsetDateTime::now_utc();
+        let args = |doc_type: &str| IssueArgs {
+            claims: dir.join("claims.json"),
+            device_key: dir.join("device_key.jwk"),
+            issuer_key: "test/issuance/issuer-key.pem".into(),
+            issuer_cert: "test/issuance/issuer-cert.pem".into(),
+            validity_info: ValidityInfo {
+                signed: now,
+                valid_from: now,
+                valid_until: now + time::Duration::days(365),
+                expected_update: None,
+            },
+            doc_type: doc_type.to_string(),
+            format: Format::Stringified,
+            output: Some(dir.join("mdl.txt")),
+        };
+        // The claims are validated against the registry, which does not know this document type.
+        assert!(super::issue(args("com.example.unknown")).is_err());
+        super::issue(args("org.iso.18013.5.1.mDL")).unwrap();
+
+        let mdl = fs::read_to_string(dir.join("mdl.txt")).unwrap();
+        let document = Document::parse(mdl).unwrap();
+        let elements = document.namespaces.into_inner();
+        assert!(elements[OrgIso1801351::NAMESPACE]
+            .as_ref()
+            .contains_key("family_name"));
+
+        super::issue(IssueArgs {
+            issuer_key: "test/issuance/521-key.pem".into(),
+            issuer_cert: "test/issuance/521-cert.pem".into(),
+            ..args("org.iso.18013.5.1.mDL")
+        })
+        .unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn decode_binary() {
+        let bytes = vec![0xa1, 0x00, 0xff];
+        for input in ["a100ff", "A1 00\nFF", "oQD/", "oQD_"] {
+            assert_eq!(super::decode_binary(input.as_bytes().to_vec()), bytes);
+        }
+        assert_eq!(super::decode_binary(bytes.clone()), bytes);
+    }
+
+    /// The public key of a device private key, as a COSE_Key.
+    fn public_key(key: &DeviceSigningKey) -> CoseKey {
+        let ec2 = |crv, point: &[u8], size: usize| CoseKey::EC2 {
+            crv,
+            x: point[1..1 + size].to_vec(),
+            y: EC2Y::Value(point[1 + size..].to_vec()),
+        };
+        match key {
+            DeviceSigningKey::P256(key) => ec2(
+                EC2Curve::P256,
+                key.verifying_key().to_encoded_point(false).as_bytes(),
+                32,
+            ),
+            DeviceSigningKey::P384(key) => ec2(
+                EC2Curve::P384,
+                key.verifying_key().to_encoded_point(false).as_bytes(),
+                48,
+            ),
+            DeviceSigningKey::P521(key) => ec2(
+                EC2Curve::P521,
+                p521::ecdsa::VerifyingKey::from(key)
+                    .to_encoded_point(false)
+                    .as_bytes(),
+                66,
+            ),
+            DeviceSigningKey::Ed25519(key) => CoseKey::OKP {
+                crv: OKPCurve::Ed25519,
+                x: key.verifying_key().to_bytes().to_vec(),
+            },
+        }
+    }
+
+    #[test]
+    fn generate_key() {
+        for curve in [Curve::P256, Curve::P384, Curve::P521, Curve::Ed25519] {
+            let (pem, jwk) = super::generate_key(curve).unwrap();
+            let key = read_device_private_key(&pem).unwrap();
+            assert_eq!(
+                public_key(&key),
+                CoseKey::try_from(jwk).unwrap(),
+                "{curve:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn decrypt_session() {
+        let session_transcript: Tag24<SessionTranscript180135> = serde_cbor::from_slice(
+            &hex::decode(include_str!(
+                "../test/definitions/session/session_transcript.cbor"
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+        let SessionTranscript180135(device_engagement, _, handover) =
+            session_transcript.into_inner();
+        let qr_code_uri = device_engagement.to_qr_code_uri().unwrap();
+        let tagged = hex::encode(serde_cbor::to_vec(&device_engagement).unwrap());
+        let ephemeral_key = read_ephemeral_key(
+            include_bytes!("../test/definitions/session/e_device_key.cbor").to_vec(),
+        )
+        .unwrap();
+        let session_establishment = super::decode_binary(
+            include_bytes!("../test/definitions/session/session_establishment.cbor").to_vec(),
+        );
+
+        for input in [qr_code_uri, tagged] {
+            let device_engagement = read_device_engagement(input.into_bytes()).unwrap();
+            let session = inspect::decrypt_session(
+                device_engagement,
+                handover.clone(),
+                &ephemeral_key,
+                &session_establishment,
+                &[],
+            )
+            .unwrap();
+            assert!(session.messages[0].inspection.decrypted.is_some());
+        }
+    }
+
+    #[test]
+    fn inspect_input() {
+        let response = include_str!("../test/definitions/device_response.cbor");
+        let inspection =
+            super::inspect_input(response.as_bytes().to_vec(), None, &Default::default()).unwrap();
+        assert_eq!(inspection.structure, Structure::DeviceResponse);
+
+        let document =
+            Document::parse(include_str!("../test/stringified-mdl.txt").to_string()).unwrap();
+        let documents = NonEmptyMap::new(document.mso.doc_type.clone(), document);
+        let (_, qr_code_uri) = device::SessionManagerInit::initialise(documents, None, None)
+            .unwrap()
+            .qr_engagement()
+            .unwrap();
+        let qr_code_uri = format!("{qr_code_uri}\n");
+        let inspection =
+            super::inspect_input(qr_code_uri.as_bytes().to_vec(), None, &Default::default())
+                .unwrap();
+        assert_eq!(inspection.structure, Structure::DeviceEngagement);
+    }
+
+    #[test]
+    fn holder_and_reader() {
+        let document =
+            Document::parse(include_str!("../test/stringified-mdl.txt").to_string()).unwrap();
+        let der = base64::decode(include_str!("../test/issuance/device_key.b64")).unwrap();
+        let device_key =
+            DeviceSigningKey::P256(p256::SecretKey::from_sec1_der(&der).unwrap().into());
+
+        let (holder_input, reader_output) = std::io::pipe().unwrap();
+        let (reader_input, holder_output) = std::io::pipe().unwrap();
+        let holder = std::thread::spawn(move || {
+            run_holder(
+                document,
+                device_key,
+                BufReader::new(holder_input),
+                holder_output,
+            )
+        });
+
+        let requests = [
+            serde_json::json!({ "org.iso.18013.5.1": { "family_name": false } }),
+            serde_json::json!({ "org.iso.18013.5.1": { "given_name": true } }),
+        ]
+        .into_iter()
+        .map(|request| serde_json::from_value(request).unwrap())
+        .collect();
+        let received = run_reader(requests, BufReader::new(reader_input), reader_output).unwrap();
+        holder.join().unwrap().unwrap();
+
+        assert_eq!(received.len(), 2);
+        let elements = |i: usize| -> Vec<&String> {
+            received[i].issuer_signed[OrgIso1801351::NAMESPACE]
+                .keys()
+                .collect()
+        };
+        assert_eq!(elements(0), ["family_name"]);
+        assert_eq!(elements(1), ["given_name"]);
     }
 }
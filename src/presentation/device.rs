@@ -7,13 +7,18 @@ use crate::{
             Document as DeviceResponseDoc, DocumentError, DocumentErrorCode, DocumentErrors,
             Errors as NamespaceErrors, Status,
         },
-        device_signed::{DeviceAuth, DeviceAuthentication, DeviceNamespacesBytes, DeviceSigned},
-        helpers::{tag24, NonEmptyMap, NonEmptyVec, Tag24},
+        device_signed::{
+            DeviceAuth, DeviceAuthentication, DeviceNamespaces, DeviceNamespacesBytes, DeviceSigned,
+        },
+        helpers::{
+            signature_format, tag24, ByteStr, NonEmptyMap, NonEmptyVec, SignatureFormat, Tag24,
+        },
         issuer_signed::{IssuerSigned, IssuerSignedItemBytes},
+        namespaces::registry::Registry,
         session::{
             self, derive_session_key, get_shared_secret, Handover, SessionData, SessionTranscript,
         },
-        CoseKey, DeviceEngagement, DeviceResponse, Mso, SessionEstablishment,
+        CoseKey, DeviceEngagement, DeviceKeyInfo, DeviceResponse, Mso, SessionEstablishment,
     },
     issuance::Mdoc,
 };
@@ -50,6 +55,8 @@ pub struct SessionManager {
     sk_reader: [u8; 32],
     reader_message_counter: u32,
     state: State,
+    #[serde(default)]
+    device_signed: BTreeMap<DocType, DeviceNamespaces>,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -58,6 +65,18 @@ pub enum State {
     AwaitingRequest,
     Signing(PreparedDeviceResponse),
     ReadyToRespond(Vec<u8>),
+    Terminated,
+}
+
+/// An event arising from a message received from the reader.
+#[derive(Debug, Clone)]
+pub enum SessionEvent {
+    /// The reader requested the following items.
+    Request(RequestedItems),
+    /// The reader ended the session.
+    Terminated,
+    /// The reader reported an error, ending the session.
+    Error(session::Status),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -76,10 +95,20 @@ pub enum Error {
     ParsingError(#[from] ParseIntError),
     #[error("age_over element identifier is malformed")]
     PrefixError,
+    #[error("the session has been terminated")]
+    SessionTerminated,
+    /// The request could not be decrypted, which ends the session. The second field is the
+    /// SessionData message with status 10 (session encryption error) to send to the reader.
+    #[error("unable to decrypt request: {0}")]
+    Decryption(session::DecryptionError, Vec<u8>),
+    #[error("invalid signature: {0}")]
+    Signature(signature_format::Error),
+    #[error("the reader sent data along with status {0:?}")]
+    UnexpectedData(session::Status),
 }
 
 pub type Documents = NonEmptyMap<DocType, Document>;
-type DocType = String;
+pub type DocType = String;
 
 /// Device-internal document datatype.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -105,12 +134,14 @@ struct PreparedDocument {
     issuer_signed: IssuerSigned,
     device_namespaces: DeviceNamespacesBytes,
     prepared_cose_sign1: PreparedCoseSign1,
+    #[serde(default)]
+    signature_format: SignatureFormat,
     errors: Option<NamespaceErrors>,
 }
 
 type Namespaces = NonEmptyMap<Namespace, NonEmptyMap<ElementIdentifier, IssuerSignedItemBytes>>;
-type Namespace = String;
-type ElementIdentifier = String;
+pub type Namespace = String;
+pub type ElementIdentifier = String;
 
 pub type RequestedItems = Vec<ItemsRequest>;
 pub type PermittedItems = BTreeMap<DocType, BTreeMap<Namespace, Vec<ElementIdentifier>>>;
@@ -167,6 +198,19 @@ impl SessionManagerEngaged {
     pub fn process_session_establishment(
         self,
         session_establishment: SessionEstablishment,
+    ) -> anyhow::Result<(SessionManager, RequestedItems)> {
+        self.process_session_establishment_with_registry(
+            session_establishment,
+            &Registry::default(),
+        )
+    }
+
+    /// Process the session establishment message, rejecting a request which the reader intends
+    /// to retain against the policy of the given registry.
+    pub fn process_session_establishment_with_registry(
+        self,
+        session_establishment: SessionEstablishment,
+        registry: &Registry,
     ) -> anyhow::Result<(SessionManager, RequestedItems)> {
         let e_reader_key = session_establishment.e_reader_key;
         let session_transcript =
@@ -191,12 +235,10 @@ impl SessionManagerEngaged {
             sk_reader,
             reader_message_counter: 0,
             state: State::AwaitingRequest,
+            device_signed: BTreeMap::new(),
         };
 
-        let requested_data = sm.handle_decoded_request(SessionData {
-            data: Some(session_establishment.data),
-            status: None,
-        })?;
+        let requested_data = sm.handle_encrypted_request(session_establishment.data, registry)?;
 
         Ok((sm, requested_data))
     }
@@ -218,6 +260,7 @@ impl SessionManager {
     fn validate_request(
         &self,
         request: DeviceRequest,
+        registry: &Registry,
     ) -> Result<Vec<ItemsRequest>, PreparedDeviceResponse> {
         if request.version != DeviceRequest::VERSION {
             // tracing::error!(
@@ -227,29 +270,101 @@ impl SessionManager {
             // );
             return Err(PreparedDeviceResponse::empty(Status::GeneralError));
         }
-        Ok(request
+        let items_requests: Vec<ItemsRequest> = request
             .doc_requests
             .into_inner()
             .into_iter()
             .map(|DocRequest { items_request, .. }| items_request.into_inner())
-            .collect())
+            .collect();
+        if items_requests.iter().any(|items_request| {
+            registry
+                .validate_intent_to_retain(&items_request.namespaces)
+                .is_err()
+        }) {
+            return Err(PreparedDeviceResponse::empty(Status::GeneralError));
+        }
+        Ok(items_requests)
+    }
+
+    /// Provide data elements for the mdoc to self-assert in the response for the given document
+    /// type, authenticated by device authentication rather than by the issuer.
+    ///
+    /// An element is only returned as device-signed if it is requested, permitted, not available
+    /// as issuer-signed data, and authorized by the key authorizations in the document's MSO.
+    pub fn set_device_signed_elements(
+        &mut self,
+        doc_type: DocType,
+        namespace: Namespace,
+        elements: NonEmptyMap<ElementIdentifier, CborValue>,
+    ) {
+        self.device_signed
+            .entry(doc_type)
+            .or_default()
+            .insert(namespace, elements);
     }
 
-    pub fn prepare_response(&mut self, requests: &RequestedItems, permitted: PermittedItems) {
+    pub fn prepare_response(
+        &mut self,
+        requests: &RequestedItems,
+        permitted: PermittedItems,
+    ) -> anyhow::Result<()> {
+        if self.is_terminated() {
+            return Err(Error::SessionTerminated.into());
+        }
         let prepared_response = DeviceSession::prepare_response(self, requests, permitted);
         self.state = State::Signing(prepared_response);
+        Ok(())
     }
 
-    fn handle_decoded_request(&mut self, request: SessionData) -> anyhow::Result<RequestedItems> {
-        let data = request.data.ok_or_else(|| {
-            anyhow::anyhow!("no mdoc requests received, assume session can be terminated")
-        })?;
-        let decrypted_request = session::decrypt_reader_data(
+    fn handle_decoded_request(
+        &mut self,
+        request: SessionData,
+        registry: &Registry,
+    ) -> anyhow::Result<SessionEvent> {
+        if self.is_terminated() {
+            return Err(Error::SessionTerminated.into());
+        }
+        if let Some(status) = request.status {
+            self.state = State::Terminated;
+            // No response can follow a status code, so a request sent along with one is rejected
+            // rather than dropped.
+            if request.data.is_some() {
+                return Err(Error::UnexpectedData(status).into());
+            }
+            return Ok(match status {
+                session::Status::SessionTermination => SessionEvent::Terminated,
+                status => SessionEvent::Error(status),
+            });
+        }
+        let data = request
+            .data
+            .ok_or_else(|| anyhow::anyhow!("reader sent neither a request nor a status code"))?;
+        self.handle_encrypted_request(data, registry)
+            .map(SessionEvent::Request)
+    }
+
+    fn handle_encrypted_request(
+        &mut self,
+        data: ByteStr,
+        registry: &Registry,
+    ) -> anyhow::Result<RequestedItems> {
+        let decrypted_request = match session::decrypt_reader_data(
             &self.sk_reader.into(),
             data.as_ref(),
             &mut self.reader_message_counter,
-        )
-        .map_err(|e| anyhow::anyhow!("unable to decrypt request: {}", e))?;
+        ) {
+            Ok(decrypted_request) => decrypted_request,
+            Err(e) => {
+                self.state = State::Terminated;
+                let session_data = SessionData {
+                    data: None,
+                    status: Some(session::Status::SessionEncryptionError),
+                };
+                let session_data =
+                    serde_cbor::to_vec(&session_data).map_err(Error::CborEncoding)?;
+                return Err(Error::Decryption(e, session_data).into());
+            }
+        };
         let request = match self.parse_request(&decrypted_request) {
             Ok(r) => r,
             Err(e) => {
@@ -257,7 +372,7 @@ impl SessionManager {
                 return Ok(Default::default());
             }
         };
-        let request = match self.validate_request(request) {
+        let request = match self.validate_request(request, registry) {
             Ok(r) => r,
             Err(e) => {
                 self.state = State::Signing(e);
@@ -267,10 +382,41 @@ impl SessionManager {
         Ok(request)
     }
 
-    /// Handle a request from the reader.
-    pub fn handle_request(&mut self, request: &[u8]) -> anyhow::Result<RequestedItems> {
+    /// Handle a message from the reader.
+    ///
+    /// A message carrying a status code ends the session, and is surfaced as
+    /// [SessionEvent::Terminated] or [SessionEvent::Error]. A message carrying both a status code
+    /// and a request is rejected.
+    pub fn handle_request(&mut self, request: &[u8]) -> anyhow::Result<SessionEvent> {
+        self.handle_request_with_registry(request, &Registry::default())
+    }
+
+    /// Handle a message from the reader, rejecting a request which the reader intends to retain
+    /// against the policy of the given registry.
+    pub fn handle_request_with_registry(
+        &mut self,
+        request: &[u8],
+        registry: &Registry,
+    ) -> anyhow::Result<SessionEvent> {
         let session_data: SessionData = serde_cbor::from_slice(request)?;
-        self.handle_decoded_request(session_data)
+        self.handle_decoded_request(session_data, registry)
+    }
+
+    /// End the session, returning the SessionData message to send to the reader.
+    ///
+    /// No further messages can be encrypted or decrypted once the session has been terminated.
+    pub fn terminate(&mut self) -> anyhow::Result<Vec<u8>> {
+        self.state = State::Terminated;
+        let session_data = SessionData {
+            data: None,
+            status: Some(session::Status::SessionTermination),
+        };
+        serde_cbor::to_vec(&session_data).map_err(Into::into)
+    }
+
+    /// Identifies that the session has been terminated.
+    pub fn is_terminated(&self) -> bool {
+        matches!(self.state, State::Terminated)
     }
 
     /// Get next payload for signing.
@@ -283,10 +429,16 @@ impl SessionManager {
 
     /// Submit the externally signed signature.
     pub fn submit_next_signature(&mut self, signature: Vec<u8>) -> anyhow::Result<()> {
+        if self.is_terminated() {
+            return Err(Error::SessionTerminated.into());
+        }
         if matches!(self.state, State::Signing(_)) {
             match std::mem::take(&mut self.state) {
                 State::Signing(mut p) => {
-                    p.submit_next_signature(signature);
+                    if let Err(e) = p.submit_next_signature(signature) {
+                        self.state = State::Signing(p);
+                        return Err(e.into());
+                    }
                     if p.is_complete() {
                         let response = p.finalize_response();
                         let mut status: Option<session::Status> = None;
@@ -325,6 +477,9 @@ impl SessionManager {
     }
 
     /// Retrieve the completed response.
+    ///
+    /// The session then awaits the next request from the reader, which may be handled in the same
+    /// way as the first.
     pub fn retrieve_response(&mut self) -> Option<Vec<u8>> {
         if self.response_ready() {
             // Replace state with AwaitingRequest.
@@ -363,17 +518,28 @@ impl PreparedDeviceResponse {
             .map(|doc| (doc.id, doc.prepared_cose_sign1.signature_payload()))
     }
 
-    pub fn submit_next_signature(&mut self, signature: Vec<u8>) {
-        let signed_doc = match self.prepared_documents.pop() {
-            Some(doc) => doc.finalize(signature),
+    /// Submit the externally signed signature for the next prepared document.
+    ///
+    /// ECDSA signatures may be either `r || s` or DER encoded. If the signature is malformed, the
+    /// document remains prepared so that a corrected signature can be submitted.
+    pub fn submit_next_signature(&mut self, signature: Vec<u8>) -> Result<(), Error> {
+        let doc = match self.prepared_documents.last() {
+            Some(doc) => doc,
             None => {
                 //tracing::error!(
                 //    "received a signature for finalising when there are no more prepared docs"
                 //);
-                return;
+                return Ok(());
             }
         };
+        let signature = doc
+            .signature_format
+            .normalize(signature)
+            .map_err(Error::Signature)?;
+        // Safe to unwrap as the last document has just been checked.
+        let signed_doc = self.prepared_documents.pop().unwrap().finalize(signature);
         self.signed_documents.push(signed_doc);
+        Ok(())
     }
 
     pub fn finalize_response(self) -> DeviceResponse {
@@ -422,6 +588,10 @@ pub trait DeviceSession {
 
     fn documents(&self) -> &Documents;
     fn session_transcript(&self) -> Self::ST;
+    /// Data elements that may be returned as device-signed for the given document type.
+    fn device_signed_elements(&self, _doc_type: &str) -> Option<&DeviceNamespaces> {
+        None
+    }
     fn prepare_response(
         &self,
         requests: &RequestedItems,
@@ -469,44 +639,55 @@ pub trait DeviceSession {
             let mut errors: BTreeMap<String, NonEmptyMap<String, DocumentErrorCode>> =
                 Default::default();
 
+            let mut device_namespaces: BTreeMap<String, BTreeMap<String, CborValue>> =
+                Default::default();
+            let device_signed = self.device_signed_elements(&doc_type);
+
             for (namespace, elements) in namespaces.into_iter() {
-                if let Some(issuer_items) = document.namespaces.get(&namespace) {
-                    for element_identifier in elements.into_iter() {
-                        if let Some(item) = issuer_items.get(&element_identifier) {
-                            if let Some(returned_items) = issuer_namespaces.get_mut(&namespace) {
-                                returned_items.push(item.clone());
-                            } else {
-                                let returned_items = NonEmptyVec::new(item.clone());
-                                issuer_namespaces.insert(namespace.clone(), returned_items);
-                            }
-                        } else if let Some(returned_errors) = errors.get_mut(&namespace) {
-                            returned_errors
-                                .insert(element_identifier, DocumentErrorCode::DataNotReturned);
-                        } else {
-                            let returned_errors = NonEmptyMap::new(
-                                element_identifier,
-                                DocumentErrorCode::DataNotReturned,
-                            );
-                            errors.insert(namespace.clone(), returned_errors);
-                        }
-                    }
-                } else {
-                    for element_identifier in elements.into_iter() {
-                        if let Some(returned_errors) = errors.get_mut(&namespace) {
-                            returned_errors
-                                .insert(element_identifier, DocumentErrorCode::DataNotReturned);
+                let issuer_items = document.namespaces.get(&namespace);
+                let device_items = device_signed.and_then(|ns| ns.get(&namespace));
+                for element_identifier in elements.into_iter() {
+                    if let Some(item) =
+                        issuer_items.and_then(|items| items.get(&element_identifier))
+                    {
+                        if let Some(returned_items) = issuer_namespaces.get_mut(&namespace) {
+                            returned_items.push(item.clone());
                         } else {
-                            let returned_errors = NonEmptyMap::new(
-                                element_identifier,
-                                DocumentErrorCode::DataNotReturned,
-                            );
-                            errors.insert(namespace.clone(), returned_errors);
+                            let returned_items = NonEmptyVec::new(item.clone());
+                            issuer_namespaces.insert(namespace.clone(), returned_items);
                         }
+                    } else if let Some(value) =
+                        device_items.and_then(|items| items.get(&element_identifier))
+                    {
+                        device_namespaces
+                            .entry(namespace.clone())
+                            .or_default()
+                            .insert(element_identifier, value.clone());
+                    } else if let Some(returned_errors) = errors.get_mut(&namespace) {
+                        returned_errors
+                            .insert(element_identifier, DocumentErrorCode::DataNotReturned);
+                    } else {
+                        let returned_errors = NonEmptyMap::new(
+                            element_identifier,
+                            DocumentErrorCode::DataNotReturned,
+                        );
+                        errors.insert(namespace.clone(), returned_errors);
                     }
                 }
             }
 
-            let device_namespaces = match Tag24::new(Default::default()) {
+            let device_namespaces = device_namespaces
+                .into_iter()
+                .filter_map(|(namespace, elements)| {
+                    NonEmptyMap::maybe_new(elements).map(|elements| (namespace, elements))
+                })
+                .collect();
+            let device_namespaces = authorize_device_namespaces(
+                &document.mso.device_key_info,
+                device_namespaces,
+                &mut errors,
+            );
+            let device_namespaces = match Tag24::new(device_namespaces) {
                 Ok(dp) => dp,
                 Err(_e) => {
                     let error: DocumentError =
@@ -567,6 +748,7 @@ pub trait DeviceSession {
                 },
                 device_namespaces,
                 prepared_cose_sign1,
+                signature_format: signature_algorithm.into(),
                 errors: errors.try_into().ok(),
             };
             prepared_documents.push(prepared_document);
@@ -590,6 +772,10 @@ impl DeviceSession for SessionManager {
     fn session_transcript(&self) -> SessionTranscript180135 {
         self.session_transcript.clone()
     }
+
+    fn device_signed_elements(&self, doc_type: &str) -> Option<&DeviceNamespaces> {
+        self.device_signed.get(doc_type)
+    }
 }
 
 impl From<Mdoc> for Document {
@@ -630,6 +816,48 @@ impl From<Mdoc> for Document {
     }
 }
 
+/// Remove any device-signed elements that the device key is not authorized to sign over, as
+/// defined by the key authorizations in the MSO, reporting them as not returned.
+fn authorize_device_namespaces(
+    device_key_info: &DeviceKeyInfo,
+    namespaces: DeviceNamespaces,
+    errors: &mut BTreeMap<String, NonEmptyMap<String, DocumentErrorCode>>,
+) -> DeviceNamespaces {
+    namespaces
+        .into_iter()
+        .filter_map(|(namespace, elements)| {
+            let authorized = elements
+                .into_inner()
+                .into_iter()
+                .filter(|(element_identifier, _)| {
+                    if device_key_info.permitted(&namespace, element_identifier) {
+                        return true;
+                    }
+                    //tracing::warn!(
+                    //    "device key is not authorized to sign over '{}' in '{}'",
+                    //    element_identifier,
+                    //    namespace
+                    //);
+                    if let Some(returned_errors) = errors.get_mut(&namespace) {
+                        returned_errors.insert(
+                            element_identifier.clone(),
+                            DocumentErrorCode::DataNotReturned,
+                        );
+                    } else {
+                        let returned_errors = NonEmptyMap::new(
+                            element_identifier.clone(),
+                            DocumentErrorCode::DataNotReturned,
+                        );
+                        errors.insert(namespace.clone(), returned_errors);
+                    }
+                    false
+                })
+                .collect::<BTreeMap<_, _>>();
+            NonEmptyMap::maybe_new(authorized).map(|elements| (namespace, elements))
+        })
+        .collect()
+}
+
 /// Filter permitted items to only permit the items that were requested.
 fn filter_permitted(request: &RequestedItems, permitted: PermittedItems) -> PermittedItems {
     permitted
@@ -734,7 +962,9 @@ mod test {
     use crate::definitions::helpers::ByteStr;
 
     use super::*;
+    use crate::definitions::device_key::cose_key::OKPCurve;
     use crate::definitions::mso::DigestId;
+    use crate::definitions::KeyAuthorizations;
     use serde_json::json;
 
     #[test]
@@ -793,6 +1023,45 @@ mod test {
         assert_eq!(expected, filtered);
     }
 
+    #[test]
+    fn authorize_device_namespaces() {
+        let device_key_info = DeviceKeyInfo {
+            device_key: CoseKey::OKP {
+                crv: OKPCurve::Ed25519,
+                x: vec![],
+            },
+            key_authorizations: Some(KeyAuthorizations {
+                namespaces: None,
+                data_elements: Some(NonEmptyMap::new(
+                    "namespace_1".to_string(),
+                    NonEmptyVec::new("element_1".to_string()),
+                )),
+            }),
+            key_info: None,
+        };
+        let mut elements = NonEmptyMap::new("element_1".to_string(), CborValue::Bool(true));
+        elements.insert("element_2".to_string(), CborValue::Bool(true));
+        let namespaces: DeviceNamespaces = [
+            ("namespace_1".to_string(), elements),
+            (
+                "namespace_2".to_string(),
+                NonEmptyMap::new("element_1".to_string(), CborValue::Bool(true)),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut errors = BTreeMap::new();
+        let authorized =
+            super::authorize_device_namespaces(&device_key_info, namespaces, &mut errors);
+
+        assert_eq!(authorized.len(), 1);
+        assert!(authorized["namespace_1"].contains_key("element_1"));
+        assert!(!authorized["namespace_1"].contains_key("element_2"));
+        assert!(errors["namespace_1"].contains_key("element_2"));
+        assert!(errors["namespace_2"].contains_key("element_1"));
+    }
+
     #[test]
     fn test_parse_age_from_element_identifier() {
         let element_identifier = "age_over_88".to_string();
@@ -1,5 +1,6 @@
 pub mod device;
 pub mod reader;
+pub mod verification;
 
 use anyhow::Result;
 use base64::{decode, encode};
@@ -39,3 +40,528 @@ fn calculate_ble_ident(e_device_key: &Tag24<CoseKey>) -> Result<[u8; 16]> {
 
     Ok(ble_ident)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::{
+        device_key::cose_key::{EC2Curve, EC2Y},
+        device_request,
+        helpers::{NonEmptyMap, NonEmptyVec},
+        namespaces::registry::{self, DocTypeSchema, ElementType, NamespaceSchema, Registry},
+        session::{DecryptionError, Status},
+        DeviceKeyInfo, KeyAuthorizations, SessionData, SessionEstablishment,
+    };
+    use crate::issuance::{mdoc::test::minimal_test_mdoc_builder, X5Chain};
+    use elliptic_curve::sec1::ToEncodedPoint;
+    use p256::ecdsa::{Signature, SigningKey};
+    use p256::pkcs8::DecodePrivateKey;
+    use serde_cbor::Value as CborValue;
+    use serde_json::json;
+    use signature::Signer;
+
+    static DOC_TYPE: &str = "org.iso.18013.5.1.mDL";
+    static NAMESPACE: &str = "org.iso.18013.5.1";
+    static ISSUER_CERT: &[u8] = include_bytes!("../../test/issuance/issuer-cert.pem");
+    static ISSUER_KEY: &str = include_str!("../../test/issuance/issuer-key.pem");
+    static DEVICE_KEY: &str = include_str!("../../test/issuance/device_key.b64");
+
+    fn device_key() -> p256::SecretKey {
+        let der_bytes = base64::decode(DEVICE_KEY).unwrap();
+        p256::SecretKey::from_sec1_der(&der_bytes).unwrap()
+    }
+
+    fn documents(key_authorizations: Option<KeyAuthorizations>) -> device::Documents {
+        let ec = device_key().public_key().to_encoded_point(false);
+        let device_key_info = DeviceKeyInfo {
+            device_key: CoseKey::EC2 {
+                crv: EC2Curve::P256,
+                x: ec.x().unwrap().to_vec(),
+                y: EC2Y::Value(ec.y().unwrap().to_vec()),
+            },
+            key_authorizations,
+            key_info: None,
+        };
+        let x5chain = X5Chain::builder()
+            .with_pem(ISSUER_CERT)
+            .unwrap()
+            .build()
+            .unwrap();
+        let signer: SigningKey = p256::SecretKey::from_pkcs8_pem(ISSUER_KEY).unwrap().into();
+        let mdoc = minimal_test_mdoc_builder()
+            .device_key_info(device_key_info)
+            .issue::<SigningKey, Signature>(x5chain, signer)
+            .unwrap();
+        NonEmptyMap::new(DOC_TYPE.to_string(), mdoc.into())
+    }
+
+    fn establish_session(
+        documents: device::Documents,
+        requested: serde_json::Value,
+    ) -> (
+        device::SessionManager,
+        reader::SessionManager,
+        device::RequestedItems,
+    ) {
+        let (engaged, qr_code_uri) = device::SessionManagerInit::initialise(documents, None, None)
+            .unwrap()
+            .qr_engagement()
+            .unwrap();
+        let requested: device_request::Namespaces = serde_json::from_value(requested).unwrap();
+        let (reader, request, _ble_ident) =
+            reader::SessionManager::establish_session(qr_code_uri, requested).unwrap();
+        let session_establishment: SessionEstablishment = serde_cbor::from_slice(&request).unwrap();
+        let (holder, requested_items) = engaged
+            .process_session_establishment(session_establishment)
+            .unwrap();
+        (holder, reader, requested_items)
+    }
+
+    fn sign_response(holder: &mut device::SessionManager) -> Vec<u8> {
+        let signer: SigningKey = device_key().into();
+        while let Some((_, payload)) = holder.get_next_signature_payload() {
+            let signature: Signature = signer.sign(payload);
+            holder.submit_next_signature(signature.to_vec()).unwrap();
+        }
+        holder.retrieve_response().unwrap()
+    }
+
+    #[test]
+    fn device_signed_elements() {
+        let key_authorizations = KeyAuthorizations {
+            namespaces: None,
+            data_elements: Some(NonEmptyMap::new(
+                NAMESPACE.to_string(),
+                NonEmptyVec::new("age_over_25".to_string()),
+            )),
+        };
+        let requested = json!({
+            NAMESPACE: {
+                "family_name": false,
+                "age_over_25": false,
+                "age_over_30": false,
+            }
+        });
+        let (mut holder, mut reader, requested_items) =
+            establish_session(documents(Some(key_authorizations)), requested);
+
+        let mut device_signed = NonEmptyMap::new("age_over_25".to_string(), CborValue::Bool(true));
+        device_signed.insert("age_over_30".to_string(), CborValue::Bool(false));
+        holder.set_device_signed_elements(
+            DOC_TYPE.to_string(),
+            NAMESPACE.to_string(),
+            device_signed,
+        );
+
+        let permitted = serde_json::from_value(json!({
+            DOC_TYPE: {
+                NAMESPACE: ["family_name", "age_over_25", "age_over_30"]
+            }
+        }))
+        .unwrap();
+        holder
+            .prepare_response(&requested_items, permitted)
+            .unwrap();
+        let response = sign_response(&mut holder);
+
+        let parsed = match reader.handle_response(&response).unwrap() {
+            reader::SessionEvent::Response(parsed) => parsed,
+            event => panic!("unexpected event: {event:?}"),
+        };
+        assert_eq!(
+            parsed.issuer_signed[NAMESPACE]["family_name"],
+            json!("Smith")
+        );
+        assert!(!parsed.issuer_signed[NAMESPACE].contains_key("age_over_25"));
+        assert_eq!(parsed.device_signed[NAMESPACE]["age_over_25"], json!(true));
+        // Not authorized by the key authorizations in the MSO.
+        assert!(!parsed.device_signed[NAMESPACE].contains_key("age_over_30"));
+    }
+
+    #[test]
+    fn custom_registry() {
+        let respond = || {
+            let requested = json!({ NAMESPACE: { "family_name": false } });
+            let (mut holder, reader, requested_items) =
+                establish_session(documents(None), requested);
+            let permitted = serde_json::from_value(json!({
+                DOC_TYPE: { NAMESPACE: ["family_name"] }
+            }))
+            .unwrap();
+            holder
+                .prepare_response(&requested_items, permitted)
+                .unwrap();
+            (reader, sign_response(&mut holder))
+        };
+
+        let mut registry = Registry::empty();
+        registry.register_doc_type(DOC_TYPE, DocTypeSchema::new().required(NAMESPACE));
+        let (mut reader, response) = respond();
+        let parsed = match reader
+            .handle_response_with_registry(&response, &registry)
+            .unwrap()
+        {
+            reader::SessionEvent::Response(parsed) => parsed,
+            event => panic!("unexpected event: {event:?}"),
+        };
+        assert_eq!(parsed.doc_type, DOC_TYPE);
+        assert_eq!(
+            parsed.issuer_signed[NAMESPACE]["family_name"],
+            json!("Smith")
+        );
+
+        registry.register_doc_type(
+            DOC_TYPE,
+            DocTypeSchema::new()
+                .required(NAMESPACE)
+                .required("org.iso.18013.5.1.aamva"),
+        );
+        let (mut reader, response) = respond();
+        assert!(matches!(
+            reader.handle_response_with_registry(&response, &registry),
+            Err(reader::Error::IncorrectNamespace)
+        ));
+
+        let (mut reader, response) = respond();
+        assert!(matches!(
+            reader.handle_response_with_registry(&response, &Registry::empty()),
+            Err(reader::Error::DocumentTypeError)
+        ));
+    }
+
+    #[test]
+    fn reader_terminates_session() {
+        let requested = json!({ NAMESPACE: { "family_name": false } });
+        let (mut holder, mut reader, _) = establish_session(documents(None), requested.clone());
+
+        let termination = reader.terminate().unwrap();
+        assert!(reader.is_terminated());
+        assert!(reader
+            .new_request(serde_json::from_value(requested).unwrap())
+            .is_err());
+
+        assert!(matches!(
+            holder.handle_request(&termination).unwrap(),
+            device::SessionEvent::Terminated
+        ));
+        assert!(holder.is_terminated());
+        assert!(holder.handle_request(&termination).is_err());
+    }
+
+    #[test]
+    fn device_terminates_session() {
+        let requested = json!({ NAMESPACE: { "family_name": false } });
+        let (mut holder, mut reader, requested_items) =
+            establish_session(documents(None), requested);
+
+        let termination = holder.terminate().unwrap();
+        assert!(holder.is_terminated());
+        assert!(matches!(
+            holder
+                .prepare_response(&requested_items, Default::default())
+                .unwrap_err()
+                .downcast_ref(),
+            Some(device::Error::SessionTerminated)
+        ));
+        assert!(holder.get_next_signature_payload().is_none());
+        assert!(holder.submit_next_signature(vec![]).is_err());
+
+        assert_eq!(
+            reader.handle_response(&termination).unwrap(),
+            reader::SessionEvent::Terminated(None)
+        );
+        assert!(matches!(
+            reader.handle_response(&termination),
+            Err(reader::Error::SessionTerminated)
+        ));
+    }
+
+    #[test]
+    fn device_reports_error() {
+        let requested = json!({ NAMESPACE: { "family_name": false } });
+        let (_, mut reader, _) = establish_session(documents(None), requested);
+
+        let session_data = SessionData {
+            data: None,
+            status: Some(Status::SessionEncryptionError),
+        };
+        let message = serde_cbor::to_vec(&session_data).unwrap();
+        assert_eq!(
+            reader.handle_response(&message).unwrap(),
+            reader::SessionEvent::Error(Status::SessionEncryptionError)
+        );
+        assert!(reader.is_terminated());
+    }
+
+    #[test]
+    fn status_with_data() {
+        let requested = json!({ NAMESPACE: { "family_name": false } });
+        let (mut holder, mut reader, _) = establish_session(documents(None), requested);
+
+        let session_data = SessionData {
+            data: Some(vec![0; 32].into()),
+            status: Some(Status::SessionTermination),
+        };
+        let error = holder
+            .handle_request(&serde_cbor::to_vec(&session_data).unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<device::Error>(),
+            Some(device::Error::UnexpectedData(Status::SessionTermination))
+        ));
+        assert!(holder.is_terminated());
+
+        let session_data = SessionData {
+            data: Some(vec![0; 32].into()),
+            status: Some(Status::CborDecodingError),
+        };
+        assert!(matches!(
+            reader.handle_response(&serde_cbor::to_vec(&session_data).unwrap()),
+            Err(reader::Error::UnexpectedData(Status::CborDecodingError))
+        ));
+        assert!(reader.is_terminated());
+    }
+
+    #[test]
+    fn multiple_rounds() {
+        let requested = json!({ NAMESPACE: { "family_name": false } });
+        let (mut holder, mut reader, requested_items) =
+            establish_session(documents(None), requested);
+        let permitted: device::PermittedItems = serde_json::from_value(json!({
+            DOC_TYPE: { NAMESPACE: ["family_name", "given_name"] }
+        }))
+        .unwrap();
+
+        holder
+            .prepare_response(&requested_items, permitted.clone())
+            .unwrap();
+        let first_response = sign_response(&mut holder);
+        let parsed = match reader.handle_response(&first_response).unwrap() {
+            reader::SessionEvent::Response(parsed) => parsed,
+            event => panic!("unexpected event: {event:?}"),
+        };
+        assert!(parsed.issuer_signed[NAMESPACE].contains_key("family_name"));
+
+        let second_request = reader
+            .new_request(
+                serde_json::from_value(json!({ NAMESPACE: { "given_name": false } })).unwrap(),
+            )
+            .unwrap();
+        let requested_items = match holder.handle_request(&second_request).unwrap() {
+            device::SessionEvent::Request(requested_items) => requested_items,
+            event => panic!("unexpected event: {event:?}"),
+        };
+        holder
+            .prepare_response(&requested_items, permitted)
+            .unwrap();
+        let second_response = sign_response(&mut holder);
+        let parsed = match reader.handle_response(&second_response).unwrap() {
+            reader::SessionEvent::Response(parsed) => parsed,
+            event => panic!("unexpected event: {event:?}"),
+        };
+        assert_eq!(
+            parsed.issuer_signed[NAMESPACE]["given_name"],
+            json!("Alice")
+        );
+        assert!(!parsed.issuer_signed[NAMESPACE].contains_key("family_name"));
+    }
+
+    fn permitted() -> device::PermittedItems {
+        serde_json::from_value(json!({ DOC_TYPE: { NAMESPACE: ["family_name"] } })).unwrap()
+    }
+
+    #[test]
+    fn replayed_request_terminates_session() {
+        let requested = json!({ NAMESPACE: { "family_name": false } });
+        let (mut holder, mut reader, requested_items) =
+            establish_session(documents(None), requested.clone());
+        holder
+            .prepare_response(&requested_items, permitted())
+            .unwrap();
+        reader.handle_response(&sign_response(&mut holder)).unwrap();
+
+        let request = reader
+            .new_request(serde_json::from_value(requested).unwrap())
+            .unwrap();
+        holder.handle_request(&request).unwrap();
+        let error = holder.handle_request(&request).unwrap_err();
+        let Some(device::Error::Decryption(DecryptionError::Replayed(2), message)) =
+            error.downcast_ref::<device::Error>()
+        else {
+            panic!("unexpected error: {error:?}");
+        };
+        assert!(holder.is_terminated());
+        assert!(holder.handle_request(&request).is_err());
+
+        assert_eq!(
+            reader.handle_response(message).unwrap(),
+            reader::SessionEvent::Error(Status::SessionEncryptionError)
+        );
+        assert!(reader.is_terminated());
+    }
+
+    #[test]
+    fn replayed_response_terminates_session() {
+        let requested = json!({ NAMESPACE: { "family_name": false } });
+        let (mut holder, mut reader, requested_items) =
+            establish_session(documents(None), requested);
+        holder
+            .prepare_response(&requested_items, permitted())
+            .unwrap();
+        let response = sign_response(&mut holder);
+        reader.handle_response(&response).unwrap();
+
+        let Err(reader::Error::DecryptionError(DecryptionError::Replayed(1), message)) =
+            reader.handle_response(&response)
+        else {
+            panic!("replayed response was not rejected");
+        };
+        assert!(reader.is_terminated());
+        assert!(matches!(
+            reader.handle_response(&response),
+            Err(reader::Error::SessionTerminated)
+        ));
+
+        assert!(matches!(
+            holder.handle_request(&message).unwrap(),
+            device::SessionEvent::Error(Status::SessionEncryptionError)
+        ));
+        assert!(holder.is_terminated());
+    }
+
+    #[test]
+    fn der_device_signature() {
+        let requested = json!({ NAMESPACE: { "family_name": false } });
+        let (mut holder, mut reader, requested_items) =
+            establish_session(documents(None), requested);
+        let permitted: device::PermittedItems = serde_json::from_value(json!({
+            DOC_TYPE: { NAMESPACE: ["family_name"] }
+        }))
+        .unwrap();
+        holder
+            .prepare_response(&requested_items, permitted)
+            .unwrap();
+
+        let signer: SigningKey = device_key().into();
+        let (_, payload) = holder.get_next_signature_payload().unwrap();
+        let signature: Signature = signer.sign(payload);
+
+        // A malformed signature is rejected, leaving the document to be signed again.
+        let error = holder.submit_next_signature(vec![0; 63]).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<device::Error>(),
+            Some(device::Error::Signature(_))
+        ));
+        assert!(holder.get_next_signature_payload().is_some());
+
+        holder
+            .submit_next_signature(signature.to_der().as_bytes().to_vec())
+            .unwrap();
+        let response = holder.retrieve_response().unwrap();
+        let parsed = match reader.handle_response(&response).unwrap() {
+            reader::SessionEvent::Response(parsed) => parsed,
+            event => panic!("unexpected event: {event:?}"),
+        };
+        assert_eq!(
+            parsed.issuer_signed[NAMESPACE]["family_name"],
+            json!("Smith")
+        );
+    }
+
+    #[test]
+    fn request_validation() {
+        let requested = json!({ NAMESPACE: { "family_name": false } });
+        let (_, mut reader, _) = establish_session(documents(None), requested);
+
+        let error = reader
+            .new_request(
+                serde_json::from_value(json!({ NAMESPACE: { "favourite_colour": false } }))
+                    .unwrap(),
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<reader::Error>(),
+            Some(reader::Error::UnregisteredRequest(
+                registry::Error::UnknownElement(_, _)
+            ))
+        ));
+
+        let loyalty: device_request::Namespaces =
+            serde_json::from_value(json!({ "com.example.loyalty.1": { "member_id": false } }))
+                .unwrap();
+        let error = reader
+            .new_request_for_doc_type(
+                "com.example.loyalty.1".to_string(),
+                loyalty.clone(),
+                &Registry::default(),
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<reader::Error>(),
+            Some(reader::Error::UnregisteredRequest(
+                registry::Error::UnknownDocType(_)
+            ))
+        ));
+
+        // A document type which is not built in can be requested once it is registered.
+        let mut registry = Registry::default();
+        registry.register_doc_type(
+            "com.example.loyalty.1",
+            DocTypeSchema::new().required("com.example.loyalty.1"),
+        );
+        let (engaged, qr_code_uri) =
+            device::SessionManagerInit::initialise(documents(None), None, None)
+                .unwrap()
+                .qr_engagement()
+                .unwrap();
+        let (_, request, _) = reader::SessionManager::establish_session_for_doc_type(
+            qr_code_uri,
+            "com.example.loyalty.1".to_string(),
+            loyalty,
+            &registry,
+        )
+        .unwrap();
+        let (_, requested_items) = engaged
+            .process_session_establishment(serde_cbor::from_slice(&request).unwrap())
+            .unwrap();
+        assert_eq!(requested_items[0].doc_type, "com.example.loyalty.1");
+    }
+
+    #[test]
+    fn retention_policy() {
+        let requested: device_request::Namespaces = serde_json::from_value(json!({
+            NAMESPACE: { "family_name": true, "biometric_template_face": true }
+        }))
+        .unwrap();
+        let (engaged, qr_code_uri) =
+            device::SessionManagerInit::initialise(documents(None), None, None)
+                .unwrap()
+                .qr_engagement()
+                .unwrap();
+        assert!(
+            reader::SessionManager::establish_session(qr_code_uri.clone(), requested.clone())
+                .is_err()
+        );
+
+        // A reader with a more permissive registry is refused by the mdoc.
+        let mut permissive = Registry::default();
+        permissive.register_namespace(
+            NAMESPACE,
+            NamespaceSchema::new()
+                .optional("family_name", ElementType::Text)
+                .optional("biometric_template_*", ElementType::Bytes),
+        );
+        let (_, request, _) = reader::SessionManager::establish_session_for_doc_type(
+            qr_code_uri,
+            DOC_TYPE.to_string(),
+            requested,
+            &permissive,
+        )
+        .unwrap();
+        let (holder, requested_items) = engaged
+            .process_session_establishment(serde_cbor::from_slice(&request).unwrap())
+            .unwrap();
+        assert!(requested_items.is_empty());
+        assert!(holder.get_next_signature_payload().is_none());
+    }
+}
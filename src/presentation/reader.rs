@@ -1,12 +1,15 @@
 use crate::definitions::{
     device_engagement::DeviceRetrievalMethod,
     device_request::{self, DeviceRequest, DocRequest, ItemsRequest},
-    helpers::{NonEmptyVec, Tag24},
+    device_response::Document,
+    helpers::{ByteStr, NonEmptyVec, Tag24},
+    namespaces::registry::{self, Registry},
     session::{
         self, create_p256_ephemeral_keys, derive_session_key, get_shared_secret, Handover,
         SessionEstablishment,
     },
-    DeviceEngagement, DeviceResponse, SessionData, SessionTranscript180135,
+    status_list::Status,
+    DeviceEngagement, DeviceResponse, Mso, SessionData, SessionTranscript180135,
 };
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
@@ -16,6 +19,8 @@ use serde_json::Value;
 use std::collections::BTreeMap;
 use uuid::Uuid;
 
+const MDL_DOC_TYPE: &str = "org.iso.18013.5.1.mDL";
+
 #[derive(Serialize, Deserialize)]
 pub struct SessionManager {
     session_transcript: SessionTranscript180135,
@@ -23,6 +28,33 @@ pub struct SessionManager {
     device_message_counter: u32,
     sk_reader: [u8; 32],
     reader_message_counter: u32,
+    #[serde(default)]
+    terminated: bool,
+}
+
+/// Data elements received for a document, keyed by namespace and then by element identifier.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParsedResponse {
+    /// The type of the document, e.g. `org.iso.18013.5.1.mDL`.
+    #[serde(default)]
+    pub doc_type: String,
+    /// Elements authenticated by the issuer.
+    pub issuer_signed: BTreeMap<String, BTreeMap<String, Value>>,
+    /// Elements self-asserted by the mdoc and authenticated only by device authentication.
+    pub device_signed: BTreeMap<String, BTreeMap<String, Value>>,
+    /// Status information from the MSO, which can be checked against a status list token.
+    pub status: Option<Status>,
+}
+
+/// An event arising from a message received from the device.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    /// The device responded, and the session remains open for further requests.
+    Response(ParsedResponse),
+    /// The device ended the session, optionally alongside a final response.
+    Terminated(Option<ParsedResponse>),
+    /// The device reported an error, ending the session.
+    Error(session::Status),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -31,16 +63,18 @@ pub enum Error {
     InvalidQrCode(anyhow::Error),
     #[error("Device did not transmit any data.")]
     DeviceTransmissionError,
-    #[error("Device did not transmit an mDL.")]
+    #[error("Device did not transmit a document of a registered type.")]
     DocumentTypeError,
     #[error("the device did not transmit any mDL data.")]
     NoMdlDataTransmission,
-    #[error("device did not transmit any data in the org.iso.18013.5.1 namespace.")]
+    #[error("device did not transmit any data in a namespace required by the document type.")]
     IncorrectNamespace,
     #[error("device responded with an error.")]
     HolderError,
-    #[error("could not decrypt the response.")]
-    DecryptionError,
+    /// The response could not be decrypted, which ends the session. The second field is the
+    /// SessionData message with status 10 (session encryption error) to send to the device.
+    #[error("could not decrypt the response: {0}")]
+    DecryptionError(session::DecryptionError, Vec<u8>),
     #[error("Unexpected CBOR type for offered value")]
     CborDecodingError,
     #[error("not a valid JSON input.")]
@@ -49,6 +83,16 @@ pub enum Error {
     ParsingError,
     #[error("Request for data is invalid.")]
     InvalidRequest,
+    #[error("request for data is not permitted by the registry: {0}")]
+    UnregisteredRequest(registry::Error),
+    #[error("could not parse the mobile security object.")]
+    MsoParsingError,
+    #[error("device key is not authorized to sign over '{1}' in the {0} namespace.")]
+    UnauthorizedDeviceSignedElement(String, String),
+    #[error("the session has been terminated.")]
+    SessionTerminated,
+    #[error("the device sent data along with status {0:?}.")]
+    UnexpectedData(session::Status),
 }
 
 impl From<serde_cbor::Error> for Error {
@@ -64,9 +108,27 @@ impl From<serde_json::Error> for Error {
 }
 
 impl SessionManager {
+    /// Establish a session, requesting the given data elements of an mDL.
     pub fn establish_session(
         qr_code: String,
         namespaces: device_request::Namespaces,
+    ) -> Result<(Self, Vec<u8>, [u8; 16])> {
+        Self::establish_session_for_doc_type(
+            qr_code,
+            MDL_DOC_TYPE.to_string(),
+            namespaces,
+            &Registry::default(),
+        )
+    }
+
+    /// Establish a session, requesting the given data elements of a document of the given type.
+    ///
+    /// The request is checked against the registry before it is sent.
+    pub fn establish_session_for_doc_type(
+        qr_code: String,
+        doc_type: String,
+        namespaces: device_request::Namespaces,
+        registry: &Registry,
     ) -> Result<(Self, Vec<u8>, [u8; 16])> {
         let device_engagement_bytes =
             Tag24::<DeviceEngagement>::from_qr_code_uri(&qr_code).map_err(Error::InvalidQrCode)?;
@@ -108,9 +170,10 @@ impl SessionManager {
             device_message_counter: 0,
             sk_reader,
             reader_message_counter: 0,
+            terminated: false,
         };
 
-        let request = session_manager.build_request(namespaces)?;
+        let request = session_manager.build_request(doc_type, namespaces, registry)?;
         let session = SessionEstablishment {
             data: request.into(),
             e_reader_key: e_reader_key_public,
@@ -139,8 +202,24 @@ impl SessionManager {
             })
     }
 
+    /// Request further data elements of an mDL.
     pub fn new_request(&mut self, namespaces: device_request::Namespaces) -> Result<Vec<u8>> {
-        let request = self.build_request(namespaces)?;
+        self.new_request_for_doc_type(MDL_DOC_TYPE.to_string(), namespaces, &Registry::default())
+    }
+
+    /// Request further data elements of a document of the given type.
+    ///
+    /// The request is checked against the registry before it is sent.
+    pub fn new_request_for_doc_type(
+        &mut self,
+        doc_type: String,
+        namespaces: device_request::Namespaces,
+        registry: &Registry,
+    ) -> Result<Vec<u8>> {
+        if self.terminated {
+            return Err(Error::SessionTerminated.into());
+        }
+        let request = self.build_request(doc_type, namespaces, registry)?;
         let session = SessionData {
             data: Some(request.into()),
             status: None,
@@ -148,14 +227,17 @@ impl SessionManager {
         serde_cbor::to_vec(&session).map_err(Into::into)
     }
 
-    fn build_request(&mut self, namespaces: device_request::Namespaces) -> Result<Vec<u8>> {
-        // if !validate_request(namespaces.clone()).is_ok() {
-        //     return Err(anyhow::Error::msg(
-        //         "At least one of the namespaces contain an invalid combination of fields to request",
-        //     ));
-        // }
+    fn build_request(
+        &mut self,
+        doc_type: String,
+        namespaces: device_request::Namespaces,
+        registry: &Registry,
+    ) -> Result<Vec<u8>> {
+        registry
+            .validate_request(&doc_type, &namespaces)
+            .map_err(Error::UnregisteredRequest)?;
         let items_request = ItemsRequest {
-            doc_type: "org.iso.18013.5.1.mDL".into(),
+            doc_type,
             namespaces,
             request_info: None,
         };
@@ -176,73 +258,184 @@ impl SessionManager {
         .map_err(|e| anyhow!("unable to encrypt request: {}", e))
     }
 
-    pub fn handle_response(
+    /// End the session, returning the SessionData message to send to the device.
+    ///
+    /// No further messages can be encrypted or decrypted once the session has been terminated.
+    pub fn terminate(&mut self) -> Result<Vec<u8>> {
+        self.terminated = true;
+        let session = SessionData {
+            data: None,
+            status: Some(session::Status::SessionTermination),
+        };
+        serde_cbor::to_vec(&session).map_err(Into::into)
+    }
+
+    /// Identifies that the session has been terminated.
+    pub fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+
+    /// Handle a message from the device.
+    ///
+    /// A message carrying a status code ends the session, and is surfaced as
+    /// [SessionEvent::Terminated] or [SessionEvent::Error]. The final response may accompany
+    /// the session termination status, but data sent along with an error status is rejected.
+    pub fn handle_response(&mut self, response: &[u8]) -> Result<SessionEvent, Error> {
+        self.handle_response_with_registry(response, &Registry::default())
+    }
+
+    /// Handle a message from the device, decoding the first document of a type known to the
+    /// given registry.
+    ///
+    /// The issuer-signed data elements are returned for each namespace of the document type, and
+    /// each namespace which the document type requires must be present.
+    pub fn handle_response_with_registry(
         &mut self,
         response: &[u8],
-    ) -> Result<BTreeMap<String, BTreeMap<String, Value>>, Error> {
+        registry: &Registry,
+    ) -> Result<SessionEvent, Error> {
+        if self.terminated {
+            return Err(Error::SessionTerminated);
+        }
         let session_data: SessionData = serde_cbor::from_slice(response)?;
-        let encrypted_response = match session_data.data {
-            None => return Err(Error::HolderError),
-            Some(r) => r,
-        };
-        let decrypted_response = session::decrypt_device_data(
+        match (session_data.data, session_data.status) {
+            (Some(data), None) => self
+                .decrypt_response(data, registry)
+                .map(SessionEvent::Response),
+            (data, Some(session::Status::SessionTermination)) => {
+                let response = data.map(|d| self.decrypt_response(d, registry)).transpose();
+                self.terminated = true;
+                response.map(SessionEvent::Terminated)
+            }
+            (Some(_), Some(status)) => {
+                self.terminated = true;
+                Err(Error::UnexpectedData(status))
+            }
+            (None, Some(status)) => {
+                self.terminated = true;
+                Ok(SessionEvent::Error(status))
+            }
+            (None, None) => Err(Error::HolderError),
+        }
+    }
+
+    fn decrypt_response(
+        &mut self,
+        encrypted_response: ByteStr,
+        registry: &Registry,
+    ) -> Result<ParsedResponse, Error> {
+        let decrypted_response = match session::decrypt_device_data(
             &self.sk_device.into(),
             encrypted_response.as_ref(),
             &mut self.device_message_counter,
-        )
-        .map_err(|_e| Error::DecryptionError)?;
+        ) {
+            Ok(decrypted_response) => decrypted_response,
+            Err(e) => {
+                self.terminated = true;
+                let session_data = SessionData {
+                    data: None,
+                    status: Some(session::Status::SessionEncryptionError),
+                };
+                return Err(Error::DecryptionError(
+                    e,
+                    serde_cbor::to_vec(&session_data)?,
+                ));
+            }
+        };
         let response: DeviceResponse = serde_cbor::from_slice(&decrypted_response)?;
-        let mut core_namespace = BTreeMap::<String, serde_json::Value>::new();
-        let mut aamva_namespace = BTreeMap::<String, serde_json::Value>::new();
-        let mut parsed_response = BTreeMap::<String, BTreeMap<String, serde_json::Value>>::new();
+        let mut parsed_response = ParsedResponse::default();
 
-        let mut namespaces = response
+        let document = response
             .documents
             .ok_or(Error::DeviceTransmissionError)?
             .into_inner()
             .into_iter()
-            .find(|doc| doc.doc_type == "org.iso.18013.5.1.mDL")
-            .ok_or(Error::DocumentTypeError)?
+            .find(|doc| registry.doc_type(&doc.doc_type).is_some())
+            .ok_or(Error::DocumentTypeError)?;
+        let doc_type_schema = registry
+            .doc_type(&document.doc_type)
+            .ok_or(Error::DocumentTypeError)?;
+        parsed_response.doc_type = document.doc_type.clone();
+
+        let mso: Tag24<Mso> = document
+            .issuer_signed
+            .issuer_auth
+            .payload()
+            .ok_or(Error::MsoParsingError)
+            .and_then(|bytes| serde_cbor::from_slice(bytes).map_err(|_| Error::MsoParsingError))?;
+        let mso = mso.into_inner();
+        check_device_key_authorizations(&mso, &document)?;
+        parsed_response.status = mso.status;
+
+        let mut namespaces = document
             .issuer_signed
             .namespaces
             .ok_or(Error::NoMdlDataTransmission)?
             .into_inner();
 
-        namespaces
-            .remove("org.iso.18013.5.1")
-            .ok_or(Error::IncorrectNamespace)?
-            .into_inner()
-            .into_iter()
-            .map(|item| item.into_inner())
-            .for_each(|item| {
-                let value = parse_response(item.element_value.clone());
-                if let Ok(val) = value {
-                    core_namespace.insert(item.element_identifier, val);
-                }
-            });
-
-        parsed_response.insert("org.iso.18013.5.1".to_string(), core_namespace);
-
-        if let Some(aamva_response) = namespaces.remove("org.iso.18013.5.1.aamva") {
-            aamva_response
+        for (namespace, required) in doc_type_schema.namespaces() {
+            let items = match namespaces.remove(namespace) {
+                Some(items) => items,
+                None if *required => return Err(Error::IncorrectNamespace),
+                None => continue,
+            };
+            let elements = items
                 .into_inner()
                 .into_iter()
                 .map(|item| item.into_inner())
-                .for_each(|item| {
-                    let value = parse_response(item.element_value.clone());
-                    if let Ok(val) = value {
-                        aamva_namespace.insert(item.element_identifier, val);
-                    }
-                });
-
-            parsed_response.insert("org.iso.18013.5.1.aamva".to_string(), aamva_namespace);
+                .filter_map(|item| {
+                    parse_response(item.element_value)
+                        .ok()
+                        .map(|value| (item.element_identifier, value))
+                })
+                .collect();
+            parsed_response
+                .issuer_signed
+                .insert(namespace.clone(), elements);
         }
 
+        parsed_response.device_signed = document
+            .device_signed
+            .namespaces
+            .into_inner()
+            .into_iter()
+            .map(|(namespace, elements)| {
+                let elements = elements
+                    .into_inner()
+                    .into_iter()
+                    .filter_map(|(element_identifier, element_value)| {
+                        parse_response(element_value)
+                            .ok()
+                            .map(|value| (element_identifier, value))
+                    })
+                    .collect();
+                (namespace, elements)
+            })
+            .collect();
+
         Ok(parsed_response)
     }
 }
 
-fn parse_response(value: CborValue) -> Result<Value, Error> {
+/// Check that every device-signed element was authorized by the key authorizations in the MSO.
+pub(crate) fn check_device_key_authorizations(mso: &Mso, document: &Document) -> Result<(), Error> {
+    let device_key_info = &mso.device_key_info;
+
+    for (namespace, elements) in document.device_signed.namespaces.as_ref() {
+        for element_identifier in elements.keys() {
+            if !device_key_info.permitted(namespace, element_identifier) {
+                return Err(Error::UnauthorizedDeviceSignedElement(
+                    namespace.clone(),
+                    element_identifier.clone(),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn parse_response(value: CborValue) -> Result<Value, Error> {
     match value {
         CborValue::Text(s) => Ok(Value::String(s)),
         CborValue::Tag(_t, v) => {
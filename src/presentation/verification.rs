@@ -0,0 +1,1132 @@
+//! Offline verification of presented mdocs, for diagnosing a `DeviceResponse` or `IssuerSigned`
+//! structure captured from a presentation.
+//!
+//! Unlike [reader::SessionManager](super::reader::SessionManager), which stops at the first
+//! problem it finds, verification runs every check that it can and reports the outcome of each,
+//! so that a single run shows everything that is wrong with a document.
+use crate::{
+    definitions::{
+        device_key::cose_key::{CoseKey, EC2Curve, OKPCurve, EC2Y},
+        device_response::Document,
+        device_signed::{DeviceAuth, DeviceAuthentication, DeviceNamespaces},
+        helpers::Tag24,
+        issuer_signed::IssuerSigned,
+        session::SessionTranscript180135,
+        DeviceResponse, Mso,
+    },
+    issuance::{
+        mdoc::{digest, verify_issuer_auth, P521VerifyingKey},
+        x5chain::{X5Chain, X5CHAIN_HEADER_LABEL},
+    },
+    presentation::reader::{check_device_key_authorizations, parse_response},
+};
+use anyhow::{anyhow, Result};
+use cose_rs::{
+    algorithm::{Algorithm, SignatureAlgorithm},
+    sign1::{CoseSign1, VerificationResult},
+};
+use serde::Serialize;
+use serde_cbor::Value as CborValue;
+use serde_json::Value;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use signature::{hazmat::PrehashVerifier, Verifier};
+use std::collections::{BTreeMap, HashSet};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use x509_cert::{
+    certificate::Certificate,
+    der::{Decode, Encode},
+    ext::pkix::{BasicConstraints, ExtendedKeyUsage, KeyUsage},
+    spki::ObjectIdentifier,
+};
+
+const ECDSA_WITH_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const ECDSA_WITH_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+const ECDSA_WITH_SHA512: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.4");
+const COUNTRY_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.6");
+/// id-mdl-kp-mdlDS, the extended key usage of a document signer.
+const MDL_DS: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.0.18013.5.1.2");
+
+#[derive(Debug, Clone, Default)]
+/// The IACA certificates trusted to issue document signer certificates.
+pub struct TrustStore {
+    certificates: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "result", content = "reason", rename_all = "snake_case")]
+/// The outcome of a single check.
+pub enum Check {
+    Valid,
+    Invalid(String),
+    /// The check could not be performed, for example because an input it needs was not given.
+    Skipped(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// The outcome of verifying every document in a `DeviceResponse`.
+pub struct Report {
+    /// The status code of the response.
+    pub status: u64,
+    pub documents: Vec<DocumentReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// The outcome of verifying a single document.
+pub struct DocumentReport {
+    pub doc_type: String,
+    /// Whether the MSO signature verifies with the key of the document signer certificate.
+    pub issuer_signature: Check,
+    /// Whether the x5chain leads to a trusted IACA certificate, with every certificate valid.
+    pub certificate_chain: Check,
+    /// The subject of the document signer certificate.
+    pub document_signer: Option<String>,
+    /// Whether the MSO could be decoded, and is consistent with the document.
+    pub mso: Check,
+    pub validity: Validity,
+    /// Whether each disclosed issuer-signed element matches its digest in the MSO.
+    pub digests: BTreeMap<String, BTreeMap<String, Check>>,
+    /// Whether the device authentication verifies with the device key in the MSO.
+    pub device_auth: Check,
+    pub issuer_signed: BTreeMap<String, BTreeMap<String, Value>>,
+    pub device_signed: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// The validity information of the MSO, and whether it covers the time of verification.
+pub struct Validity {
+    pub check: Check,
+    pub signed: Option<String>,
+    pub valid_from: Option<String>,
+    pub valid_until: Option<String>,
+    pub expected_update: Option<String>,
+}
+
+impl TrustStore {
+    pub fn with_pem(self, data: &[u8]) -> Result<Self> {
+        let bytes = pem_rfc7468::decode_vec(data)
+            .map_err(|e| anyhow!("unable to parse pem: {}", e))?
+            .1;
+        self.with_der(&bytes)
+    }
+
+    pub fn with_der(mut self, data: &[u8]) -> Result<Self> {
+        Certificate::from_der(data)
+            .map_err(|e| anyhow!("unable to parse certificate from der encoding: {}", e))?;
+        self.certificates.push(data.to_vec());
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.certificates.is_empty()
+    }
+}
+
+impl Check {
+    fn from_result(result: Result<()>) -> Self {
+        match result {
+            Ok(()) => Self::Valid,
+            Err(e) => Self::Invalid(e.to_string()),
+        }
+    }
+
+    pub fn is_invalid(&self) -> bool {
+        matches!(self, Self::Invalid(_))
+    }
+}
+
+impl Report {
+    /// Whether no check failed for any document.
+    pub fn is_valid(&self) -> bool {
+        self.documents.iter().all(DocumentReport::is_valid)
+    }
+}
+
+impl DocumentReport {
+    /// Whether no check failed. Skipped checks are not failures.
+    pub fn is_valid(&self) -> bool {
+        ![
+            &self.issuer_signature,
+            &self.certificate_chain,
+            &self.mso,
+            &self.validity.check,
+            &self.device_auth,
+        ]
+        .into_iter()
+        .chain(self.digests.values().flat_map(BTreeMap::values))
+        .any(Check::is_invalid)
+    }
+}
+
+/// Verify every document of a `DeviceResponse`, as at the given time.
+///
+/// Device authentication is only checked if the session transcript is given.
+pub fn verify_device_response(
+    response: &DeviceResponse,
+    session_transcript: Option<&SessionTranscript180135>,
+    trust_store: &TrustStore,
+    at: OffsetDateTime,
+) -> Report {
+    Report {
+        status: response.status.clone().into(),
+        documents: response
+            .documents
+            .iter()
+            .flat_map(|documents| documents.iter())
+            .map(|document| verify_document(document, session_transcript, trust_store, at))
+            .collect(),
+    }
+}
+
+/// Verify a document of a `DeviceResponse`, as at the given time.
+pub fn verify_document(
+    document: &Document,
+    session_transcript: Option<&SessionTranscript180135>,
+    trust_store: &TrustStore,
+    at: OffsetDateTime,
+) -> DocumentReport {
+    let (mut report, mso) = check_issuer_signed(
+        Some(&document.doc_type),
+        &document.issuer_signed,
+        trust_store,
+        at,
+    );
+    report.device_signed = claims(document.device_signed.namespaces.as_ref());
+    report.device_auth = match (&mso, session_transcript) {
+        (None, _) => Check::Skipped("the MSO could not be decoded".to_string()),
+        (_, None) => Check::Skipped("no session transcript was given".to_string()),
+        (Some(mso), Some(session_transcript)) => match &document.device_signed.device_auth {
+            DeviceAuth::Signature { .. } => {
+                match DeviceVerifyingKey::from_cose_key(&mso.device_key_info.device_key) {
+                    Ok(Some(device_key)) => Check::from_result(verify_device_signature(
+                        document,
+                        mso,
+                        &device_key,
+                        session_transcript,
+                    )),
+                    Ok(None) => Check::Skipped(format!(
+                        "verifying device signatures made with {} keys is not supported",
+                        match &mso.device_key_info.device_key {
+                            CoseKey::EC2 { crv, .. } => format!("{crv:?}"),
+                            CoseKey::OKP { crv, .. } => format!("{crv:?}"),
+                        }
+                    )),
+                    Err(e) => Check::Invalid(format!("unable to parse device key: {e}")),
+                }
+            }
+            DeviceAuth::Mac { .. } => Check::Skipped(
+                "verifying a device MAC requires the reader's ephemeral private key".to_string(),
+            ),
+        },
+    };
+    report
+}
+
+/// Verify an `IssuerSigned` structure, as at the given time.
+pub fn verify_issuer_signed(
+    issuer_signed: &IssuerSigned,
+    trust_store: &TrustStore,
+    at: OffsetDateTime,
+) -> DocumentReport {
+    let (mut report, _) = check_issuer_signed(None, issuer_signed, trust_store, at);
+    report.device_auth =
+        Check::Skipped("an IssuerSigned structure has no device authentication".to_string());
+    report
+}
+
+fn check_issuer_signed(
+    doc_type: Option<&str>,
+    issuer_signed: &IssuerSigned,
+    trust_store: &TrustStore,
+    at: OffsetDateTime,
+) -> (DocumentReport, Option<Mso>) {
+    let issuer_auth = &issuer_signed.issuer_auth;
+    let x5chain = issuer_auth
+        .unprotected()
+        .get_i(X5CHAIN_HEADER_LABEL)
+        .ok_or_else(|| anyhow!("issuer signature has no x5chain"))
+        .and_then(X5Chain::from_cbor);
+    let certificate_chain = match &x5chain {
+        Ok(_) if trust_store.is_empty() => {
+            Check::Skipped("no trusted IACA certificates were given".to_string())
+        }
+        Ok(x5chain) => Check::from_result(verify_chain(x5chain, trust_store, at)),
+        Err(e) => Check::Invalid(e.to_string()),
+    };
+    let document_signer = x5chain.ok().and_then(|x5chain| {
+        let der = x5chain.certificates().next()?;
+        let certificate = Certificate::from_der(der).ok()?;
+        Some(certificate.tbs_certificate.subject.to_string())
+    });
+
+    let issuer_signed_claims = issuer_signed
+        .namespaces
+        .iter()
+        .flat_map(|namespaces| namespaces.iter())
+        .map(|(namespace, items)| {
+            let elements = items
+                .iter()
+                .map(|item| {
+                    let item = item.as_ref();
+                    (
+                        item.element_identifier.clone(),
+                        claim(item.element_value.clone()),
+                    )
+                })
+                .collect();
+            (namespace.clone(), elements)
+        })
+        .collect();
+
+    let mut report = DocumentReport {
+        doc_type: doc_type.unwrap_or_default().to_string(),
+        issuer_signature: Check::from_result(verify_issuer_auth(issuer_auth)),
+        certificate_chain,
+        document_signer,
+        mso: Check::Valid,
+        validity: Validity {
+            check: Check::Skipped("the MSO could not be decoded".to_string()),
+            signed: None,
+            valid_from: None,
+            valid_until: None,
+            expected_update: None,
+        },
+        digests: BTreeMap::new(),
+        device_auth: Check::Skipped("the MSO could not be decoded".to_string()),
+        issuer_signed: issuer_signed_claims,
+        device_signed: BTreeMap::new(),
+    };
+
+    let mso = match decode_mso(issuer_signed) {
+        Ok(mso) => mso,
+        Err(e) => {
+            report.mso = Check::Invalid(e.to_string());
+            return (report, None);
+        }
+    };
+    match doc_type {
+        Some(doc_type) if doc_type != mso.doc_type => {
+            report.mso = Check::Invalid(format!(
+                "docType of the MSO '{}' does not match the document '{doc_type}'",
+                mso.doc_type
+            ))
+        }
+        Some(_) => {}
+        None => report.doc_type = mso.doc_type.clone(),
+    }
+
+    report.validity = check_validity(&mso, at);
+    report.digests = check_digests(&mso, issuer_signed);
+    (report, Some(mso))
+}
+
+fn decode_mso(issuer_signed: &IssuerSigned) -> Result<Mso> {
+    let mso: Tag24<Mso> = serde_cbor::from_slice(
+        issuer_signed
+            .issuer_auth
+            .payload()
+            .ok_or_else(|| anyhow!("issuer signature has no payload"))?,
+    )
+    .map_err(|e| anyhow!("unable to decode the signed MSO: {}", e))?;
+    Ok(mso.into_inner())
+}
+
+fn check_validity(mso: &Mso, at: OffsetDateTime) -> Validity {
+    let validity_info = &mso.validity_info;
+    let format = |time: OffsetDateTime| time.format(&Rfc3339).ok();
+    let check = if at < validity_info.valid_from {
+        Check::Invalid("the MSO is not yet valid".to_string())
+    } else if at > validity_info.valid_until {
+        Check::Invalid("the MSO has expired".to_string())
+    } else {
+        Check::Valid
+    };
+    Validity {
+        check,
+        signed: format(validity_info.signed),
+        valid_from: format(validity_info.valid_from),
+        valid_until: format(validity_info.valid_until),
+        expected_update: validity_info.expected_update.and_then(format),
+    }
+}
+
+fn check_digests(
+    mso: &Mso,
+    issuer_signed: &IssuerSigned,
+) -> BTreeMap<String, BTreeMap<String, Check>> {
+    issuer_signed
+        .namespaces
+        .iter()
+        .flat_map(|namespaces| namespaces.iter())
+        .map(|(namespace, items)| {
+            let digests = mso.value_digests.get(namespace);
+            let mut digest_ids = HashSet::new();
+            let checks = items
+                .iter()
+                .map(|item| {
+                    let digest_id = item.as_ref().digest_id;
+                    let check = match digests.and_then(|digests| digests.get(&digest_id)) {
+                        _ if !digest_ids.insert(digest_id) => {
+                            Check::Invalid("the digest ID is used more than once".to_string())
+                        }
+                        None => Check::Invalid("the MSO has no digest with this ID".to_string()),
+                        Some(expected) => match serde_cbor::to_vec(item) {
+                            Ok(bytes)
+                                if digest(mso.digest_algorithm, &bytes) == expected.as_ref() =>
+                            {
+                                Check::Valid
+                            }
+                            Ok(_) => {
+                                Check::Invalid("the digest does not match the MSO".to_string())
+                            }
+                            Err(e) => Check::Invalid(format!("unable to encode the element: {e}")),
+                        },
+                    };
+                    (item.as_ref().element_identifier.clone(), check)
+                })
+                .collect();
+            (namespace.clone(), checks)
+        })
+        .collect()
+}
+
+fn verify_device_signature(
+    document: &Document,
+    mso: &Mso,
+    device_key: &DeviceVerifyingKey,
+    session_transcript: &SessionTranscript180135,
+) -> Result<()> {
+    let device_signature = match &document.device_signed.device_auth {
+        DeviceAuth::Signature { device_signature } => device_signature,
+        DeviceAuth::Mac { .. } => return Err(anyhow!("the document is authenticated with a MAC")),
+    };
+    check_device_key_authorizations(mso, document)?;
+
+    let device_authentication = Tag24::new(DeviceAuthentication::new(
+        session_transcript.clone(),
+        document.doc_type.clone(),
+        document.device_signed.namespaces.clone(),
+    ))?;
+    let device_authentication_bytes = serde_cbor::to_vec(&device_authentication)?;
+
+    match device_key.verify(device_signature, device_authentication_bytes) {
+        VerificationResult::Success => Ok(()),
+        VerificationResult::Failure(reason) => {
+            Err(anyhow!("device signature is invalid: {reason}"))
+        }
+        VerificationResult::Error(e) => Err(anyhow!("unable to verify device signature: {}", e)),
+    }
+}
+
+/// A device key that device signatures can be verified with.
+enum DeviceVerifyingKey {
+    P256(p256::ecdsa::VerifyingKey),
+    P384(p384::ecdsa::VerifyingKey),
+    P521(P521VerifyingKey),
+    Ed25519(Ed25519VerifyingKey),
+}
+
+impl DeviceVerifyingKey {
+    /// Parse a device key, or return `None` if its curve is not supported.
+    fn from_cose_key(device_key: &CoseKey) -> Result<Option<Self>> {
+        let key = match device_key {
+            CoseKey::EC2 { crv, x, y } => {
+                let mut sec1 = match y {
+                    EC2Y::Value(_) => vec![4],
+                    EC2Y::SignBit(sign) => vec![2 + u8::from(*sign)],
+                };
+                sec1.extend_from_slice(x);
+                if let EC2Y::Value(y) = y {
+                    sec1.extend_from_slice(y);
+                }
+                match crv {
+                    EC2Curve::P256 => {
+                        Self::P256(p256::ecdsa::VerifyingKey::from_sec1_bytes(&sec1)?)
+                    }
+                    EC2Curve::P384 => {
+                        Self::P384(p384::ecdsa::VerifyingKey::from_sec1_bytes(&sec1)?)
+                    }
+                    EC2Curve::P521 => Self::P521(P521VerifyingKey(
+                        p521::ecdsa::VerifyingKey::from_sec1_bytes(&sec1)?,
+                    )),
+                    EC2Curve::P256K => return Ok(None),
+                }
+            }
+            CoseKey::OKP {
+                crv: OKPCurve::Ed25519,
+                x,
+            } => {
+                let x = x
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow!("expected a 32 byte Ed25519 public key"))?;
+                Self::Ed25519(Ed25519VerifyingKey(
+                    ed25519_dalek::VerifyingKey::from_bytes(x)?,
+                ))
+            }
+            CoseKey::OKP { .. } => return Ok(None),
+        };
+        Ok(Some(key))
+    }
+
+    fn verify(&self, device_signature: &CoseSign1, payload: Vec<u8>) -> VerificationResult {
+        match self {
+            Self::P256(key) => {
+                device_signature.verify::<_, p256::ecdsa::Signature>(key, Some(payload), None)
+            }
+            Self::P384(key) => {
+                device_signature.verify::<_, p384::ecdsa::Signature>(key, Some(payload), None)
+            }
+            Self::P521(key) => {
+                device_signature.verify::<_, p521::ecdsa::Signature>(key, Some(payload), None)
+            }
+            Self::Ed25519(key) => {
+                device_signature.verify::<_, ed25519_dalek::Signature>(key, Some(payload), None)
+            }
+        }
+    }
+}
+
+/// An Ed25519 verifying key, which does not itself declare its COSE algorithm.
+struct Ed25519VerifyingKey(ed25519_dalek::VerifyingKey);
+
+impl Verifier<ed25519_dalek::Signature> for Ed25519VerifyingKey {
+    fn verify(&self, msg: &[u8], signature: &ed25519_dalek::Signature) -> signature::Result<()> {
+        self.0.verify(msg, signature)
+    }
+}
+
+impl SignatureAlgorithm for Ed25519VerifyingKey {
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::EdDSA
+    }
+}
+
+/// Check that the x5chain leads to a trusted IACA certificate, that every certificate is valid
+/// at the given time, and that the certificates follow the profiles of ISO/IEC 18013-5 Annex B.
+fn verify_chain(x5chain: &X5Chain, trust_store: &TrustStore, at: OffsetDateTime) -> Result<()> {
+    let mut chain = x5chain
+        .certificates()
+        .map(parse_certificate)
+        .collect::<Result<Vec<_>>>()?;
+    for certificate in &chain {
+        check_certificate_validity(certificate, at)?;
+    }
+    for pair in chain.windows(2) {
+        verify_certificate_signature(&pair[0], &pair[1])?;
+    }
+
+    // Can unwrap as an X5Chain is never empty.
+    let last = x5chain.certificates().last().unwrap();
+    if !trust_store.certificates.iter().any(|der| der == last) {
+        let last = chain.last().unwrap();
+        let iaca = trust_store
+            .certificates
+            .iter()
+            .map(|der| parse_certificate(der))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|iaca| iaca.tbs_certificate.subject == last.tbs_certificate.issuer)
+            .find(|iaca| verify_certificate_signature(last, iaca).is_ok())
+            .ok_or_else(|| {
+                anyhow!(
+                    "no trusted IACA certificate issued the certificate '{}'",
+                    last.tbs_certificate.subject
+                )
+            })?;
+        check_certificate_validity(&iaca, at)?;
+        chain.push(iaca);
+    }
+
+    check_document_signer(&chain[0])?;
+    // Every issuer must be a CA whose path length constraint permits the CAs below it.
+    for (depth, issuer) in chain.iter().enumerate().skip(1) {
+        check_ca(issuer, depth - 1)?;
+    }
+    let document_signer_country = country(&chain[0])?;
+    // Can unwrap as the chain is never empty.
+    let iaca_country = country(chain.last().unwrap())?;
+    if document_signer_country != iaca_country {
+        return Err(anyhow!(
+            "the country of the document signer '{}' does not match the country of the IACA '{}'",
+            String::from_utf8_lossy(document_signer_country),
+            String::from_utf8_lossy(iaca_country)
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a certificate is a document signer, as per Table B.3.
+fn check_document_signer(certificate: &Certificate) -> Result<()> {
+    let subject = &certificate.tbs_certificate.subject;
+    if basic_constraints(certificate)?.is_some_and(|constraints| constraints.ca) {
+        return Err(anyhow!(
+            "the document signer certificate '{subject}' must not be a CA"
+        ));
+    }
+    if !key_usage(certificate)?.is_some_and(|usage| usage.digital_signature()) {
+        return Err(anyhow!(
+            "the certificate '{subject}' is not permitted to sign documents"
+        ));
+    }
+    let extended_key_usage = certificate
+        .tbs_certificate
+        .get::<ExtendedKeyUsage>()
+        .map_err(|e| anyhow!("unable to parse extended key usage of '{subject}': {}", e))?;
+    if !extended_key_usage.is_some_and(|(_, usage)| usage.0.contains(&MDL_DS)) {
+        return Err(anyhow!(
+            "the certificate '{subject}' is not a document signer certificate"
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a certificate is a CA that may sign certificates, with `depth` CAs below it in the
+/// chain.
+fn check_ca(certificate: &Certificate, depth: usize) -> Result<()> {
+    let subject = &certificate.tbs_certificate.subject;
+    let constraints = basic_constraints(certificate)?
+        .filter(|constraints| constraints.ca)
+        .ok_or_else(|| anyhow!("the issuer certificate '{subject}' is not a CA"))?;
+    if let Some(path_len) = constraints.path_len_constraint {
+        if depth > path_len as usize {
+            return Err(anyhow!(
+                "the certificate chain exceeds the path length constraint of '{subject}'"
+            ));
+        }
+    }
+    if !key_usage(certificate)?.is_some_and(|usage| usage.key_cert_sign()) {
+        return Err(anyhow!(
+            "the certificate '{subject}' is not permitted to sign certificates"
+        ));
+    }
+    Ok(())
+}
+
+fn basic_constraints(certificate: &Certificate) -> Result<Option<BasicConstraints>> {
+    Ok(certificate
+        .tbs_certificate
+        .get::<BasicConstraints>()
+        .map_err(|e| {
+            anyhow!(
+                "unable to parse basic constraints of '{}': {}",
+                certificate.tbs_certificate.subject,
+                e
+            )
+        })?
+        .map(|(_, constraints)| constraints))
+}
+
+fn key_usage(certificate: &Certificate) -> Result<Option<KeyUsage>> {
+    Ok(certificate
+        .tbs_certificate
+        .get::<KeyUsage>()
+        .map_err(|e| {
+            anyhow!(
+                "unable to parse key usage of '{}': {}",
+                certificate.tbs_certificate.subject,
+                e
+            )
+        })?
+        .map(|(_, usage)| usage))
+}
+
+/// The country name in the subject of a certificate.
+fn country(certificate: &Certificate) -> Result<&[u8]> {
+    certificate
+        .tbs_certificate
+        .subject
+        .0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .find(|attribute| attribute.oid == COUNTRY_NAME)
+        .map(|attribute| attribute.value.value())
+        .ok_or_else(|| {
+            anyhow!(
+                "the certificate '{}' has no country name",
+                certificate.tbs_certificate.subject
+            )
+        })
+}
+
+fn parse_certificate(der: &[u8]) -> Result<Certificate> {
+    Certificate::from_der(der)
+        .map_err(|e| anyhow!("unable to parse certificate from der encoding: {}", e))
+}
+
+fn check_certificate_validity(certificate: &Certificate, at: OffsetDateTime) -> Result<()> {
+    let validity = &certificate.tbs_certificate.validity;
+    let not_before = OffsetDateTime::UNIX_EPOCH + validity.not_before.to_unix_duration();
+    let not_after = OffsetDateTime::UNIX_EPOCH + validity.not_after.to_unix_duration();
+    let subject = &certificate.tbs_certificate.subject;
+    if at < not_before {
+        return Err(anyhow!("the certificate '{subject}' is not yet valid"));
+    }
+    if at > not_after {
+        return Err(anyhow!("the certificate '{subject}' has expired"));
+    }
+    Ok(())
+}
+
+fn verify_certificate_signature(certificate: &Certificate, issuer: &Certificate) -> Result<()> {
+    let tbs_certificate = certificate
+        .tbs_certificate
+        .to_der()
+        .map_err(|e| anyhow!("unable to encode certificate: {}", e))?;
+    let oid = certificate.signature_algorithm.oid;
+    let prehash = if oid == ECDSA_WITH_SHA256 {
+        Sha256::digest(&tbs_certificate).to_vec()
+    } else if oid == ECDSA_WITH_SHA384 {
+        Sha384::digest(&tbs_certificate).to_vec()
+    } else if oid == ECDSA_WITH_SHA512 {
+        Sha512::digest(&tbs_certificate).to_vec()
+    } else {
+        return Err(anyhow!(
+            "unsupported certificate signature algorithm: {oid}"
+        ));
+    };
+
+    let public_key = issuer
+        .tbs_certificate
+        .subject_public_key_info
+        .subject_public_key
+        .raw_bytes();
+    let signature = certificate.signature.raw_bytes();
+    // The curve is identified by the length of the uncompressed SEC1 point.
+    let result = match public_key.len() {
+        65 => p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key).and_then(|key| {
+            key.verify_prehash(&prehash, &p256::ecdsa::Signature::from_der(signature)?)
+        }),
+        97 => p384::ecdsa::VerifyingKey::from_sec1_bytes(public_key).and_then(|key| {
+            key.verify_prehash(&prehash, &p384::ecdsa::Signature::from_der(signature)?)
+        }),
+        133 => p521::ecdsa::VerifyingKey::from_sec1_bytes(public_key).and_then(|key| {
+            key.verify_prehash(&prehash, &p521::ecdsa::Signature::from_der(signature)?)
+        }),
+        len => {
+            return Err(anyhow!(
+                "unsupported public key in issuer certificate '{}': {len} byte SEC1 point",
+                issuer.tbs_certificate.subject
+            ))
+        }
+    };
+    result.map_err(|_| {
+        anyhow!(
+            "the signature of the certificate '{}' does not verify with the key of '{}'",
+            certificate.tbs_certificate.subject,
+            issuer.tbs_certificate.subject
+        )
+    })
+}
+
+fn claims(namespaces: &DeviceNamespaces) -> BTreeMap<String, BTreeMap<String, Value>> {
+    namespaces
+        .iter()
+        .map(|(namespace, elements)| {
+            let elements = elements
+                .iter()
+                .map(|(element_identifier, value)| {
+                    (element_identifier.clone(), claim(value.clone()))
+                })
+                .collect();
+            (namespace.clone(), elements)
+        })
+        .collect()
+}
+
+/// Render an element value as JSON, falling back to CBOR diagnostic-like debug output for values
+/// that have no JSON representation.
+fn claim(value: CborValue) -> Value {
+    let fallback = format!("{value:?}");
+    parse_response(value).unwrap_or(Value::String(fallback))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::{
+        device_engagement::Security,
+        device_key::cose_key::{CoseKey, EC2Curve, EC2Y},
+        device_request::ItemsRequest,
+        helpers::NonEmptyMap,
+        session::{create_p256_ephemeral_keys, Handover},
+        DeviceEngagement, DeviceKeyInfo,
+    };
+    use crate::issuance::{
+        mdoc::test::minimal_test_mdoc_builder,
+        pki::{
+            test::{p521_iaca, p521_key, P521Signer},
+            IssuerAlternativeName,
+        },
+        DocumentSigner, Iaca,
+    };
+    use crate::presentation::device::{self, DeviceSession};
+    use p256::ecdsa::{DerSignature, Signature, SigningKey};
+    use signature::{SignatureEncoding, Signer};
+    use std::str::FromStr;
+    use time::macros::datetime;
+    use x509_cert::{
+        der::asn1::BitString, name::Name, spki::SubjectPublicKeyInfoOwned, TbsCertificate,
+    };
+
+    static DOC_TYPE: &str = "org.iso.18013.5.1.mDL";
+    static NAMESPACE: &str = "org.iso.18013.5.1";
+
+    struct Session {
+        documents: device::Documents,
+        session_transcript: SessionTranscript180135,
+    }
+
+    impl DeviceSession for Session {
+        type ST = SessionTranscript180135;
+
+        fn documents(&self) -> &device::Documents {
+            &self.documents
+        }
+
+        fn session_transcript(&self) -> SessionTranscript180135 {
+            self.session_transcript.clone()
+        }
+    }
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_slice(&[seed; 32]).unwrap()
+    }
+
+    fn iaca(seed: u8) -> Iaca {
+        Iaca::builder()
+            .country("US".to_string())
+            .common_name(format!("Example IACA {seed}"))
+            .issuer_alternative_name(IssuerAlternativeName::Uri(
+                "https://dmv.example.com".to_string(),
+            ))
+            .crl_distribution_point("https://dmv.example.com/crl".to_string())
+            .validity(
+                datetime!(2023-01-01 0:00 UTC),
+                datetime!(2033-01-01 0:00 UTC),
+            )
+            .build::<_, SigningKey, Signature>(key(seed).verifying_key(), key(seed))
+            .unwrap()
+    }
+
+    fn document_signer(iaca: &Iaca) -> DocumentSigner {
+        iaca.document_signer()
+            .common_name("Example Document Signer".to_string())
+            .validity(
+                datetime!(2024-01-01 0:00 UTC),
+                datetime!(2026-01-01 0:00 UTC),
+            )
+            .build::<_, SigningKey, Signature>(key(2).verifying_key(), key(1))
+            .unwrap()
+    }
+
+    fn session_transcript() -> SessionTranscript180135 {
+        let (_, e_device_key) = create_p256_ephemeral_keys().unwrap();
+        let (_, e_reader_key) = create_p256_ephemeral_keys().unwrap();
+        let device_engagement = DeviceEngagement {
+            version: "1.0".into(),
+            security: Security(1, Tag24::new(e_device_key).unwrap()),
+            device_retrieval_methods: None,
+            server_retrieval_methods: None,
+            protocol_info: None,
+        };
+        SessionTranscript180135(
+            Tag24::new(device_engagement).unwrap(),
+            Tag24::new(e_reader_key).unwrap(),
+            Handover::QR,
+        )
+    }
+
+    /// A response disclosing the family name, signed by the document signer of IACA 1.
+    fn device_response(session_transcript: SessionTranscript180135) -> DeviceResponse {
+        let device_key = key(3);
+        let ec = device_key.verifying_key().to_encoded_point(false);
+        device_response_with(
+            session_transcript,
+            CoseKey::EC2 {
+                crv: EC2Curve::P256,
+                x: ec.x().unwrap().to_vec(),
+                y: EC2Y::Value(ec.y().unwrap().to_vec()),
+            },
+            |payload| {
+                let signature: Signature = device_key.sign(payload);
+                signature.to_vec()
+            },
+        )
+    }
+
+    /// A response disclosing the family name, device signed with the given device key.
+    fn device_response_with(
+        session_transcript: SessionTranscript180135,
+        device_key: CoseKey,
+        sign: impl Fn(&[u8]) -> Vec<u8>,
+    ) -> DeviceResponse {
+        let device_key_info = DeviceKeyInfo {
+            device_key,
+            key_authorizations: None,
+            key_info: None,
+        };
+        let validity_info = crate::definitions::ValidityInfo {
+            signed: datetime!(2025-01-01 0:00 UTC),
+            valid_from: datetime!(2025-01-01 0:00 UTC),
+            valid_until: datetime!(2025-07-01 0:00 UTC),
+            expected_update: None,
+        };
+        let mdoc = minimal_test_mdoc_builder()
+            .validity_info(validity_info)
+            .device_key_info(device_key_info)
+            .issue::<SigningKey, Signature>(document_signer(&iaca(1)).x5chain().unwrap(), key(2))
+            .unwrap();
+
+        let session = Session {
+            documents: NonEmptyMap::new(DOC_TYPE.to_string(), mdoc.into()),
+            session_transcript,
+        };
+        let requested = vec![ItemsRequest {
+            doc_type: DOC_TYPE.to_string(),
+            namespaces: serde_json::from_value(
+                serde_json::json!({ NAMESPACE: { "family_name": false } }),
+            )
+            .unwrap(),
+            request_info: None,
+        }];
+        let permitted = [(
+            DOC_TYPE.to_string(),
+            [(NAMESPACE.to_string(), vec!["family_name".to_string()])].into(),
+        )]
+        .into();
+        let mut prepared = session.prepare_response(&requested, permitted);
+        while let Some((_, payload)) = prepared.get_next_signature_payload() {
+            let signature = sign(payload);
+            prepared.submit_next_signature(signature).unwrap();
+        }
+        prepared.finalize_response()
+    }
+
+    fn trust_store(seed: u8) -> TrustStore {
+        TrustStore::default().with_der(iaca(seed).to_der()).unwrap()
+    }
+
+    #[test]
+    fn valid() {
+        let session_transcript = session_transcript();
+        let response = device_response(session_transcript.clone());
+        let report = verify_device_response(
+            &response,
+            Some(&session_transcript),
+            &trust_store(1),
+            datetime!(2025-03-01 0:00 UTC),
+        );
+        assert!(report.is_valid(), "{report:#?}");
+        let document = &report.documents[0];
+        assert_eq!(document.doc_type, DOC_TYPE);
+        assert_eq!(document.certificate_chain, Check::Valid);
+        assert_eq!(document.device_auth, Check::Valid);
+        assert_eq!(document.digests[NAMESPACE]["family_name"], Check::Valid);
+        assert_eq!(
+            document.issuer_signed[NAMESPACE]["family_name"],
+            Value::String("Smith".to_string())
+        );
+    }
+
+    #[test]
+    fn device_key_curves() {
+        let verify = |response: &DeviceResponse, session_transcript: &SessionTranscript180135| {
+            verify_device_response(
+                response,
+                Some(session_transcript),
+                &trust_store(1),
+                datetime!(2025-03-01 0:00 UTC),
+            )
+            .documents[0]
+                .device_auth
+                .clone()
+        };
+
+        let session_transcript = session_transcript();
+        let p384_key = p384::ecdsa::SigningKey::from_slice(&[5; 48]).unwrap();
+        let ec = p384_key.verifying_key().to_encoded_point(false);
+        let response = device_response_with(
+            session_transcript.clone(),
+            CoseKey::EC2 {
+                crv: EC2Curve::P384,
+                x: ec.x().unwrap().to_vec(),
+                y: EC2Y::Value(ec.y().unwrap().to_vec()),
+            },
+            |payload| {
+                let signature: p384::ecdsa::Signature = p384_key.sign(payload);
+                signature.to_vec()
+            },
+        );
+        assert_eq!(verify(&response, &session_transcript), Check::Valid);
+
+        let p521_signer = P521Signer(p521_key());
+        let ec = p521::ecdsa::VerifyingKey::from(&p521_signer.0).to_encoded_point(false);
+        let response = device_response_with(
+            session_transcript.clone(),
+            CoseKey::EC2 {
+                crv: EC2Curve::P521,
+                x: ec.x().unwrap().to_vec(),
+                y: EC2Y::Value(ec.y().unwrap().to_vec()),
+            },
+            |payload| {
+                let signature: p521::ecdsa::Signature = p521_signer.sign(payload);
+                signature.to_vec()
+            },
+        );
+        assert_eq!(verify(&response, &session_transcript), Check::Valid);
+
+        let ed25519_key = ed25519_dalek::SigningKey::from_bytes(&[6; 32]);
+        let device_key = CoseKey::OKP {
+            crv: OKPCurve::Ed25519,
+            x: ed25519_key.verifying_key().to_bytes().to_vec(),
+        };
+        let response =
+            device_response_with(session_transcript.clone(), device_key.clone(), |payload| {
+                ed25519_key.sign(payload).to_bytes().to_vec()
+            });
+        assert_eq!(verify(&response, &session_transcript), Check::Valid);
+        // A signature over a different session transcript does not verify.
+        assert!(verify(&response, &self::session_transcript()).is_invalid());
+
+        let response = device_response_with(
+            session_transcript.clone(),
+            CoseKey::OKP {
+                crv: OKPCurve::Ed448,
+                x: vec![0; 57],
+            },
+            |_| vec![0; 114],
+        );
+        assert!(matches!(
+            verify(&response, &session_transcript),
+            Check::Skipped(_)
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        let session_transcript = session_transcript();
+        let response = device_response(session_transcript.clone());
+
+        let report = verify_device_response(
+            &response,
+            Some(&self::session_transcript()),
+            &trust_store(4),
+            datetime!(2025-08-01 0:00 UTC),
+        );
+        assert!(!report.is_valid());
+        let document = &report.documents[0];
+        assert_eq!(document.issuer_signature, Check::Valid);
+        assert!(document.certificate_chain.is_invalid());
+        assert!(document.validity.check.is_invalid());
+        assert!(document.device_auth.is_invalid());
+        assert_eq!(document.digests[NAMESPACE]["family_name"], Check::Valid);
+
+        // The document signer certificate has expired by 2026-06-01.
+        let report = verify_device_response(
+            &response,
+            None,
+            &trust_store(1),
+            datetime!(2026-06-01 0:00 UTC),
+        );
+        let document = &report.documents[0];
+        assert!(document.certificate_chain.is_invalid());
+        assert!(matches!(document.device_auth, Check::Skipped(_)));
+    }
+
+    #[test]
+    fn issuer_signed() {
+        let response = device_response(session_transcript());
+        let document = &response.documents.unwrap()[0];
+        let report = verify_issuer_signed(
+            &document.issuer_signed,
+            &TrustStore::default(),
+            datetime!(2025-03-01 0:00 UTC),
+        );
+        assert!(report.is_valid());
+        assert_eq!(report.doc_type, DOC_TYPE);
+        assert!(matches!(report.certificate_chain, Check::Skipped(_)));
+        assert!(matches!(report.device_auth, Check::Skipped(_)));
+    }
+
+    /// Sign a certificate with the given issuer key.
+    fn sign_certificate(tbs_certificate: TbsCertificate, issuer_key: &SigningKey) -> Vec<u8> {
+        let signature: DerSignature = issuer_key.sign(&tbs_certificate.to_der().unwrap());
+        Certificate {
+            signature_algorithm: tbs_certificate.signature.clone(),
+            tbs_certificate,
+            signature: BitString::from_bytes(&signature.to_vec()).unwrap(),
+        }
+        .to_der()
+        .unwrap()
+    }
+
+    #[test]
+    fn document_signer_cannot_issue_certificates() {
+        let document_signer = document_signer(&iaca(1));
+        let mut tbs_certificate = parse_certificate(document_signer.to_der())
+            .unwrap()
+            .tbs_certificate;
+        // A second document signer certificate, issued by the first.
+        tbs_certificate.issuer = tbs_certificate.subject.clone();
+        tbs_certificate.subject = Name::from_str("CN=Rogue Document Signer,C=US").unwrap();
+        tbs_certificate.subject_public_key_info =
+            SubjectPublicKeyInfoOwned::from_key(*key(5).verifying_key()).unwrap();
+        let x5chain = X5Chain::builder()
+            .with_der(&sign_certificate(tbs_certificate, &key(2)))
+            .unwrap()
+            .with_der(document_signer.to_der())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let error = verify_chain(&x5chain, &trust_store(1), datetime!(2025-03-01 0:00 UTC))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("is not a CA"), "{error}");
+    }
+
+    #[test]
+    fn country_mismatch() {
+        let mut tbs_certificate = parse_certificate(document_signer(&iaca(1)).to_der())
+            .unwrap()
+            .tbs_certificate;
+        tbs_certificate.subject = Name::from_str("CN=Example Document Signer,C=CA").unwrap();
+        let x5chain = X5Chain::builder()
+            .with_der(&sign_certificate(tbs_certificate, &key(1)))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let error = verify_chain(&x5chain, &trust_store(1), datetime!(2025-03-01 0:00 UTC))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("does not match the country"), "{error}");
+    }
+
+    #[test]
+    fn p521_chain() {
+        let iaca = p521_iaca();
+        let document_signer = iaca
+            .document_signer()
+            .common_name("Example Document Signer".to_string())
+            .validity(
+                datetime!(2024-01-01 0:00 UTC),
+                datetime!(2026-01-01 0:00 UTC),
+            )
+            .build(key(2).verifying_key(), P521Signer(p521_key()))
+            .unwrap();
+        let trust_store = TrustStore::default().with_der(iaca.to_der()).unwrap();
+        let at = datetime!(2025-03-01 0:00 UTC);
+        verify_chain(&document_signer.x5chain().unwrap(), &trust_store, at).unwrap();
+
+        // The trusted IACA may also be included in the chain.
+        let x5chain = X5Chain::builder()
+            .with_der(document_signer.to_der())
+            .unwrap()
+            .with_der(iaca.to_der())
+            .unwrap()
+            .build()
+            .unwrap();
+        verify_chain(&x5chain, &trust_store, at).unwrap();
+    }
+}